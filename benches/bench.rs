@@ -4,6 +4,7 @@ extern crate kdtree;
 use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
 use kdtree::KdTree;
 use kdtree::distance::squared_euclidean;
+use kdtree::kernels::{Columns, Kernel};
 use std::collections::BTreeSet;
 
 fn deterministic_points(len: usize) -> (Vec<([f64; 3], f64)>, ([f64; 3], f64)) {
@@ -141,6 +142,52 @@ fn bench_within_count_2k_data_02_radius(c: &mut Criterion) {
     });
 }
 
+fn bench_leaf_scan_kernels(c: &mut Criterion) {
+    let mut group = c.benchmark_group("leaf_scan_3d_points");
+    for len in [16usize, 256] {
+        let (points, point) = deterministic_points(len);
+        let rows = points.iter().map(|(p, _)| p.map(|v| v as f32)).collect::<Vec<_>>();
+        let query = point.0.map(|v| v as f32);
+        let mut block: Columns = Columns::with_capacity(3, len);
+        let mut half: Columns<half::f16> = Columns::with_capacity(3, len);
+        for p in rows.iter() {
+            block.push(p).unwrap();
+            half.push(p).unwrap();
+        }
+        let mut out = Vec::with_capacity(len);
+        group.bench_with_input(BenchmarkId::new("scalar_rows", len), &len, |b, _| {
+            b.iter(|| {
+                out.clear();
+                out.extend(rows.iter().map(|p| squared_euclidean(p, &query)));
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("kernel_f32_columns", len), &len, |b, _| {
+            b.iter(|| Kernel::SquaredEuclidean.evaluate(&block, &query, &mut out).unwrap());
+        });
+        group.bench_with_input(BenchmarkId::new("kernel_f16_columns", len), &len, |b, _| {
+            b.iter(|| Kernel::SquaredEuclidean.evaluate(&half, &query, &mut out).unwrap());
+        });
+    }
+    group.finish();
+}
+
+fn bench_nearest_kernel_from_packed_kdtree(c: &mut Criterion) {
+    let (points, point) = deterministic_points(1000);
+    let query = point.0.map(|v| v as f32);
+    let mut kdtree = KdTree::with_packed_leaves(3, 16);
+    for (p, value) in points.iter() {
+        kdtree.add(p.map(|v| v as f32), *value).unwrap();
+    }
+    let mut group = c.benchmark_group("nearest_from_packed_kdtree_with_1k_3d_points");
+    group.bench_function("metric", |b| {
+        b.iter(|| kdtree.nearest(&query, 8, &squared_euclidean).unwrap());
+    });
+    group.bench_function("kernel", |b| {
+        b.iter(|| kdtree.nearest_kernel(&query, 8, Kernel::SquaredEuclidean).unwrap());
+    });
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_add_to_kdtree_with_1k_3d_points,
@@ -151,6 +198,8 @@ criterion_group!(
     bench_within_2k_data_02_radius,
     bench_within_count_2k_data_01_radius,
     bench_within_count_2k_data_02_radius,
+    bench_leaf_scan_kernels,
+    bench_nearest_kernel_from_packed_kdtree,
 );
 
 criterion_main!(benches);
@@ -0,0 +1,369 @@
+//! Clustering algorithms built on [`KdTree`] queries.
+//!
+//! [`emst`] computes a minimum spanning tree of a set of points with dual-tree
+//! Borůvka: every round finds the shortest edge leaving each component in a single
+//! traversal of pairs of nodes, skipping pairs that lie in one component or are
+//! farther apart than the edges found so far. Its edges, sorted by weight, are the
+//! merges of single-linkage clustering, which [`single_linkage`] cuts at a distance.
+//! [`dbscan`] clusters points by density with `within` and `within_count` queries.
+//!
+//! Points are given as a slice and referred to by their index in it. Distances are
+//! those of the metric, e.g. squared for
+//! [`squared_euclidean`](crate::distance::squared_euclidean).
+//!
+//! ```
+//! use kdtree::algorithms::{dbscan, emst, single_linkage};
+//! use kdtree::distance::squared_euclidean;
+//!
+//! let points = [[0.0, 0.0], [1.0, 0.0], [10.0, 0.0], [12.0, 0.0]];
+//! let edges = emst(&points, &squared_euclidean).unwrap();
+//! assert_eq!(edges, vec![(0, 1, 1.0), (2, 3, 4.0), (1, 2, 81.0)]);
+//! assert_eq!(single_linkage(points.len(), &edges, 4.0), vec![0, 0, 1, 1]);
+//!
+//! let labels = dbscan(&points, 1.0, 2, &squared_euclidean).unwrap();
+//! assert_eq!(labels, vec![Some(0), Some(0), None, None]);
+//! ```
+
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use crate::coordinate::{Coordinate, Distance};
+use crate::kdtree::{ErrorKind, KdTree, finite};
+
+/// Edges of a minimum spanning tree of `points` as `(a, b, weight)` with `a < b`,
+/// lightest first. Ties between equal weights are broken by the indices of the points,
+/// so the result doesn't depend on the order edges are found in.
+pub fn emst<A, U, D, F>(points: &[U], distance: &F) -> Result<Vec<(usize, usize, D)>, ErrorKind>
+where
+    A: Coordinate,
+    U: AsRef<[A]>,
+    D: Distance,
+    F: Fn(&[A], &[A]) -> D,
+{
+    let tree = index(points)?;
+    let Some(tree) = tree.as_ref() else {
+        return Ok(vec![]);
+    };
+    let flat = Flat::new(tree);
+    let mut components = UnionFind::new(points.len());
+    let mut edges = Vec::with_capacity(points.len() - 1);
+    while edges.len() + 1 < points.len() {
+        let component = (0..points.len()).map(|i| components.find(i)).collect::<Vec<_>>();
+        let mut round = Round {
+            flat: &flat,
+            node_component: flat.node_components(&component),
+            component,
+            bound: vec![D::MAX; flat.nodes.len()],
+            best: vec![None; points.len()],
+            distance,
+        };
+        round.visit(0, 0)?;
+        for (weight, a, b) in round.best.into_iter().flatten() {
+            if components.union(a, b) {
+                edges.push((a.min(b), a.max(b), weight));
+            }
+        }
+    }
+    edges.sort_by(|x, y| {
+        let by_weight = x.2.partial_cmp(&y.2).unwrap_or(Ordering::Equal);
+        by_weight.then((x.0, x.1).cmp(&(y.0, y.1)))
+    });
+    Ok(edges)
+}
+
+/// Cluster of each of `count` points after merging along the `edges` of weight at most
+/// `max_weight`, numbered from zero in order of each cluster's first point. With the
+/// edges of [`emst`] this is single-linkage clustering cut at `max_weight`.
+pub fn single_linkage<D: Distance>(count: usize, edges: &[(usize, usize, D)], max_weight: D) -> Vec<usize> {
+    let mut components = UnionFind::new(count);
+    for &(a, b, weight) in edges {
+        if weight <= max_weight {
+            components.union(a, b);
+        }
+    }
+    let mut labels = vec![usize::MAX; count];
+    let mut clusters = 0;
+    for i in 0..count {
+        let root = components.find(i);
+        if labels[root] == usize::MAX {
+            labels[root] = clusters;
+            clusters += 1;
+        }
+        labels[i] = labels[root];
+    }
+    labels
+}
+
+/// DBSCAN cluster of each point, numbered from zero, or `None` for noise. Points with
+/// at least `min_points` points within `radius`, themselves included, are core points;
+/// a cluster is a set of core points connected through each other's neighbourhoods
+/// together with the other points inside those. A point within reach of several
+/// clusters joins the first one found.
+pub fn dbscan<A, U, D, F>(
+    points: &[U],
+    radius: D,
+    min_points: usize,
+    distance: &F,
+) -> Result<Vec<Option<usize>>, ErrorKind>
+where
+    A: Coordinate,
+    U: AsRef<[A]>,
+    D: Distance,
+    F: Fn(&[A], &[A]) -> D,
+{
+    let mut labels = vec![None; points.len()];
+    let Some(tree) = index(points)? else {
+        return Ok(labels);
+    };
+    let mut clusters = 0;
+    for start in 0..points.len() {
+        if labels[start].is_some() || tree.within_count(points[start].as_ref(), radius, distance)? < min_points {
+            continue;
+        }
+        labels[start] = Some(clusters);
+        let mut pending = vec![start];
+        while let Some(core) = pending.pop() {
+            for (_, &neighbour) in tree.within(points[core].as_ref(), radius, distance)? {
+                if labels[neighbour].is_some() {
+                    continue;
+                }
+                labels[neighbour] = Some(clusters);
+                if tree.within_count(points[neighbour].as_ref(), radius, distance)? >= min_points {
+                    pending.push(neighbour);
+                }
+            }
+        }
+        clusters += 1;
+    }
+    Ok(labels)
+}
+
+/// Tree of the points with their indices as payloads, `None` when there are none
+fn index<A: Coordinate, U: AsRef<[A]>>(points: &[U]) -> Result<Option<KdTree<A, usize, &U>>, ErrorKind> {
+    let Some(first) = points.first() else {
+        return Ok(None);
+    };
+    let mut tree = KdTree::new(first.as_ref().len());
+    for (i, point) in points.iter().enumerate() {
+        tree.add(point, i)?;
+    }
+    Ok(Some(tree))
+}
+
+/// The non-empty nodes of a tree in pre-order, with the points below each one stored
+/// contiguously
+struct Flat<'t, A> {
+    nodes: Vec<Node<'t, A>>,
+    // coordinates and index of every point, leaf by leaf
+    points: Vec<(&'t [A], usize)>,
+}
+
+struct Node<'t, A> {
+    min_bounds: &'t [A],
+    max_bounds: &'t [A],
+    children: Vec<usize>,
+    points: Range<usize>,
+}
+
+impl<'t, A: Coordinate> Flat<'t, A> {
+    fn new<U: AsRef<[A]>>(tree: &'t KdTree<A, usize, U>) -> Self {
+        let mut flat = Flat {
+            nodes: vec![],
+            points: Vec::with_capacity(tree.size),
+        };
+        flat.push(tree);
+        flat
+    }
+
+    fn push<U: AsRef<[A]>>(&mut self, node: &'t KdTree<A, usize, U>) -> usize {
+        let index = self.nodes.len();
+        let start = self.points.len();
+        self.nodes.push(Node {
+            min_bounds: &node.min_bounds,
+            max_bounds: &node.max_bounds,
+            children: vec![],
+            points: start..start,
+        });
+        if node.is_leaf() {
+            let rows = KdTree::<A, usize, U>::rows(node.points.as_ref().unwrap(), &node.packed, node.dimensions);
+            self.points
+                .extend(rows.cycle().zip(node.bucket.as_ref().unwrap().iter().copied()));
+        } else {
+            for child in [node.left.as_ref().unwrap(), node.right.as_ref().unwrap()] {
+                if child.size > 0 {
+                    let child = self.push(child);
+                    self.nodes[index].children.push(child);
+                }
+            }
+        }
+        self.nodes[index].points.end = self.points.len();
+        index
+    }
+
+    /// The component shared by all points below each node, if there is one
+    fn node_components(&self, component: &[usize]) -> Vec<Option<usize>> {
+        let mut shared = vec![None; self.nodes.len()];
+        // children come after their parent in pre-order
+        for (index, node) in self.nodes.iter().enumerate().rev() {
+            shared[index] = if node.children.is_empty() {
+                let mut points = self.points[node.points.clone()].iter().map(|&(_, i)| component[i]);
+                let first = points.next();
+                first.filter(|&c| points.all(|other| other == c))
+            } else {
+                let mut children = node.children.iter().map(|&child| shared[child]);
+                let first = children.next().flatten();
+                first.filter(|&c| children.all(|other| other == Some(c)))
+            };
+        }
+        shared
+    }
+}
+
+/// State of one Borůvka round
+struct Round<'f, 't, A, D, F> {
+    flat: &'f Flat<'t, A>,
+    // component of each point, named by its root in the union-find
+    component: Vec<usize>,
+    node_component: Vec<Option<usize>>,
+    // the largest distance any point below a node still has to beat
+    bound: Vec<D>,
+    // shortest edge leaving each component, indexed by its root
+    best: Vec<Option<(D, usize, usize)>>,
+    distance: &'f F,
+}
+
+impl<A: Coordinate, D: Distance, F: Fn(&[A], &[A]) -> D> Round<'_, '_, A, D, F> {
+    /// Looks for shorter edges from points below `query` to points below `reference`
+    fn visit(&mut self, query: usize, reference: usize) -> Result<(), ErrorKind> {
+        let shared = self.node_component[query];
+        if shared.is_some() && shared == self.node_component[reference] {
+            return Ok(());
+        }
+        if self.box_distance(query, reference)? > self.bound[query] {
+            return Ok(());
+        }
+        let (q, r) = (&self.flat.nodes[query], &self.flat.nodes[reference]);
+        match (q.children.is_empty(), r.children.is_empty()) {
+            (true, true) => self.leaves(query, reference),
+            (false, reference_leaf) if reference_leaf || q.points.len() >= r.points.len() => {
+                for &child in &q.children {
+                    self.visit(child, reference)?;
+                }
+                let children = q.children.iter().map(|&child| self.bound[child]);
+                self.bound[query] = children.fold(D::ZERO, |a, b| if b > a { b } else { a });
+                Ok(())
+            }
+            _ => {
+                let mut children = r
+                    .children
+                    .iter()
+                    .map(|&child| Ok((self.box_distance(query, child)?, child)))
+                    .collect::<Result<Vec<_>, ErrorKind>>()?;
+                children.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+                for (_, child) in children {
+                    self.visit(query, child)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn leaves(&mut self, query: usize, reference: usize) -> Result<(), ErrorKind> {
+        let flat = self.flat;
+        let references = &flat.points[flat.nodes[reference].points.clone()];
+        let mut bound = D::ZERO;
+        for &(p, i) in &flat.points[flat.nodes[query].points.clone()] {
+            let component = self.component[i];
+            for &(other, j) in references {
+                if self.component[j] == component {
+                    continue;
+                }
+                let edge = (finite((self.distance)(p, other))?, i, j);
+                if shorter(edge, self.best[component]) {
+                    self.best[component] = Some(edge);
+                }
+            }
+            let beat = self.best[component].map_or(D::MAX, |(d, _, _)| d);
+            if beat > bound {
+                bound = beat;
+            }
+        }
+        self.bound[query] = bound;
+        Ok(())
+    }
+
+    /// Distance between the closest points of two nodes' bounding boxes
+    fn box_distance(&self, a: usize, b: usize) -> Result<D, ErrorKind> {
+        let (a, b) = (&self.flat.nodes[a], &self.flat.nodes[b]);
+        let mut p1 = Vec::with_capacity(a.min_bounds.len());
+        let mut p2 = Vec::with_capacity(a.min_bounds.len());
+        let axes = a
+            .min_bounds
+            .iter()
+            .zip(a.max_bounds)
+            .zip(b.min_bounds.iter().zip(b.max_bounds));
+        for ((&l1, &h1), (&l2, &h2)) in axes {
+            let (x1, x2) = if h1 < l2 {
+                (h1, l2)
+            } else if h2 < l1 {
+                (l1, h2)
+            } else {
+                let x = if l1 > l2 { l1 } else { l2 };
+                (x, x)
+            };
+            p1.push(x1);
+            p2.push(x2);
+        }
+        finite((self.distance)(&p1, &p2))
+    }
+}
+
+/// Whether `edge` comes before `best`, by weight and then by the indices of its points
+fn shorter<D: Distance>(edge: (D, usize, usize), best: Option<(D, usize, usize)>) -> bool {
+    let Some(best) = best else {
+        return true;
+    };
+    let ends = |(_, a, b): (D, usize, usize)| (a.min(b), a.max(b));
+    match edge.0.partial_cmp(&best.0) {
+        Some(Ordering::Less) => true,
+        Some(Ordering::Equal) => ends(edge) < ends(best),
+        _ => false,
+    }
+}
+
+/// Disjoint sets of point indices
+struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(count: usize) -> Self {
+        UnionFind {
+            parent: (0..count).collect(),
+            size: vec![1; count],
+        }
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+
+    /// Merges the sets of `a` and `b`, returning whether they were apart
+    fn union(&mut self, a: usize, b: usize) -> bool {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+        true
+    }
+}
@@ -0,0 +1,365 @@
+//! Queries on trees archived with [rkyv](https://docs.rs/rkyv).
+//!
+//! With the `rkyv` feature, [`KdTree`](crate::KdTree) implements rkyv's `Archive`,
+//! `Serialize` and `Deserialize`. An archive written with `rkyv::to_bytes` can be
+//! opened with `rkyv::access`, which checks the bytes once, and then queried in
+//! place without deserializing any node:
+//!
+//! ```
+//! use kdtree::KdTree;
+//! use kdtree::distance::squared_euclidean;
+//! use kdtree::kdtree::ArchivedKdTree;
+//!
+//! let mut tree: KdTree<f64, u32, [f64; 2]> = KdTree::new(2);
+//! tree.add([1.0, 2.0], 7).unwrap();
+//!
+//! let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&tree).unwrap();
+//! let archived = rkyv::access::<ArchivedKdTree<f64, u32, [f64; 2]>, rkyv::rancor::Error>(&bytes).unwrap();
+//! let nearest = archived.nearest(&[0.0, 0.0], 1, &squared_euclidean).unwrap();
+//! assert_eq!(nearest[0].0, 5.0);
+//! assert_eq!(nearest[0].1.to_native(), 7);
+//! ```
+//!
+//! Results borrow the archived payloads. `rkyv::access` only checks the bytes for
+//! memory safety, so queries check the nodes they visit and fail with
+//! [`ErrorKind::Corrupted`] on an archive whose structure is inconsistent.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use rkyv::Archive;
+
+use crate::coordinate::{Coordinate, Distance};
+use crate::heap_element::HeapElement;
+use crate::kdtree::{ArchivedKdTree, ClosestFirst, ErrorKind, check_point, finite};
+
+/// A node of an archive that passed the checks queries rely on
+enum Node<'b, A: Archive, T: Archive, U: AsRef<[A]> + Archive> {
+    Stem {
+        split_dimension: usize,
+        split_value: A,
+        left: &'b ArchivedKdTree<A, T, U>,
+        right: &'b ArchivedKdTree<A, T, U>,
+    },
+    Leaf {
+        points: &'b [U::Archived],
+        bucket: &'b [T::Archived],
+        coincident: bool,
+    },
+}
+
+impl<A, T, U> ArchivedKdTree<A, T, U>
+where
+    A: Coordinate + Archive,
+    A::Archived: Copy + Into<A>,
+    T: Archive,
+    U: AsRef<[A]> + Archive,
+    U::Archived: AsRef<[A::Archived]>,
+{
+    pub fn size(&self) -> usize {
+        self.size.to_native() as usize
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.dimensions.to_native() as usize
+    }
+
+    pub fn nearest<D, F>(&self, point: &[A], num: usize, distance: &F) -> Result<Vec<(D, &T::Archived)>, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        check_point(self.dimensions(), point)?;
+        let num = std::cmp::min(num, self.size());
+        if num == 0 {
+            return Ok(vec![]);
+        }
+        let mut pending = BinaryHeap::new();
+        let mut evaluated = BinaryHeap::<HeapElement<D, &T::Archived>>::new();
+        let mut scratch = Vec::with_capacity(point.len());
+        pending.push(Reverse(HeapElement {
+            distance: D::ZERO,
+            element: self,
+        }));
+        while let Some(next) = pending.peek() {
+            let bound = next.0.distance;
+            if evaluated.len() == num && evaluated.peek().is_some_and(|x| bound > x.distance) {
+                break;
+            }
+            Self::nearest_step(point, num, D::MAX, distance, &mut pending, &mut evaluated, &mut scratch)?;
+        }
+        Ok(evaluated
+            .into_sorted_vec()
+            .into_iter()
+            .take(num)
+            .map(Into::into)
+            .collect())
+    }
+
+    pub fn within<D, F>(&self, point: &[A], radius: D, distance: &F) -> Result<Vec<(D, &T::Archived)>, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        check_point(self.dimensions(), point)?;
+        if self.size() == 0 {
+            return Ok(vec![]);
+        }
+        let evaluated = self.evaluated_heap(point, radius, distance)?;
+        Ok(evaluated.into_iter().map(Into::into).collect())
+    }
+
+    pub fn within_count<D, F>(&self, point: &[A], radius: D, distance: &F) -> Result<usize, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        check_point(self.dimensions(), point)?;
+        if self.size() == 0 {
+            return Ok(0);
+        }
+        let evaluated = self.evaluated_heap(point, radius, distance)?;
+        Ok(evaluated.len())
+    }
+
+    pub fn bounding_box(&self, min_bounds: &[A], max_bounds: &[A]) -> Result<Vec<&T::Archived>, ErrorKind> {
+        check_point(self.dimensions(), min_bounds)?;
+        check_point(self.dimensions(), max_bounds)?;
+        if let Some(axis) = min_bounds.iter().zip(max_bounds).position(|(l, h)| l > h) {
+            return Err(ErrorKind::InvalidBoundingBox { axis });
+        }
+        if self.size() == 0 {
+            return Ok(vec![]);
+        }
+        let in_bounding_box = |p: &U::Archived| {
+            let p = p.as_ref().iter().map(|&v| v.into());
+            min_bounds
+                .iter()
+                .zip(max_bounds)
+                .zip(p)
+                .all(|((&l, &h), v)| v >= l && v <= h)
+        };
+        let mut pending = vec![self];
+        let mut evaluated = vec![];
+        while let Some(curr) = pending.pop() {
+            match curr.node()? {
+                Node::Leaf {
+                    points,
+                    bucket,
+                    coincident: true,
+                } => {
+                    if in_bounding_box(&points[0]) {
+                        evaluated.extend(bucket.iter());
+                    }
+                }
+                Node::Leaf { points, bucket, .. } => {
+                    for (p, b) in points.iter().zip(bucket.iter()) {
+                        if in_bounding_box(p) {
+                            evaluated.push(b);
+                        }
+                    }
+                }
+                Node::Stem {
+                    split_dimension,
+                    split_value,
+                    left,
+                    right,
+                } => {
+                    if min_bounds[split_dimension] < split_value {
+                        pending.push(left);
+                    }
+                    if max_bounds[split_dimension] >= split_value {
+                        pending.push(right);
+                    }
+                }
+            }
+        }
+        Ok(evaluated)
+    }
+
+    // ============================================================================
+    // === TRAVERSAL HELPERS ===
+    // ============================================================================
+    fn nearest_step<'b, D, F>(
+        point: &[A],
+        num: usize,
+        max_dist: D,
+        distance: &F,
+        pending: &mut ClosestFirst<D, &'b Self>,
+        evaluated: &mut BinaryHeap<HeapElement<D, &'b T::Archived>>,
+        scratch: &mut Vec<A>,
+    ) -> Result<(), ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        let Some(Reverse(HeapElement { element: mut curr, .. })) = pending.pop() else {
+            return Ok(());
+        };
+        let evaluated_dist = match evaluated.peek() {
+            Some(worst) if evaluated.len() == num && worst.distance < max_dist => worst.distance,
+            _ => max_dist,
+        };
+
+        let (points, bucket, coincident) = loop {
+            let candidate;
+            match curr.node()? {
+                Node::Leaf {
+                    points,
+                    bucket,
+                    coincident,
+                } => break (points, bucket, coincident),
+                Node::Stem {
+                    split_dimension,
+                    split_value,
+                    left,
+                    right,
+                } => {
+                    if point[split_dimension] < split_value {
+                        candidate = right;
+                        curr = left;
+                    } else {
+                        candidate = left;
+                        curr = right;
+                    }
+                }
+            }
+            if candidate.size() == 0 {
+                continue;
+            }
+            let candidate_to_space = finite(candidate.distance_to_space(point, distance, scratch)?)?;
+            if candidate_to_space <= evaluated_dist {
+                pending.push(Reverse(HeapElement {
+                    distance: candidate_to_space,
+                    element: candidate,
+                }));
+            }
+        };
+
+        let mut shared = None;
+        for (p, data) in points.iter().cycle().zip(bucket.iter()) {
+            let mut evaluate = || {
+                scratch.clear();
+                scratch.extend(p.as_ref().iter().map(|&v| v.into()));
+                distance(point, scratch)
+            };
+            let dist = if coincident {
+                *shared.get_or_insert_with(evaluate)
+            } else {
+                evaluate()
+            };
+            let element = HeapElement {
+                distance: dist,
+                element: data,
+            };
+            if finite(element.distance)? <= max_dist {
+                if evaluated.len() < num {
+                    evaluated.push(element);
+                } else if evaluated.peek().is_some_and(|worst| element < *worst) {
+                    evaluated.pop();
+                    evaluated.push(element);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn evaluated_heap<D, F>(
+        &self,
+        point: &[A],
+        radius: D,
+        distance: &F,
+    ) -> Result<BinaryHeap<HeapElement<D, &T::Archived>>, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        let mut pending = BinaryHeap::new();
+        let mut evaluated = BinaryHeap::new();
+        let mut scratch = Vec::with_capacity(point.len());
+        pending.push(Reverse(HeapElement {
+            distance: D::ZERO,
+            element: self,
+        }));
+        while pending.peek().is_some_and(|next| next.0.distance <= radius) {
+            Self::nearest_step(
+                point,
+                self.size(),
+                radius,
+                distance,
+                &mut pending,
+                &mut evaluated,
+                &mut scratch,
+            )?;
+        }
+        Ok(evaluated)
+    }
+
+    /// Distance from `point` to the closest point of this node's bounds, using
+    /// `scratch` for the clamped coordinates
+    fn distance_to_space<D, F>(&self, point: &[A], distance: &F, scratch: &mut Vec<A>) -> Result<D, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        if self.min_bounds.len() != point.len() || self.max_bounds.len() != point.len() {
+            return Err(ErrorKind::Corrupted("bounds have the wrong number of dimensions"));
+        }
+        scratch.clear();
+        let bounds = self.min_bounds.iter().zip(self.max_bounds.iter());
+        scratch.extend(point.iter().zip(bounds).map(|(&v, (&l, &h))| {
+            let (l, h) = (l.into(), h.into());
+            if v > h {
+                h
+            } else if v < l {
+                l
+            } else {
+                v
+            }
+        }));
+        Ok(distance(point, scratch))
+    }
+
+    /// Checks what queries rely on for a node, since `rkyv::access` accepts any archive
+    /// that is memory safe: a stem needs its split and children of the same dimensions,
+    /// and a leaf needs one point per entry, or a single one when it is coincident.
+    fn node(&self) -> Result<Node<'_, A, T, U>, ErrorKind> {
+        let dims = self.dimensions();
+        match (
+            self.left.as_ref(),
+            self.right.as_ref(),
+            self.points.as_ref(),
+            self.bucket.as_ref(),
+        ) {
+            (Some(left), Some(right), None, None) => {
+                let (Some(split_dimension), Some(&split_value)) =
+                    (self.split_dimension.as_ref(), self.split_value.as_ref())
+                else {
+                    return Err(ErrorKind::Corrupted("stem is missing its split"));
+                };
+                let split_dimension = split_dimension.to_native() as usize;
+                if split_dimension >= dims || left.dimensions() != dims || right.dimensions() != dims {
+                    return Err(ErrorKind::Corrupted("stem disagrees with the tree dimensions"));
+                }
+                Ok(Node::Stem {
+                    split_dimension,
+                    split_value: split_value.into(),
+                    left,
+                    right,
+                })
+            }
+            (None, None, Some(points), Some(bucket)) => {
+                let rows = if self.coincident { 1 } else { bucket.len() };
+                if points.len() != rows || points.iter().any(|p| p.as_ref().len() != dims) {
+                    return Err(ErrorKind::Corrupted("leaf points do not match its entries"));
+                }
+                Ok(Node::Leaf {
+                    points: points.as_slice(),
+                    bucket: bucket.as_slice(),
+                    coincident: self.coincident,
+                })
+            }
+            _ => Err(ErrorKind::Corrupted("neither a complete leaf nor a complete stem")),
+        }
+    }
+}
@@ -0,0 +1,765 @@
+//! Compact binary on-disk format for [`KdTree`].
+//!
+//! A tree is stored as a fixed 64 byte header followed by flattened sections.
+//! Every section starts on an 8 byte boundary and all values are little-endian.
+//!
+//! | section  | contents                                                         |
+//! |----------|------------------------------------------------------------------|
+//! | header   | magic, version, scalar tag, dimensions, capacity, section counts |
+//! | nodes    | one 56 byte record per node, in pre-order                        |
+//! | bounds   | split value, min bounds and max bounds of every node             |
+//! | points   | coordinates of every stored point, row-major                     |
+//! | offsets  | start of every payload within the payload section, plus the end  |
+//! | payloads | data of every entry, encoded with [`Payload`]                    |
+//!
+//! Loading validates the whole structure, so corrupted, truncated or
+//! version-mismatched files are rejected with a [`FormatError`] instead of
+//! producing a tree that panics on its first query.
+//!
+//! ```
+//! use kdtree::KdTree;
+//!
+//! let mut tree: KdTree<f64, u32, [f64; 2]> = KdTree::new(2);
+//! tree.add([1.0, 2.0], 7).unwrap();
+//!
+//! let mut bytes = Vec::new();
+//! tree.write_to(&mut bytes).unwrap();
+//! let restored: KdTree<f64, u32, [f64; 2]> = KdTree::read_from(&bytes[..]).unwrap();
+//! assert_eq!(restored.get(&[1.0, 2.0]).unwrap(), Some(&7));
+//! ```
+
+use std::io::{self, Read, Write};
+use std::ops::Range;
+
+use thiserror::Error;
+
+use crate::coordinate::Coordinate;
+use crate::kdtree::{KdTree, max_depth};
+
+/// First eight bytes of every file
+pub const MAGIC: [u8; 8] = *b"KDTREE\0\0";
+/// Format version written by this crate, files with another version are rejected
+pub const VERSION: u32 = 1;
+
+pub(crate) const HEADER_LEN: usize = 64;
+pub(crate) const NODE_LEN: usize = 56;
+const LEAF: u32 = 1;
+const COINCIDENT: u32 = 2;
+
+#[derive(Error, Debug)]
+pub enum FormatError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("not a kdtree file")]
+    BadMagic,
+    #[error("unsupported format version {found}, expected {expected}")]
+    UnsupportedVersion { found: u32, expected: u32 },
+    #[error("scalar type mismatch: file stores tag {found}, expected {expected}")]
+    ScalarMismatch { found: u8, expected: u8 },
+    #[error("corrupted file: {0}")]
+    Corrupted(&'static str),
+    #[error("buffer is not aligned to {required} bytes")]
+    Misaligned { required: usize },
+    #[error("buffers can only be viewed in place on little-endian targets")]
+    UnsupportedEndianness,
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Coordinate types that can be stored in the binary format. The trait is
+/// sealed because [`KdTreeView`](crate::view::KdTreeView) reinterprets stored
+/// bytes as values of these types in place.
+pub trait Scalar: Copy + sealed::Sealed {
+    /// Identifies the scalar type in the file header
+    const TAG: u8;
+    /// Encoded width in bytes
+    const SIZE: usize;
+
+    fn write_le(self, out: &mut Vec<u8>);
+
+    /// Decodes a value from exactly `SIZE` bytes
+    fn read_le(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_scalar {
+    ($($t:ty => $tag:expr),*) => {
+        $(
+            impl sealed::Sealed for $t {}
+
+            impl Scalar for $t {
+                const TAG: u8 = $tag;
+                const SIZE: usize = std::mem::size_of::<$t>();
+
+                fn write_le(self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn read_le(bytes: &[u8]) -> Self {
+                    let mut raw = [0; std::mem::size_of::<$t>()];
+                    raw.copy_from_slice(bytes);
+                    <$t>::from_le_bytes(raw)
+                }
+            }
+        )*
+    };
+}
+
+impl_scalar!(f32 => 1, f64 => 2, i32 => 3, u32 => 4, i64 => 5, u64 => 6);
+
+/// Data types that can be stored alongside points in the binary format
+pub trait Payload: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Decodes a value from exactly the bytes produced by `encode`, returning
+    /// `None` for malformed input
+    fn decode(bytes: &[u8]) -> Option<Self>;
+}
+
+macro_rules! impl_payload {
+    ($($t:ty),*) => {
+        $(
+            impl Payload for $t {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn decode(bytes: &[u8]) -> Option<Self> {
+                    bytes.try_into().ok().map(<$t>::from_le_bytes)
+                }
+            }
+        )*
+    };
+}
+
+impl_payload!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+impl Payload for usize {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as u64).encode(out)
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        u64::decode(bytes).and_then(|v| v.try_into().ok())
+    }
+}
+
+impl Payload for isize {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as i64).encode(out)
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        i64::decode(bytes).and_then(|v| v.try_into().ok())
+    }
+}
+
+impl Payload for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8)
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0] => Some(false),
+            [1] => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl Payload for char {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as u32).encode(out)
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        u32::decode(bytes).and_then(char::from_u32)
+    }
+}
+
+impl Payload for () {
+    fn encode(&self, _: &mut Vec<u8>) {}
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        bytes.is_empty().then_some(())
+    }
+}
+
+impl Payload for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.as_bytes())
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+impl Payload for Vec<u8> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self)
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        Some(bytes.to_vec())
+    }
+}
+
+// ============================================================================
+// === LAYOUT ===
+// ============================================================================
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Header {
+    pub(crate) scalar: u8,
+    pub(crate) dimensions: usize,
+    pub(crate) capacity: usize,
+    pub(crate) node_count: usize,
+    pub(crate) point_rows: usize,
+    pub(crate) entry_count: usize,
+    pub(crate) payload_len: usize,
+}
+
+impl Header {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&[self.scalar, 0, 0, 0]);
+        for value in [
+            self.dimensions,
+            self.capacity,
+            self.node_count,
+            self.point_rows,
+            self.entry_count,
+            self.payload_len,
+        ] {
+            out.extend_from_slice(&(value as u64).to_le_bytes());
+        }
+    }
+
+    fn read(bytes: &[u8]) -> Result<Self, FormatError> {
+        if bytes.len() < MAGIC.len() || bytes[..MAGIC.len()] != MAGIC {
+            return Err(FormatError::BadMagic);
+        }
+        if bytes.len() < HEADER_LEN {
+            return Err(FormatError::Corrupted("truncated header"));
+        }
+        let version = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+        if version != VERSION {
+            return Err(FormatError::UnsupportedVersion {
+                found: version,
+                expected: VERSION,
+            });
+        }
+        let field = |i: usize| {
+            let start = 16 + 8 * i;
+            let value = u64::from_le_bytes(bytes[start..start + 8].try_into().unwrap());
+            usize::try_from(value).map_err(|_| FormatError::Corrupted("header field out of range"))
+        };
+        Ok(Header {
+            scalar: bytes[12],
+            dimensions: field(0)?,
+            capacity: field(1)?,
+            node_count: field(2)?,
+            point_rows: field(3)?,
+            entry_count: field(4)?,
+            payload_len: field(5)?,
+        })
+    }
+
+    /// Byte ranges of the nodes, bounds, points, offsets and payloads sections
+    fn sections(&self, scalar_size: usize) -> Option<[Range<usize>; 5]> {
+        let nodes = self.node_count.checked_mul(NODE_LEN)?;
+        let bounds = self
+            .dimensions
+            .checked_mul(2)?
+            .checked_add(1)?
+            .checked_mul(self.node_count)?
+            .checked_mul(scalar_size)?;
+        let points = self.point_rows.checked_mul(self.dimensions)?.checked_mul(scalar_size)?;
+        let offsets = self.entry_count.checked_add(1)?.checked_mul(8)?;
+        let mut start = HEADER_LEN;
+        let mut section = |len: usize| -> Option<Range<usize>> {
+            let range = start..start.checked_add(len)?;
+            start = align(range.end)?;
+            Some(range)
+        };
+        Some([
+            section(nodes)?,
+            section(bounds)?,
+            section(points)?,
+            section(offsets)?,
+            section(self.payload_len)?,
+        ])
+    }
+}
+
+fn align(offset: usize) -> Option<usize> {
+    offset.checked_add(7).map(|end| end & !7)
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct NodeRecord {
+    flags: u32,
+    pub(crate) split_dimension: u32,
+    pub(crate) left: u64,
+    pub(crate) right: u64,
+    pub(crate) size: u64,
+    pub(crate) point_start: u64,
+    pub(crate) entry_start: u64,
+    pub(crate) len: u64,
+}
+
+impl NodeRecord {
+    pub(crate) fn is_leaf(&self) -> bool {
+        self.flags & LEAF != 0
+    }
+
+    pub(crate) fn is_coincident(&self) -> bool {
+        self.flags & COINCIDENT != 0
+    }
+
+    /// Number of coordinate rows stored for a leaf
+    pub(crate) fn rows(&self) -> u64 {
+        if self.is_coincident() { 1 } else { self.len }
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.flags.to_le_bytes());
+        out.extend_from_slice(&self.split_dimension.to_le_bytes());
+        for value in [
+            self.left,
+            self.right,
+            self.size,
+            self.point_start,
+            self.entry_start,
+            self.len,
+        ] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fn read(bytes: &[u8]) -> Self {
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+        NodeRecord {
+            flags: u32_at(0),
+            split_dimension: u32_at(4),
+            left: u64_at(8),
+            right: u64_at(16),
+            size: u64_at(24),
+            point_start: u64_at(32),
+            entry_start: u64_at(40),
+            len: u64_at(48),
+        }
+    }
+}
+
+/// Borrowed view over the sections of an encoded tree
+pub(crate) struct Layout<'a> {
+    pub(crate) header: Header,
+    nodes: &'a [u8],
+    pub(crate) bounds: &'a [u8],
+    pub(crate) points: &'a [u8],
+    offsets: &'a [u8],
+    payloads: &'a [u8],
+}
+
+impl<'a> Layout<'a> {
+    /// Splits `bytes` into sections after checking the header against the scalar type `A`
+    pub(crate) fn parse<A: Scalar>(bytes: &'a [u8]) -> Result<Self, FormatError> {
+        let header = Header::read(bytes)?;
+        if header.scalar != A::TAG {
+            return Err(FormatError::ScalarMismatch {
+                found: header.scalar,
+                expected: A::TAG,
+            });
+        }
+        let [nodes, bounds, points, offsets, payloads] = header
+            .sections(A::SIZE)
+            .ok_or(FormatError::Corrupted("section sizes overflow"))?;
+        if bytes.len() < payloads.end {
+            return Err(FormatError::Corrupted("truncated file"));
+        }
+        Ok(Layout {
+            header,
+            nodes: &bytes[nodes],
+            bounds: &bytes[bounds],
+            points: &bytes[points],
+            offsets: &bytes[offsets],
+            payloads: &bytes[payloads],
+        })
+    }
+
+    pub(crate) fn node(&self, index: usize) -> NodeRecord {
+        NodeRecord::read(&self.nodes[index * NODE_LEN..(index + 1) * NODE_LEN])
+    }
+
+    /// Scalar `slot` of a node's bounds record: the split value followed by the min and max bounds
+    fn bound<A: Scalar>(&self, index: usize, slot: usize) -> A {
+        let start = (index * (2 * self.header.dimensions + 1) + slot) * A::SIZE;
+        A::read_le(&self.bounds[start..start + A::SIZE])
+    }
+
+    fn split_value<A: Scalar>(&self, index: usize) -> A {
+        self.bound(index, 0)
+    }
+
+    fn min_bound<A: Scalar>(&self, index: usize, axis: usize) -> A {
+        self.bound(index, 1 + axis)
+    }
+
+    fn max_bound<A: Scalar>(&self, index: usize, axis: usize) -> A {
+        self.bound(index, 1 + self.header.dimensions + axis)
+    }
+
+    fn coordinate<A: Scalar>(&self, row: usize, axis: usize) -> A {
+        let start = (row * self.header.dimensions + axis) * A::SIZE;
+        A::read_le(&self.points[start..start + A::SIZE])
+    }
+
+    fn offset(&self, entry: usize) -> Result<usize, FormatError> {
+        let offset = u64::from_le_bytes(self.offsets[entry * 8..entry * 8 + 8].try_into().unwrap());
+        usize::try_from(offset).map_err(|_| FormatError::Corrupted("payload offset out of range"))
+    }
+
+    pub(crate) fn payload(&self, entry: usize) -> Result<&'a [u8], FormatError> {
+        self.payloads
+            .get(self.offset(entry)?..self.offset(entry + 1)?)
+            .ok_or(FormatError::Corrupted("payload offsets out of range"))
+    }
+
+    /// Checks every structural invariant queries rely on, so that a validated
+    /// layout can be traversed without further checks
+    pub(crate) fn validate<A: Coordinate + Scalar>(&self) -> Result<(), FormatError> {
+        let header = &self.header;
+        let dims = header.dimensions;
+        if header.node_count == 0 {
+            return Err(FormatError::Corrupted("missing root node"));
+        }
+        if self.offset(0)? != 0 || self.offset(header.entry_count)? != header.payload_len {
+            return Err(FormatError::Corrupted(
+                "payload offsets do not cover the payload section",
+            ));
+        }
+        for entry in 0..header.entry_count {
+            if self.offset(entry)? > self.offset(entry + 1)? {
+                return Err(FormatError::Corrupted("payload offsets are not increasing"));
+            }
+        }
+        for index in 0..header.node_count {
+            let slots = 2 * dims + 1;
+            if (0..slots).any(|slot| self.bound::<A>(index, slot).non_finite().is_some()) {
+                return Err(FormatError::Corrupted("non-finite node bounds"));
+            }
+        }
+
+        let max_depth = max_depth(header.entry_count, header.capacity);
+        let mut stack = vec![(0usize, 0usize)];
+        let mut visited = 0;
+        let mut next_row = 0;
+        let mut next_entry = 0;
+        while let Some((index, depth)) = stack.pop() {
+            if index != visited {
+                return Err(FormatError::Corrupted("nodes are not stored in pre-order"));
+            }
+            visited += 1;
+            let node = self.node(index);
+            if node.is_leaf() {
+                let len = node.len as usize;
+                let rows = node.rows() as usize;
+                if node.size != node.len
+                    || node.entry_start as usize != next_entry
+                    || node.point_start as usize != next_row
+                {
+                    return Err(FormatError::Corrupted("leaf ranges are inconsistent"));
+                }
+                if (node.is_coincident() && len < 2) || (!node.is_coincident() && len > header.capacity) {
+                    return Err(FormatError::Corrupted("leaf holds an invalid number of entries"));
+                }
+                next_entry = next_entry
+                    .checked_add(len)
+                    .filter(|&end| end <= header.entry_count)
+                    .ok_or(FormatError::Corrupted("leaf entries exceed the entry count"))?;
+                next_row = next_row
+                    .checked_add(rows)
+                    .filter(|&end| end <= header.point_rows)
+                    .ok_or(FormatError::Corrupted("leaf points exceed the point count"))?;
+                for row in next_row - rows..next_row {
+                    for axis in 0..dims {
+                        let v: A = self.coordinate(row, axis);
+                        if v.non_finite().is_some()
+                            || v < self.min_bound(index, axis)
+                            || v > self.max_bound(index, axis)
+                        {
+                            return Err(FormatError::Corrupted("point outside of its leaf bounds"));
+                        }
+                    }
+                }
+            } else {
+                let (left, right) = (node.left as usize, node.right as usize);
+                let split_dimension = node.split_dimension as usize;
+                if left >= header.node_count || right >= header.node_count || split_dimension >= dims {
+                    return Err(FormatError::Corrupted("stem references are out of range"));
+                }
+                let (left_node, right_node) = (self.node(left), self.node(right));
+                if left_node.size.checked_add(right_node.size) != Some(node.size) {
+                    return Err(FormatError::Corrupted("stem size does not match its children"));
+                }
+                let split: A = self.split_value(index);
+                if (left_node.size > 0 && self.max_bound::<A>(left, split_dimension) >= split)
+                    || (right_node.size > 0 && self.min_bound::<A>(right, split_dimension) < split)
+                {
+                    return Err(FormatError::Corrupted(
+                        "children are not partitioned by the split value",
+                    ));
+                }
+                for child in [left, right] {
+                    if self.node(child).size > 0
+                        && (0..dims).any(|axis| {
+                            self.min_bound::<A>(child, axis) < self.min_bound(index, axis)
+                                || self.max_bound::<A>(child, axis) > self.max_bound(index, axis)
+                        })
+                    {
+                        return Err(FormatError::Corrupted("child bounds exceed their parent"));
+                    }
+                }
+                if depth == max_depth {
+                    return Err(FormatError::Corrupted("stems are nested deeper than splits reach"));
+                }
+                stack.push((right, depth + 1));
+                stack.push((left, depth + 1));
+            }
+        }
+        if visited != header.node_count || next_row != header.point_rows || next_entry != header.entry_count {
+            return Err(FormatError::Corrupted("unreferenced nodes, points or entries"));
+        }
+        if self.node(0).size as usize != header.entry_count {
+            return Err(FormatError::Corrupted("root size does not match the entry count"));
+        }
+        Ok(())
+    }
+}
+
+/// Reinterprets a section as native scalars without copying
+pub(crate) fn cast_section<A: Scalar>(bytes: &[u8]) -> Result<&[A], FormatError> {
+    if cfg!(target_endian = "big") {
+        return Err(FormatError::UnsupportedEndianness);
+    }
+    let required = std::mem::align_of::<A>();
+    if bytes.as_ptr().align_offset(required) != 0 {
+        return Err(FormatError::Misaligned { required });
+    }
+    // SAFETY: `Scalar` is sealed and implemented only for `f32`, `f64`, `i32`, `u32`, `i64`
+    // and `u64`. All are plain-old-data primitives of `SIZE` bytes with no invalid bit
+    // patterns, stored little-endian like the host. Their alignment is at most 8, which the
+    // 8 byte alignment of every section guarantees once the buffer itself is aligned, as
+    // checked above. The slice covers `len / SIZE` whole values.
+    Ok(unsafe { std::slice::from_raw_parts(bytes.as_ptr().cast::<A>(), bytes.len() / A::SIZE) })
+}
+
+// ============================================================================
+// === READING AND WRITING ===
+// ============================================================================
+
+impl<A: Coordinate + Scalar, T: Payload, U: AsRef<[A]>> KdTree<A, T, U> {
+    /// Writes the tree in the compact binary format. Wrap unbuffered writers such
+    /// as files in a `BufWriter`, the tree is written in many small chunks.
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<(), FormatError> {
+        let mut nodes = Vec::new();
+        let mut records = Vec::new();
+        let (mut rows, mut entries) = (0, 0);
+        flatten(self, &mut nodes, &mut records, &mut rows, &mut entries);
+
+        let mut offsets = Vec::with_capacity(self.size + 1);
+        let mut payloads = Vec::new();
+        for node in nodes.iter().filter(|node| node.is_leaf()) {
+            for data in node.bucket.as_ref().unwrap() {
+                offsets.push(payloads.len() as u64);
+                data.encode(&mut payloads);
+            }
+        }
+        offsets.push(payloads.len() as u64);
+
+        let header = Header {
+            scalar: A::TAG,
+            dimensions: self.dimensions,
+            capacity: self.capacity,
+            node_count: records.len(),
+            point_rows: rows as usize,
+            entry_count: entries as usize,
+            payload_len: payloads.len(),
+        };
+        let mut out = SectionWriter {
+            writer,
+            written: 0,
+            buf: Vec::new(),
+        };
+        header.write(&mut out.buf);
+        out.flush()?;
+        for record in records.iter() {
+            record.write(&mut out.buf);
+        }
+        out.finish_section()?;
+        for node in nodes.iter() {
+            match node.split_value {
+                Some(split_value) => split_value.write_le(&mut out.buf),
+                // leaves have no split, zero bits read back as zero for every scalar type
+                None => out.buf.resize(out.buf.len() + A::SIZE, 0),
+            }
+            for v in node.min_bounds.iter().chain(node.max_bounds.iter()) {
+                v.write_le(&mut out.buf);
+            }
+        }
+        out.finish_section()?;
+        for node in nodes.iter().filter(|node| node.is_leaf()) {
+            let points = node.points.as_ref().unwrap();
+            let rows = if node.coincident { &points[..1] } else { &points[..] };
+            for p in rows {
+                for v in p.as_ref() {
+                    v.write_le(&mut out.buf);
+                }
+            }
+            out.flush()?;
+        }
+        out.finish_section()?;
+        for offset in offsets {
+            out.buf.extend_from_slice(&offset.to_le_bytes());
+        }
+        out.finish_section()?;
+        out.buf = payloads;
+        out.finish_section()
+    }
+
+    /// Reads a tree written by [`KdTree::write_to`], validating its structure
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, FormatError>
+    where
+        U: for<'a> TryFrom<&'a [A]>,
+    {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let layout = Layout::parse::<A>(&bytes)?;
+        layout.validate::<A>()?;
+        build(&layout)
+    }
+}
+
+fn flatten<'a, A, T, U: AsRef<[A]>>(
+    node: &'a KdTree<A, T, U>,
+    nodes: &mut Vec<&'a KdTree<A, T, U>>,
+    records: &mut Vec<NodeRecord>,
+    rows: &mut u64,
+    entries: &mut u64,
+) -> u64 {
+    let index = records.len();
+    nodes.push(node);
+    records.push(NodeRecord::default());
+    let record = match (&node.left, &node.right, &node.bucket) {
+        (Some(left), Some(right), _) => {
+            let left = flatten(left, nodes, records, rows, entries);
+            let right = flatten(right, nodes, records, rows, entries);
+            NodeRecord {
+                split_dimension: node.split_dimension.unwrap_or(0) as u32,
+                left,
+                right,
+                size: node.size as u64,
+                ..NodeRecord::default()
+            }
+        }
+        (_, _, bucket) => {
+            let len = bucket.as_ref().map_or(0, Vec::len) as u64;
+            let record = NodeRecord {
+                flags: LEAF | if node.coincident { COINCIDENT } else { 0 },
+                size: node.size as u64,
+                point_start: *rows,
+                entry_start: *entries,
+                len,
+                ..NodeRecord::default()
+            };
+            *rows += record.rows();
+            *entries += len;
+            record
+        }
+    };
+    records[index] = record;
+    index as u64
+}
+
+/// Rebuilds the nodes of a validated layout. Both children of a stem follow it in pre-order,
+/// so building from the last node backwards finds them already built, without recursing once
+/// per level of a tree that may be arbitrarily deep.
+fn build<A, T, U>(layout: &Layout) -> Result<KdTree<A, T, U>, FormatError>
+where
+    A: Coordinate + Scalar,
+    T: Payload,
+    U: AsRef<[A]> + for<'a> TryFrom<&'a [A]>,
+{
+    let header = &layout.header;
+    let dims = header.dimensions;
+    let mut scratch = Vec::with_capacity(dims);
+    let mut built: Vec<Option<Box<KdTree<A, T, U>>>> = (0..header.node_count).map(|_| None).collect();
+    for index in (0..header.node_count).rev() {
+        let node = layout.node(index);
+        let mut tree = KdTree::with_capacity(dims, header.capacity);
+        tree.size = node.size as usize;
+        tree.min_bounds = (0..dims).map(|axis| layout.min_bound(index, axis)).collect();
+        tree.max_bounds = (0..dims).map(|axis| layout.max_bound(index, axis)).collect();
+        if node.is_leaf() {
+            let start = node.point_start as usize;
+            let mut points = Vec::with_capacity(node.rows() as usize);
+            for row in start..start + node.rows() as usize {
+                scratch.clear();
+                scratch.extend((0..dims).map(|axis| layout.coordinate::<A>(row, axis)));
+                let point = U::try_from(&scratch[..])
+                    .map_err(|_| FormatError::Corrupted("point type rejected the stored coordinates"))?;
+                points.push(point);
+            }
+            let start = node.entry_start as usize;
+            let bucket = (start..start + node.len as usize)
+                .map(|entry| T::decode(layout.payload(entry)?).ok_or(FormatError::Corrupted("malformed payload")))
+                .collect::<Result<Vec<_>, _>>()?;
+            tree.points = Some(points);
+            tree.bucket = Some(bucket);
+            tree.coincident = node.is_coincident();
+        } else {
+            let mut child = |index: u64| {
+                built[index as usize]
+                    .take()
+                    .ok_or(FormatError::Corrupted("node is referenced twice"))
+            };
+            tree.split_dimension = Some(node.split_dimension as usize);
+            tree.split_value = Some(layout.split_value(index));
+            tree.left = Some(child(node.left)?);
+            tree.right = Some(child(node.right)?);
+            tree.points = None;
+            tree.bucket = None;
+        }
+        built[index] = Some(Box::new(tree));
+    }
+    Ok(*built[0].take().unwrap())
+}
+
+struct SectionWriter<W: Write> {
+    writer: W,
+    written: usize,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> SectionWriter<W> {
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.write_all(&self.buf)?;
+        self.written += self.buf.len();
+        self.buf.clear();
+        Ok(())
+    }
+
+    /// Flushes the buffer and pads the output so the next section starts on an 8 byte boundary
+    fn finish_section(&mut self) -> Result<(), FormatError> {
+        let padding = (8 - (self.written + self.buf.len()) % 8) % 8;
+        self.buf.resize(self.buf.len() + padding, 0);
+        self.flush()?;
+        Ok(())
+    }
+}
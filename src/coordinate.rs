@@ -0,0 +1,211 @@
+//! Numeric types that can be used as point coordinates and distances.
+//!
+//! [`KdTree`](crate::KdTree) works with any [`Coordinate`]: `f32` and `f64`, as well as
+//! the primitive integers, which keep large values such as Morton codes exact. Every
+//! coordinate type names a [`Distance`] type wide enough to hold the squared difference
+//! of two of its values, e.g. `i64` coordinates have `u128` distances. The metrics in
+//! [`distance`](crate::distance) saturate at the distance type's maximum instead of
+//! overflowing.
+//!
+//! Queries aren't tied to that type: they return whatever [`Distance`] the metric
+//! returns, so a tree of `f32` points can be ranked by `f64` distances with
+//! [`squared_euclidean_f64`](crate::distance::squared_euclidean_f64).
+//!
+//! ```
+//! use kdtree::KdTree;
+//! use kdtree::distance::squared_euclidean;
+//!
+//! let mut tree: KdTree<i64, &str, [i64; 2]> = KdTree::new(2);
+//! tree.add([i64::MIN, 0], "far").unwrap();
+//! tree.add([1 << 40, 3], "near").unwrap();
+//!
+//! let nearest = tree.nearest(&[(1 << 40) + 4, 0], 1, &squared_euclidean).unwrap();
+//! assert_eq!(nearest, vec![(25u128, &"near")]);
+//! ```
+//!
+//! Other numeric types implement the two traits themselves. A float type that
+//! implemented `num_traits::Float` for kdtree 0.8 uses itself as its [`Distance`], like
+//! `f32` and `f64` do. A fixed-point type can measure distances in raw units:
+//!
+//! ```
+//! use kdtree::distance::squared_euclidean;
+//! use kdtree::{Coordinate, KdTree, NonFinite};
+//!
+//! /// Q16.16 fixed-point number
+//! #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+//! struct Fixed(i32);
+//!
+//! impl Coordinate for Fixed {
+//!     // squared differences in units of 2^-32
+//!     type Distance = u64;
+//!
+//!     const LOWEST: Self = Fixed(i32::MIN);
+//!     const HIGHEST: Self = Fixed(i32::MAX);
+//!
+//!     fn non_finite(self) -> Option<NonFinite> {
+//!         None
+//!     }
+//!
+//!     fn midpoint(low: Self, high: Self) -> Self {
+//!         Fixed(((low.0 as i64 + high.0 as i64) >> 1) as i32)
+//!     }
+//!
+//!     fn abs_diff(self, other: Self) -> u64 {
+//!         self.0.abs_diff(other.0) as u64
+//!     }
+//! }
+//!
+//! let one = 1 << 16;
+//! let mut tree: KdTree<Fixed, &str, [Fixed; 2]> = KdTree::new(2);
+//! tree.add([Fixed(one), Fixed(0)], "right").unwrap();
+//! tree.add([Fixed(0), Fixed(3 * one)], "up").unwrap();
+//!
+//! let nearest = tree.nearest(&[Fixed(0), Fixed(0)], 1, &squared_euclidean).unwrap();
+//! assert_eq!(nearest, vec![(1u64 << 32, &"right")]);
+//! ```
+
+use crate::kdtree::NonFinite;
+
+/// Numeric type of a single point coordinate
+pub trait Coordinate: Copy + PartialOrd {
+    /// Type of the distances computed by the metrics in [`distance`](crate::distance)
+    type Distance: Distance;
+
+    /// Smallest value, used as the upper bound of empty nodes
+    const LOWEST: Self;
+    /// Largest value, used as the lower bound of empty nodes
+    const HIGHEST: Self;
+
+    /// Classifies values that can't be stored in a tree, integers are always finite
+    fn non_finite(self) -> Option<NonFinite>;
+
+    /// Value halfway between `low` and `high`, rounded towards `low`
+    fn midpoint(low: Self, high: Self) -> Self;
+
+    /// Absolute difference of two values in the distance type, which can't overflow
+    fn abs_diff(self, other: Self) -> Self::Distance;
+}
+
+/// Numeric type of the distances returned by a metric
+pub trait Distance: Copy + PartialOrd {
+    const ZERO: Self;
+    /// Largest distance, used as the radius of unbounded queries
+    const MAX: Self;
+
+    /// Whether the distance can be ordered against others, integers are always finite
+    fn is_finite(self) -> bool;
+
+    /// Addition that stops at [`Distance::MAX`], plain addition for floats
+    fn saturating_add(self, other: Self) -> Self;
+
+    /// Multiplication that stops at [`Distance::MAX`], plain multiplication for floats
+    fn saturating_mul(self, other: Self) -> Self;
+}
+
+macro_rules! float_coordinate {
+    ($($t:ty),*) => {
+        $(
+            impl Coordinate for $t {
+                type Distance = $t;
+
+                const LOWEST: Self = <$t>::MIN;
+                const HIGHEST: Self = <$t>::MAX;
+
+                fn non_finite(self) -> Option<NonFinite> {
+                    if self.is_nan() {
+                        Some(NonFinite::NaN)
+                    } else if self == <$t>::INFINITY {
+                        Some(NonFinite::Infinity)
+                    } else if self == <$t>::NEG_INFINITY {
+                        Some(NonFinite::NegInfinity)
+                    } else {
+                        None
+                    }
+                }
+
+                fn midpoint(low: Self, high: Self) -> Self {
+                    low + (high - low) / 2.0
+                }
+
+                fn abs_diff(self, other: Self) -> Self {
+                    (self - other).abs()
+                }
+            }
+
+            impl Distance for $t {
+                const ZERO: Self = 0.0;
+                const MAX: Self = <$t>::MAX;
+
+                fn is_finite(self) -> bool {
+                    <$t>::is_finite(self)
+                }
+
+                fn saturating_add(self, other: Self) -> Self {
+                    self + other
+                }
+
+                fn saturating_mul(self, other: Self) -> Self {
+                    self * other
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! integer_coordinate {
+    ($($t:ty => $d:ty),*) => {
+        $(
+            impl Coordinate for $t {
+                type Distance = $d;
+
+                const LOWEST: Self = <$t>::MIN;
+                const HIGHEST: Self = <$t>::MAX;
+
+                fn non_finite(self) -> Option<NonFinite> {
+                    None
+                }
+
+                fn midpoint(low: Self, high: Self) -> Self {
+                    // the sum of two values of any primitive up to 64 bits fits an i128
+                    ((low as i128 + high as i128) >> 1) as $t
+                }
+
+                fn abs_diff(self, other: Self) -> $d {
+                    <$t>::abs_diff(self, other) as $d
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! integer_distance {
+    ($($t:ty),*) => {
+        $(
+            impl Distance for $t {
+                const ZERO: Self = 0;
+                const MAX: Self = <$t>::MAX;
+
+                fn is_finite(self) -> bool {
+                    true
+                }
+
+                fn saturating_add(self, other: Self) -> Self {
+                    <$t>::saturating_add(self, other)
+                }
+
+                fn saturating_mul(self, other: Self) -> Self {
+                    <$t>::saturating_mul(self, other)
+                }
+            }
+        )*
+    };
+}
+
+float_coordinate!(f32, f64);
+
+integer_coordinate!(
+    i8 => u32, u8 => u32, i16 => u64, u16 => u64, i32 => u64, u32 => u64,
+    i64 => u128, u64 => u128, isize => u128, usize => u128
+);
+
+integer_distance!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
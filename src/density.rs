@@ -0,0 +1,53 @@
+//! Kernels for [`KdTree::kernel_density`](crate::KdTree::kernel_density).
+//!
+//! A kernel weighs each entry by its distance to the query point, measured in
+//! bandwidths. Both kernels here are evaluated on the squared scaled distance, so
+//! they pair with squared metrics such as
+//! [`squared_euclidean`](crate::distance::squared_euclidean), and both vanish past a
+//! cutoff so that only entries near the query point are visited.
+//!
+//! ```
+//! use kdtree::KdTree;
+//! use kdtree::density::DensityKernel;
+//! use kdtree::distance::squared_euclidean;
+//!
+//! let mut tree: KdTree<f64, f64, [f64; 2]> = KdTree::new(2);
+//! tree.add([0.0, 0.0], 2.0).unwrap();
+//! tree.add([0.5, 0.0], 1.0).unwrap();
+//! tree.add([5.0, 0.0], 1.0).unwrap();
+//!
+//! let density = tree
+//!     .kernel_density(&[0.0, 0.0], 1.0, DensityKernel::Epanechnikov, &squared_euclidean, |&w| w)
+//!     .unwrap();
+//! assert_eq!(density, 2.0 + 0.75);
+//! ```
+
+/// Weight of an entry as a function of its squared distance in bandwidths `u`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DensityKernel {
+    /// `exp(-u / 2)`, cut off at `cutoff` bandwidths, which must be finite and positive
+    Gaussian { cutoff: f64 },
+    /// `1 - u`, zero from one bandwidth on
+    Epanechnikov,
+}
+
+impl DensityKernel {
+    /// Weight at the squared scaled distance `u`
+    pub fn evaluate(&self, u: f64) -> f64 {
+        if u > self.support() {
+            return 0.0;
+        }
+        match self {
+            DensityKernel::Gaussian { .. } => (-u / 2.0).exp(),
+            DensityKernel::Epanechnikov => 1.0 - u,
+        }
+    }
+
+    /// Largest squared scaled distance with a nonzero weight
+    pub fn support(&self) -> f64 {
+        match self {
+            DensityKernel::Gaussian { cutoff } => cutoff * cutoff,
+            DensityKernel::Epanechnikov => 1.0,
+        }
+    }
+}
@@ -1,8 +1,13 @@
 //! Defines different distance metrics, in simplest case it defines the
 //! euclidean distance which is no more than the square root of the sum of the
 //! squares of the distances in each dimension.
+//!
+//! The metrics compute in the [`Distance`] type of the coordinates, so integer
+//! coordinates never overflow: differences are taken in a wider unsigned type
+//! and the result saturates at its maximum.
 
-use num_traits::Float;
+use crate::coordinate::{Coordinate, Distance};
+use crate::kernels::Columns;
 
 /// Returns the squared euclidean distance between two points. When you only
 /// need to compare distances, rather than having the exact distance between
@@ -29,10 +34,89 @@ use num_traits::Float;
 /// // this is broken
 /// let _ = squared_euclidean(&[0.0, 0.0], &[1.0, 0.0, 0.0]);
 /// ```
-pub fn squared_euclidean<T: Float>(a: &[T], b: &[T]) -> T {
+///
+/// Integer coordinates are measured in a wider type:
+///
+/// ```rust
+/// use kdtree::distance::squared_euclidean;
+///
+/// assert_eq!(squared_euclidean(&[i32::MIN], &[i32::MAX]), (u32::MAX as u64).pow(2));
+/// assert_eq!(squared_euclidean(&[i64::MIN, i64::MIN], &[i64::MAX, i64::MAX]), u128::MAX);
+/// ```
+pub fn squared_euclidean<T: Coordinate>(a: &[T], b: &[T]) -> T::Distance {
+    debug_assert_eq!(a.len(), b.len());
+    a.iter()
+        .zip(b.iter())
+        .map(|(x, y)| {
+            let d = x.abs_diff(*y);
+            d.saturating_mul(d)
+        })
+        .fold(T::Distance::ZERO, Distance::saturating_add)
+}
+
+/// Returns the manhattan distance between two points, the sum of the absolute
+/// differences in each dimension.
+///
+/// ```rust
+/// use kdtree::distance::manhattan;
+///
+/// assert_eq!(manhattan(&[0.0, 0.0], &[1.0, -2.0]), 3.0);
+/// assert_eq!(manhattan(&[u8::MIN, u8::MIN], &[u8::MAX, u8::MAX]), 510);
+/// ```
+pub fn manhattan<T: Coordinate>(a: &[T], b: &[T]) -> T::Distance {
+    debug_assert_eq!(a.len(), b.len());
+    a.iter()
+        .zip(b.iter())
+        .map(|(x, y)| x.abs_diff(*y))
+        .fold(T::Distance::ZERO, Distance::saturating_add)
+}
+
+/// Squared euclidean distance accumulated in `f64`, for coordinates whose own
+/// distance type is too narrow to rank dense points, such as `f32`. Storing
+/// `f32` points halves their memory while ranking stays as precise as with
+/// `f64` points.
+///
+/// ```rust
+/// use kdtree::distance::{squared_euclidean, squared_euclidean_f64};
+///
+/// let a = [3000.0f32, 3000.0];
+/// // the next f32 after 3000
+/// let b = [f32::from_bits(3000.0f32.to_bits() + 1), 3000.0];
+/// assert_eq!(squared_euclidean(&[0.0f32, 0.0], &a), squared_euclidean(&[0.0f32, 0.0], &b));
+/// assert!(squared_euclidean_f64(&[0.0f32, 0.0], &a) < squared_euclidean_f64(&[0.0f32, 0.0], &b));
+/// ```
+pub fn squared_euclidean_f64<T: Copy + Into<f64>>(a: &[T], b: &[T]) -> f64 {
     debug_assert_eq!(a.len(), b.len());
     a.iter()
         .zip(b.iter())
-        .map(|(x, y)| ((*x) - (*y)) * ((*x) - (*y)))
-        .fold(T::zero(), ::std::ops::Add::add)
+        .map(|(&x, &y)| {
+            let d = x.into() - y.into();
+            d * d
+        })
+        .sum()
+}
+
+/// Manhattan distance accumulated in `f64`, see [`squared_euclidean_f64`].
+pub fn manhattan_f64<T: Copy + Into<f64>>(a: &[T], b: &[T]) -> f64 {
+    debug_assert_eq!(a.len(), b.len());
+    a.iter().zip(b.iter()).map(|(&x, &y)| (x.into() - y.into()).abs()).sum()
+}
+
+/// Metric the traversal of a query measures with: any function of two points, or a
+/// [`Kernel`](crate::kernels::Kernel), which measures the packed leaves of an `f32`
+/// tree a whole leaf at a time
+pub(crate) trait Metric<A, D> {
+    fn distance(&self, a: &[A], b: &[A]) -> D;
+
+    /// Writes the distance from `point` to every point of `columns` to `out` and returns
+    /// `true`, or returns `false` to have the points measured one at a time
+    fn distances_to_columns(&self, _point: &[A], _columns: &Columns<A>, _out: &mut Vec<D>) -> bool {
+        false
+    }
+}
+
+impl<A, D, F: Fn(&[A], &[A]) -> D> Metric<A, D> for F {
+    fn distance(&self, a: &[A], b: &[A]) -> D {
+        self(a, b)
+    }
 }
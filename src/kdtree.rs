@@ -1,31 +1,90 @@
+use std::cmp::Reverse;
 use std::collections::BinaryHeap;
+use std::collections::binary_heap::PeekMut;
 
-use num_traits::{Float, One, Zero};
 use thiserror::Error;
 
+use crate::coordinate::{Coordinate, Distance};
+use crate::density::DensityKernel;
+use crate::distance::Metric;
 use crate::heap_element::HeapElement;
+use crate::kernels::{Columns, Kernel};
+use crate::region::Region;
+use crate::shape::Shape;
+use crate::summary::Summary;
 
 #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serialize",
+    serde(
+        try_from = "RawKdTree<A, T, U, S>",
+        bound(
+            serialize = "A: serde::Serialize, T: serde::Serialize, U: serde::Serialize",
+            deserialize = "A: Coordinate + serde::Deserialize<'de>, T: serde::Deserialize<'de>, \
+                             U: serde::Deserialize<'de>, S: Summary<T>"
+        )
+    )
+)]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    rkyv(
+        serialize_bounds(__S: rkyv::ser::Writer + rkyv::ser::Allocator, __S::Error: rkyv::rancor::Source),
+        deserialize_bounds(__D::Error: rkyv::rancor::Source),
+        bytecheck(bounds(__C: rkyv::validation::ArchiveContext, __C::Error: rkyv::rancor::Source))
+    )
+)]
 #[derive(Clone, Debug)]
-pub struct KdTree<A, T, U: AsRef<[A]>> {
+pub struct KdTree<A, T, U: AsRef<[A]>, S = ()> {
     // node
-    left: Option<Box<KdTree<A, T, U>>>,
-    right: Option<Box<KdTree<A, T, U>>>,
+    #[cfg_attr(feature = "rkyv", rkyv(omit_bounds))]
+    pub(crate) left: Option<Box<KdTree<A, T, U, S>>>,
+    #[cfg_attr(feature = "rkyv", rkyv(omit_bounds))]
+    pub(crate) right: Option<Box<KdTree<A, T, U, S>>>,
     // common
-    dimensions: usize,
-    capacity: usize,
-    size: usize,
-    min_bounds: Box<[A]>,
-    max_bounds: Box<[A]>,
+    pub(crate) dimensions: usize,
+    pub(crate) capacity: usize,
+    pub(crate) size: usize,
+    pub(crate) min_bounds: Box<[A]>,
+    pub(crate) max_bounds: Box<[A]>,
+    // aggregate of every payload below this node, rebuilt when deserialized
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    pub(crate) summary: S,
     // stem
-    split_value: Option<A>,
-    split_dimension: Option<usize>,
+    pub(crate) split_value: Option<A>,
+    pub(crate) split_dimension: Option<usize>,
     // leaf
-    points: Option<Vec<U>>,
-    bucket: Option<Vec<T>>,
+    pub(crate) points: Option<Vec<U>>,
+    pub(crate) bucket: Option<Vec<T>>,
+    // leaf whose entries all share `points[0]`; it is not split again until a distinct point arrives
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub(crate) coincident: bool,
+    // whether leaves also copy their points into `packed`, see `with_packed_leaves`
+    #[cfg_attr(feature = "serialize", serde(default))]
+    pub(crate) pack_leaves: bool,
+    // row-major coordinates of `points`, empty unless `pack_leaves` is set
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    pub(crate) packed: Vec<A>,
+    // the same coordinates in one column per dimension, which kernels evaluate at once
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    #[cfg_attr(feature = "rkyv", rkyv(with = rkyv::with::Skip))]
+    pub(crate) columns: Columns<A>,
+}
+
+impl<A, T, U: AsRef<[A]>, S> Drop for KdTree<A, T, U, S> {
+    /// Frees the nodes with an explicit stack, since trees read from untrusted input can be
+    /// too deep to drop one recursive call per level
+    fn drop(&mut self) {
+        let mut pending: Vec<Box<Self>> = self.left.take().into_iter().chain(self.right.take()).collect();
+        while let Some(mut node) = pending.pop() {
+            pending.extend(node.left.take());
+            pending.extend(node.right.take());
+        }
+    }
 }
 
-#[derive(Error, Debug, PartialEq, Eq)]
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ErrorKind {
     #[error("wrong dimension")]
     WrongDimension,
@@ -33,9 +92,63 @@ pub enum ErrorKind {
     NonFiniteCoordinate,
     #[error("zero capacity")]
     ZeroCapacity,
+    #[error("invalid bounding box: min exceeds max on axis {axis}")]
+    InvalidBoundingBox { axis: usize },
+    #[error("metric returned a non-finite distance")]
+    NonFiniteDistance,
+    #[error("invalid distance range: minimum exceeds maximum")]
+    InvalidDistanceRange,
+    #[error("cosine similarity is undefined for the zero vector")]
+    ZeroVector,
+    #[error("invalid bandwidth: must be positive and finite")]
+    InvalidBandwidth,
+    #[error("k-NN radii were computed for a different tree")]
+    StaleKnnRadii,
+    #[error("metric is not a distance")]
+    NotADistance,
+    #[error("corrupted data: {0}")]
+    Corrupted(&'static str),
+}
+
+/// Why a point was rejected with [`ErrorKind::WrongDimension`] or
+/// [`ErrorKind::NonFiniteCoordinate`], as reported by [`KdTree::check_point`]
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PointError {
+    #[error("wrong dimension: expected {expected}, got {actual}")]
+    WrongDimension { expected: usize, actual: usize },
+    #[error("non-finite coordinate {value} on axis {axis}")]
+    NonFiniteCoordinate { axis: usize, value: NonFinite },
+}
+
+impl From<PointError> for ErrorKind {
+    fn from(error: PointError) -> Self {
+        match error {
+            PointError::WrongDimension { .. } => ErrorKind::WrongDimension,
+            PointError::NonFiniteCoordinate { .. } => ErrorKind::NonFiniteCoordinate,
+        }
+    }
+}
+
+/// The offending value of a [`PointError::NonFiniteCoordinate`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonFinite {
+    NaN,
+    Infinity,
+    NegInfinity,
+}
+
+impl std::fmt::Display for NonFinite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NonFinite::NaN => write!(f, "NaN"),
+            NonFinite::Infinity => write!(f, "inf"),
+            NonFinite::NegInfinity => write!(f, "-inf"),
+        }
+    }
 }
 
-impl<A: Float + Zero + One, T, U: AsRef<[A]>> KdTree<A, T, U> {
+impl<A: Coordinate, T, U: AsRef<[A]>> KdTree<A, T, U> {
     // ============================================================================
     // === STRUCTURE ===
     // ============================================================================
@@ -46,8 +159,28 @@ impl<A: Float + Zero + One, T, U: AsRef<[A]>> KdTree<A, T, U> {
 
     /// Create a new KD tree, specifying the dimension size of each point and the capacity of leaf nodes
     pub fn with_capacity(dimensions: usize, capacity: usize) -> Self {
-        let min_bounds = vec![A::max_value(); dimensions];
-        let max_bounds = vec![A::min_value(); dimensions];
+        KdTree::with_summary(dimensions, capacity)
+    }
+
+    /// Create a new KD tree whose leaves copy the coordinates of their points into one
+    /// contiguous block, so that queries scan leaves without following a pointer per point.
+    /// This costs a second copy of every coordinate and pays off when `U` stores its
+    /// coordinates behind a pointer, like `Vec<A>`. Leaves also keep a third copy in
+    /// [`Columns`], which the kernel queries of `f32` trees, like
+    /// [`KdTree::nearest_kernel`], evaluate a leaf at a time.
+    pub fn with_packed_leaves(dimensions: usize, capacity: usize) -> Self {
+        let mut tree = KdTree::with_capacity(dimensions, capacity);
+        tree.pack_leaves = dimensions > 0;
+        tree
+    }
+}
+
+impl<A: Coordinate, T, U: AsRef<[A]>, S: Summary<T>> KdTree<A, T, U, S> {
+    /// Create a new KD tree that keeps a [`Summary`] of the payloads below every node,
+    /// see the [`summary`](crate::summary) module
+    pub fn with_summary(dimensions: usize, capacity: usize) -> Self {
+        let min_bounds = vec![A::HIGHEST; dimensions];
+        let max_bounds = vec![A::LOWEST; dimensions];
         KdTree {
             left: None,
             right: None,
@@ -56,10 +189,25 @@ impl<A: Float + Zero + One, T, U: AsRef<[A]>> KdTree<A, T, U> {
             size: 0,
             min_bounds: min_bounds.into_boxed_slice(),
             max_bounds: max_bounds.into_boxed_slice(),
+            summary: S::empty(),
             split_value: None,
             split_dimension: None,
             points: Some(vec![]),
             bucket: Some(vec![]),
+            coincident: false,
+            pack_leaves: false,
+            packed: vec![],
+            columns: Columns::default(),
+        }
+    }
+
+    /// Switches an existing tree to packed leaves, see [`KdTree::with_packed_leaves`]. Trees
+    /// restored with `read_from` or from the portable format start out unpacked.
+    pub fn pack_leaves(&mut self) {
+        self.pack_leaves = self.dimensions > 0;
+        self.repack();
+        for child in [self.left.as_mut(), self.right.as_mut()].into_iter().flatten() {
+            child.pack_leaves();
         }
     }
 
@@ -68,16 +216,18 @@ impl<A: Float + Zero + One, T, U: AsRef<[A]>> KdTree<A, T, U> {
             return Err(ErrorKind::ZeroCapacity);
         }
         self.check_point(point.as_ref())?;
-        self.add_unchecked(point, data)
+        self.add_unchecked(point, data);
+        Ok(())
     }
 
-    fn add_unchecked(&mut self, point: U, data: T) -> Result<(), ErrorKind> {
+    /// Adds an entry whose point has already been checked, returning its stored data
+    pub(crate) fn add_unchecked(&mut self, point: U, data: T) -> &mut T {
         if self.is_leaf() {
-            self.add_to_bucket(point, data);
-            return Ok(());
+            return self.add_to_bucket(point, data);
         }
         self.extend(point.as_ref());
         self.size += 1;
+        self.summary.add(&data);
         let next = if self.belongs_in_left(point.as_ref()) {
             self.left.as_mut()
         } else {
@@ -86,55 +236,102 @@ impl<A: Float + Zero + One, T, U: AsRef<[A]>> KdTree<A, T, U> {
         next.unwrap().add_unchecked(point, data)
     }
 
-    fn add_to_bucket(&mut self, point: U, data: T) {
+    fn add_to_bucket(&mut self, point: U, data: T) -> &mut T {
         self.extend(point.as_ref());
         let mut points = self.points.take().unwrap();
         let mut bucket = self.bucket.take().unwrap();
+        self.size += 1;
+        self.summary.add(&data);
+        if self.coincident && points[0].as_ref() == point.as_ref() {
+            bucket.push(data);
+            self.points = Some(points);
+            return self.bucket.insert(bucket).last_mut().unwrap();
+        }
+        if self.pack_leaves && !self.coincident {
+            self.pack(point.as_ref());
+        }
         points.push(point);
         bucket.push(data);
-        self.size += 1;
-        if self.size > self.capacity {
-            self.split(points, bucket);
+        if self.size > self.capacity || self.coincident {
+            self.split(points, bucket)
         } else {
             self.points = Some(points);
-            self.bucket = Some(bucket);
+            self.bucket.insert(bucket).last_mut().unwrap()
         }
     }
 
-    fn split(&mut self, mut points: Vec<U>, mut bucket: Vec<T>) {
-        let mut max = A::zero();
+    /// Splits an overfull leaf, returning the data of its last entry wherever it ends up
+    fn split(&mut self, mut points: Vec<U>, mut bucket: Vec<T>) -> &mut T {
+        let mut max = A::Distance::ZERO;
         for dim in 0..self.dimensions {
-            let diff = self.max_bounds[dim] - self.min_bounds[dim];
-            if !diff.is_nan() && diff > max {
+            let diff = self.max_bounds[dim].abs_diff(self.min_bounds[dim]);
+            if diff > max {
                 max = diff;
                 self.split_dimension = Some(dim);
             }
         }
         match self.split_dimension {
             None => {
+                // every point in the bucket is identical, keep a single copy and stop retrying
+                points.truncate(1);
+                self.coincident = true;
                 self.points = Some(points);
                 self.bucket = Some(bucket);
-                return;
+                self.repack();
+                return self.bucket.as_mut().unwrap().last_mut().unwrap();
             }
             Some(dim) => {
                 let min = self.min_bounds[dim];
                 let max = self.max_bounds[dim];
-                self.split_value = Some(min + (max - min) / A::from(2.0).unwrap());
+                let mid = A::midpoint(min, max);
+                // the midpoint of adjacent values can round down onto `min`, split on `max` instead so
+                // that both sides are populated and `belongs_in_left` stays a plain comparison
+                self.split_value = Some(if mid == min { max } else { mid });
             }
         };
-        let mut left = Box::new(KdTree::with_capacity(self.dimensions, self.capacity));
-        let mut right = Box::new(KdTree::with_capacity(self.dimensions, self.capacity));
-        while !points.is_empty() {
-            let point = points.swap_remove(0);
-            let data = bucket.swap_remove(0);
-            if self.belongs_in_left(point.as_ref()) {
-                left.add_to_bucket(point, data);
+        let mut left = Box::new(self.empty_child());
+        let mut right = Box::new(self.empty_child());
+        self.packed = vec![];
+        self.columns = Columns::default();
+        let point = points.pop().unwrap();
+        let data = bucket.pop().unwrap();
+        if self.coincident {
+            // the shared point moves as a whole, only the newly added point can land elsewhere
+            self.coincident = false;
+            let shared = points.pop().unwrap();
+            if self.belongs_in_left(shared.as_ref()) {
+                left.adopt_coincident(shared, bucket);
             } else {
-                right.add_to_bucket(point, data);
+                right.adopt_coincident(shared, bucket);
+            }
+        } else {
+            for (point, data) in points.into_iter().zip(bucket) {
+                if self.belongs_in_left(point.as_ref()) {
+                    left.add_to_bucket(point, data);
+                } else {
+                    right.add_to_bucket(point, data);
+                }
             }
         }
         self.left = Some(left);
         self.right = Some(right);
+        // the last entry is added after the others so that it stays last in its leaf
+        let next = if self.belongs_in_left(point.as_ref()) {
+            self.left.as_mut()
+        } else {
+            self.right.as_mut()
+        };
+        next.unwrap().add_to_bucket(point, data)
+    }
+
+    fn adopt_coincident(&mut self, point: U, bucket: Vec<T>) {
+        self.extend(point.as_ref());
+        self.size = bucket.len();
+        self.coincident = bucket.len() > 1;
+        self.points = Some(vec![point]);
+        self.bucket = Some(bucket);
+        self.repack();
+        self.resummarize();
     }
 
     pub fn remove(&mut self, point: &U, data: &T) -> Result<usize, ErrorKind>
@@ -145,18 +342,33 @@ impl<A: Float + Zero + One, T, U: AsRef<[A]>> KdTree<A, T, U> {
         let mut removed = 0;
         self.check_point(point.as_ref())?;
         if let (Some(mut points), Some(mut bucket)) = (self.points.take(), self.bucket.take()) {
-            while let Some(p_index) = points
-                .iter()
-                .zip(bucket.iter())
-                .position(|(p, d)| p == point && d == data)
-            {
-                points.remove(p_index);
-                bucket.remove(p_index);
-                removed += 1;
-                self.size -= 1;
+            if self.coincident {
+                if points[0] == *point {
+                    bucket.retain(|d| d != data);
+                    removed = self.size - bucket.len();
+                }
+                if bucket.len() <= 1 {
+                    self.coincident = false;
+                    points.truncate(bucket.len());
+                }
+            } else {
+                let mut kept = 0;
+                for i in 0..points.len() {
+                    if points[i] != *point || bucket[i] != *data {
+                        points.swap(kept, i);
+                        bucket.swap(kept, i);
+                        kept += 1;
+                    }
+                }
+                removed = points.len() - kept;
+                points.truncate(kept);
+                bucket.truncate(kept);
             }
+            self.size -= removed;
             self.points = Some(points);
             self.bucket = Some(bucket);
+            self.repack();
+            self.resummarize();
         } else {
             if let Some(right) = self.right.as_mut() {
                 let right_removed = right.remove(point, data)?;
@@ -172,124 +384,436 @@ impl<A: Float + Zero + One, T, U: AsRef<[A]>> KdTree<A, T, U> {
                     removed += left_removed;
                 }
             }
+            if removed > 0 {
+                self.resummarize();
+            }
         }
         Ok(removed)
     }
 
+    /// Moves the first entry stored at `old_point` whose data matches `predicate` to `new_point`.
+    /// The entry stays in its leaf when `new_point` still falls within the leaf's region and is
+    /// re-inserted otherwise; bounds along the affected path are kept tight in both cases.
+    /// Returns whether a matching entry was found.
+    pub fn update_position<P>(&mut self, old_point: &[A], new_point: U, mut predicate: P) -> Result<bool, ErrorKind>
+    where
+        P: FnMut(&T) -> bool,
+    {
+        self.check_point(old_point)?;
+        self.check_point(new_point.as_ref())?;
+        let mut new_point = Some(new_point);
+        match self.relocate(old_point, &mut new_point, true, &mut predicate) {
+            Relocation::Missing => Ok(false),
+            Relocation::Moved => Ok(true),
+            Relocation::Detached(data) => {
+                self.add_unchecked(new_point.take().unwrap(), data);
+                Ok(true)
+            }
+        }
+    }
+
+    fn relocate<P>(
+        &mut self,
+        old_point: &[A],
+        new_point: &mut Option<U>,
+        same_leaf: bool,
+        predicate: &mut P,
+    ) -> Relocation<T>
+    where
+        P: FnMut(&T) -> bool,
+    {
+        if !self.is_leaf() {
+            let goes_left = self.belongs_in_left(old_point);
+            let same_leaf = same_leaf && self.belongs_in_left(new_point.as_ref().unwrap().as_ref()) == goes_left;
+            let next = if goes_left {
+                self.left.as_mut()
+            } else {
+                self.right.as_mut()
+            };
+            let relocation = next.unwrap().relocate(old_point, new_point, same_leaf, predicate);
+            if let Relocation::Detached(_) = relocation {
+                self.size -= 1;
+            }
+            if !matches!(relocation, Relocation::Missing) {
+                self.refresh_bounds();
+                self.resummarize();
+            }
+            return relocation;
+        }
+
+        let points = self.points.as_mut().unwrap();
+        let bucket = self.bucket.as_mut().unwrap();
+        let index = if self.coincident {
+            if points[0].as_ref() == old_point {
+                bucket.iter().position(&mut *predicate)
+            } else {
+                None
+            }
+        } else {
+            points
+                .iter()
+                .zip(bucket.iter())
+                .position(|(p, d)| p.as_ref() == old_point && predicate(d))
+        };
+        let Some(index) = index else {
+            return Relocation::Missing;
+        };
+        let new_ref = new_point.as_ref().unwrap().as_ref();
+        let relocation = if same_leaf && !self.coincident {
+            points[index] = new_point.take().unwrap();
+            Relocation::Moved
+        } else if same_leaf && points[0].as_ref() == new_ref {
+            Relocation::Moved
+        } else {
+            let data = bucket.remove(index);
+            if !self.coincident {
+                points.remove(index);
+            } else if bucket.len() <= 1 {
+                self.coincident = false;
+                points.truncate(bucket.len());
+            }
+            self.size -= 1;
+            Relocation::Detached(data)
+        };
+        self.repack();
+        self.refresh_bounds();
+        self.resummarize();
+        relocation
+    }
+
     pub fn size(&self) -> usize {
         self.size
     }
 
+    /// Summary of every payload in the tree
+    pub fn summary(&self) -> &S {
+        &self.summary
+    }
+
+    /// Checks that `point` can be added to or queried against the tree. Other methods only
+    /// report the [`ErrorKind`] of a rejected point; this describes the dimension or axis at fault.
+    pub fn check_point(&self, point: &[A]) -> Result<(), PointError> {
+        check_point(self.dimensions, point)
+    }
+
+    // ============================================================================
+    // === EXACT POINT LOOKUP ===
+    // ============================================================================
+    /// Returns the data stored at exactly `point`, if any. When several entries share the
+    /// coordinate, the first one found in its leaf is returned.
+    pub fn get(&self, point: &[A]) -> Result<Option<&T>, ErrorKind> {
+        self.check_point(point)?;
+        let leaf = self.leaf_for(point);
+        Ok(leaf.position_in_leaf(point).map(|i| &leaf.bucket.as_ref().unwrap()[i]))
+    }
+
+    pub fn contains_point(&self, point: &[A]) -> Result<bool, ErrorKind> {
+        self.check_point(point)?;
+        Ok(self.leaf_for(point).position_in_leaf(point).is_some())
+    }
+
+    /// Stores `data` at `point`, replacing and returning the data of an existing entry at exactly
+    /// the same coordinate instead of adding a duplicate.
+    pub fn insert_or_replace(&mut self, point: U, data: T) -> Result<Option<T>, ErrorKind> {
+        self.check_point(point.as_ref())?;
+        if let Some(existing) = self.lookup_mut(point.as_ref()) {
+            let replaced = std::mem::replace(existing, data);
+            self.resummarize_path(point.as_ref());
+            return Ok(Some(replaced));
+        }
+        self.add(point, data).map(|_| None)
+    }
+
+    fn resummarize_path(&mut self, point: &[A]) {
+        if !self.is_leaf() {
+            let next = if self.belongs_in_left(point) {
+                self.left.as_mut()
+            } else {
+                self.right.as_mut()
+            };
+            next.unwrap().resummarize_path(point);
+        }
+        self.resummarize();
+    }
+
+    fn lookup_mut(&mut self, point: &[A]) -> Option<&mut T> {
+        let leaf = self.leaf_for_mut(point);
+        let index = leaf.position_in_leaf(point)?;
+        Some(&mut leaf.bucket.as_mut().unwrap()[index])
+    }
+
+    fn leaf_for_mut(&mut self, point: &[A]) -> &mut Self {
+        let mut curr = self;
+        while !curr.is_leaf() {
+            curr = if curr.belongs_in_left(point) {
+                curr.left.as_mut().unwrap()
+            } else {
+                curr.right.as_mut().unwrap()
+            };
+        }
+        curr
+    }
+
+    fn leaf_for(&self, point: &[A]) -> &Self {
+        let mut curr = self;
+        while !curr.is_leaf() {
+            curr = if curr.belongs_in_left(point) {
+                curr.left.as_ref().unwrap()
+            } else {
+                curr.right.as_ref().unwrap()
+            };
+        }
+        curr
+    }
+
+    fn position_in_leaf(&self, point: &[A]) -> Option<usize> {
+        let mut rows = Self::rows(self.points.as_ref().unwrap(), &self.packed, self.dimensions);
+        if self.coincident {
+            (rows.next() == Some(point)).then_some(0)
+        } else {
+            rows.position(|p| p == point)
+        }
+    }
+
     // ============================================================================
     // === NEAREST QUERIES ===
     // ============================================================================
-    pub fn nearest<F>(&self, point: &[A], num: usize, distance: &F) -> Result<Vec<(A, &T)>, ErrorKind>
+    pub fn nearest<D, F>(&self, point: &[A], num: usize, distance: &F) -> Result<Vec<(D, &T)>, ErrorKind>
     where
-        F: Fn(&[A], &[A]) -> A,
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
     {
-        self.nearest_within_radius_internal(point, num, A::max_value(), distance)
+        self.nearest_within_radius_internal(point, num, D::MAX, distance, Filter::all())
     }
 
-    pub fn nearest_within_radius<F>(
+    pub fn nearest_within_radius<D, F>(
         &self,
         point: &[A],
         num: usize,
-        radius: Option<A>,
+        radius: Option<D>,
         distance: &F,
-    ) -> Result<Vec<(A, &T)>, ErrorKind>
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
     where
-        F: Fn(&[A], &[A]) -> A,
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
     {
-        let radius = radius.unwrap_or_else(A::max_value);
-        self.nearest_within_radius_internal(point, num, radius, distance)
+        let radius = radius.unwrap_or(D::MAX);
+        self.nearest_within_radius_internal(point, num, radius, distance, Filter::all())
     }
 
-    pub fn iter_nearest<'a, F>(
-        &'a self,
-        point: &'a [A],
-        distance: &'a F,
-    ) -> Result<NearestIter<'a, A, T, U, F>, ErrorKind>
+    /// Like [`KdTree::nearest`], but only points for which `filter` returns `true` are
+    /// returned, so up to `num` matching points are found however many closer points don't match.
+    pub fn nearest_filtered<D, F, P>(
+        &self,
+        point: &[A],
+        num: usize,
+        distance: &F,
+        filter: P,
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
     where
-        F: Fn(&[A], &[A]) -> A,
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+        P: Fn(&U, &T) -> bool,
     {
-        self.iter_nearest_within_radius(point, None, distance)
-            .map(|inner| NearestIter { inner })
+        self.nearest_within_radius_internal(point, num, D::MAX, distance, Filter::entries(filter))
     }
 
-    pub fn iter_nearest_within_radius<'a, F>(
-        &'a self,
-        point: &'a [A],
-        radius: Option<A>,
-        distance: &'a F,
-    ) -> Result<NearestWithinRadiusIter<'a, A, T, U, F>, ErrorKind>
+    /// Like [`KdTree::nearest_within_radius`], but only points for which `filter` returns `true`
+    /// are returned
+    pub fn nearest_filtered_within_radius<D, F, P>(
+        &self,
+        point: &[A],
+        num: usize,
+        radius: Option<D>,
+        distance: &F,
+        filter: P,
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
     where
-        F: Fn(&[A], &[A]) -> A,
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+        P: Fn(&U, &T) -> bool,
     {
-        self.check_point(point)?;
-        let mut pending = BinaryHeap::new();
-        let evaluated = BinaryHeap::<HeapElement<A, &T>>::new();
-        pending.push(HeapElement {
-            distance: A::zero(),
+        let radius = radius.unwrap_or(D::MAX);
+        self.nearest_within_radius_internal(point, num, radius, distance, Filter::entries(filter))
+    }
+
+    /// Returns the `num` points closest to any of `queries`, nearest first, together with
+    /// the index of the query each one is closest to (the first of them on ties). This is
+    /// a single traversal that bounds every subtree by its distance to the closest query.
+    pub fn nearest_to_set<Q, D, F>(
+        &self,
+        queries: &[Q],
+        num: usize,
+        distance: &F,
+    ) -> Result<Vec<(D, usize, &T)>, ErrorKind>
+    where
+        Q: AsRef<[A]>,
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        for query in queries {
+            self.check_point(query.as_ref())?;
+        }
+        let num = std::cmp::min(num, self.size);
+        if num == 0 || queries.is_empty() {
+            return Ok(vec![]);
+        }
+        // smallest `measure(query)` over the queries, with the index of the query
+        let closest = |measure: &dyn Fn(&[A]) -> D| -> Result<(D, usize), ErrorKind> {
+            let mut best = (D::MAX, 0);
+            for (i, query) in queries.iter().enumerate() {
+                let dist = finite(measure(query.as_ref()))?;
+                if i == 0 || dist < best.0 {
+                    best = (dist, i);
+                }
+            }
+            Ok(best)
+        };
+        let mut pending = ClosestFirst::<D, &Self>::new();
+        let mut evaluated = BinaryHeap::<HeapElement<D, (usize, &T)>>::new();
+        pending.push(Reverse(HeapElement {
+            distance: D::ZERO,
             element: self,
-        });
-        Ok(NearestWithinRadiusIter {
-            point,
-            pending,
-            evaluated,
-            distance,
-            radius: radius.unwrap_or_else(A::max_value),
-        })
+        }));
+        while let Some(Reverse(HeapElement {
+            distance: bound,
+            element: curr,
+        })) = pending.pop()
+        {
+            let worst = match evaluated.peek() {
+                Some(worst) if evaluated.len() == num => worst.distance,
+                _ => D::MAX,
+            };
+            if bound > worst {
+                break;
+            }
+            if !curr.is_leaf() {
+                for child in [curr.left.as_ref().unwrap(), curr.right.as_ref().unwrap()] {
+                    if child.size == 0 {
+                        continue;
+                    }
+                    let (bound, _) =
+                        closest(&|q| Self::distance_to_space(q, &child.min_bounds, &child.max_bounds, distance))?;
+                    if bound <= worst {
+                        pending.push(Reverse(HeapElement {
+                            distance: bound,
+                            element: &**child,
+                        }));
+                    }
+                }
+                continue;
+            }
+            let rows = Self::rows(curr.points.as_ref().unwrap(), &curr.packed, curr.dimensions);
+            let mut shared = None;
+            for (p, data) in rows.cycle().zip(curr.bucket.as_ref().unwrap()) {
+                let (dist, index) = match shared {
+                    Some(found) => found,
+                    None => closest(&|q| distance(q, p))?,
+                };
+                if curr.coincident {
+                    shared = Some((dist, index));
+                }
+                let element = HeapElement {
+                    distance: dist,
+                    element: (index, data),
+                };
+                if evaluated.len() < num {
+                    evaluated.push(element);
+                } else if evaluated.peek().is_some_and(|worst| element < *worst) {
+                    evaluated.pop();
+                    evaluated.push(element);
+                }
+            }
+        }
+        Ok(evaluated
+            .into_sorted_vec()
+            .into_iter()
+            .map(|e| (e.distance, e.element.0, e.element.1))
+            .collect())
     }
 
-    pub fn iter_nearest_mut<'a, F>(
-        &'a mut self,
+    /// Iterates over all points from the closest to the farthest from `point`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`ErrorKind::WrongDimension`] or [`ErrorKind::NonFiniteCoordinate`] when
+    /// `point` can't be queried. If the metric returns a NaN or infinite distance during
+    /// iteration, the iterator ends early instead of yielding misordered points, and
+    /// [`NearestIter::error`] returns [`ErrorKind::NonFiniteDistance`]. Check it after the
+    /// loop when the metric can fail:
+    ///
+    /// ```
+    /// use kdtree::{ErrorKind, KdTree};
+    ///
+    /// let mut tree: KdTree<f64, usize, [f64; 1]> = KdTree::new(1);
+    /// tree.add([1.0], 0).unwrap();
+    /// let broken = |_: &[f64], _: &[f64]| f64::NAN;
+    /// let mut nearest = tree.iter_nearest(&[0.0], &broken).unwrap();
+    /// assert_eq!(nearest.next(), None);
+    /// assert_eq!(nearest.error(), Some(ErrorKind::NonFiniteDistance));
+    /// ```
+    pub fn iter_nearest<'a, D, F>(
+        &'a self,
         point: &'a [A],
         distance: &'a F,
-    ) -> Result<NearestIterMut<'a, A, T, U, F>, ErrorKind>
+    ) -> Result<NearestIter<'a, A, T, U, F, D, S>, ErrorKind>
     where
-        F: Fn(&[A], &[A]) -> A,
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
     {
-        let radius_iter = self.iter_nearest_within_radius_mut(point, None, distance)?;
-        Ok(NearestIterMut { inner: radius_iter })
+        self.iter_nearest_within_radius(point, None, distance)
+            .map(|inner| NearestIter { inner })
     }
 
-    pub fn iter_nearest_within_radius_mut<'a, F>(
-        &'a mut self,
+    /// Iterates over the points within `radius` of `point`, closest first.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`KdTree::iter_nearest`] for an invalid `point`, and likewise stops early on
+    /// a non-finite distance, reported by [`NearestWithinRadiusIter::error`].
+    pub fn iter_nearest_within_radius<'a, D, F>(
+        &'a self,
         point: &'a [A],
-        radius: Option<A>,
+        radius: Option<D>,
         distance: &'a F,
-    ) -> Result<NearestWithinRadiusIterMut<'a, A, T, U, F>, ErrorKind>
+    ) -> Result<NearestWithinRadiusIter<'a, A, T, U, F, D, S>, ErrorKind>
     where
-        F: Fn(&[A], &[A]) -> A,
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
     {
         self.check_point(point)?;
         let mut pending = BinaryHeap::new();
-        let evaluated = BinaryHeap::<HeapElement<A, &mut T>>::new();
-        pending.push(HeapElement {
-            distance: A::zero(),
+        let evaluated = BinaryHeap::<Reverse<HeapElement<D, &T>>>::new();
+        pending.push(Reverse(HeapElement {
+            distance: D::ZERO,
             element: self,
-        });
-        Ok(NearestWithinRadiusIterMut {
+        }));
+        Ok(NearestWithinRadiusIter {
             point,
             pending,
             evaluated,
             distance,
-            radius: radius.unwrap_or_else(A::max_value),
+            radius: radius.unwrap_or(D::MAX),
+            error: None,
         })
     }
 
     // ============================================================================
     // === NEAREST HELPERS ===
     // ============================================================================
-    fn nearest_within_radius_internal<F>(
+    fn nearest_within_radius_internal<D, F, N, P>(
         &self,
         point: &[A],
         num: usize,
-        radius: A,
+        radius: D,
         distance: &F,
-    ) -> Result<Vec<(A, &T)>, ErrorKind>
+        filter: Filter<N, P>,
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
     where
-        F: Fn(&[A], &[A]) -> A,
+        D: Distance,
+        F: Metric<A, D>,
+        N: Fn(&S) -> bool,
+        P: Fn(&U, &T) -> bool,
     {
         self.check_point(point)?;
         let num = std::cmp::min(num, self.size);
@@ -297,16 +821,27 @@ impl<A: Float + Zero + One, T, U: AsRef<[A]>> KdTree<A, T, U> {
             return Ok(vec![]);
         }
         let mut pending = BinaryHeap::new();
-        let mut evaluated = BinaryHeap::<HeapElement<A, &T>>::new();
-        pending.push(HeapElement {
-            distance: A::zero(),
+        let mut evaluated = BinaryHeap::<HeapElement<D, &T>>::new();
+        let mut scratch = Vec::new();
+        pending.push(Reverse(HeapElement {
+            distance: D::ZERO,
             element: self,
-        });
-        while !pending.is_empty()
-            && (-pending.peek().unwrap().distance <= radius)
-            && (evaluated.len() < num || (-pending.peek().unwrap().distance <= evaluated.peek().unwrap().distance))
-        {
-            self.nearest_step(point, num, radius, distance, &mut pending, &mut evaluated);
+        }));
+        while let Some(next) = pending.peek() {
+            let bound = next.0.distance;
+            if bound > radius || (evaluated.len() == num && evaluated.peek().is_some_and(|x| bound > x.distance)) {
+                break;
+            }
+            Self::nearest_step(
+                point,
+                num,
+                radius,
+                distance,
+                &filter,
+                &mut pending,
+                &mut evaluated,
+                &mut scratch,
+            )?;
         }
         Ok(evaluated
             .into_sorted_vec()
@@ -316,26 +851,39 @@ impl<A: Float + Zero + One, T, U: AsRef<[A]>> KdTree<A, T, U> {
             .collect())
     }
 
-    fn nearest_step<'b, F>(
-        &self,
+    #[allow(clippy::too_many_arguments)]
+    fn nearest_step<'b, D, F, N, P>(
         point: &[A],
         num: usize,
-        max_dist: A,
+        max_dist: D,
         distance: &F,
-        pending: &mut BinaryHeap<HeapElement<A, &'b Self>>,
-        evaluated: &mut BinaryHeap<HeapElement<A, &'b T>>,
-    ) where
-        F: Fn(&[A], &[A]) -> A,
+        filter: &Filter<N, P>,
+        pending: &mut ClosestFirst<D, &'b Self>,
+        evaluated: &mut BinaryHeap<HeapElement<D, &'b T>>,
+        scratch: &mut Vec<D>,
+    ) -> Result<(), ErrorKind>
+    where
+        D: Distance,
+        F: Metric<A, D>,
+        N: Fn(&S) -> bool,
+        P: Fn(&U, &T) -> bool,
     {
-        let mut curr = pending.pop().unwrap().element;
+        let Some(Reverse(HeapElement { element: mut curr, .. })) = pending.pop() else {
+            return Ok(());
+        };
         debug_assert!(evaluated.len() <= num);
-        let evaluated_dist = if evaluated.len() == num {
-            max_dist.min(evaluated.peek().unwrap().distance)
-        } else {
-            max_dist
+        let evaluated_dist = match evaluated.peek() {
+            Some(worst) if evaluated.len() == num && worst.distance < max_dist => worst.distance,
+            _ => max_dist,
         };
 
-        while !curr.is_leaf() {
+        loop {
+            if !(filter.subtree)(&curr.summary) {
+                return Ok(());
+            }
+            if curr.is_leaf() {
+                break;
+            }
             let candidate;
             if curr.belongs_in_left(point) {
                 candidate = curr.right.as_ref().unwrap();
@@ -344,86 +892,544 @@ impl<A: Float + Zero + One, T, U: AsRef<[A]>> KdTree<A, T, U> {
                 candidate = curr.left.as_ref().unwrap();
                 curr = curr.right.as_ref().unwrap();
             }
-            let candidate_to_space =
-                Self::distance_to_space(point, &candidate.min_bounds, &candidate.max_bounds, distance);
+            if candidate.size == 0 || !(filter.subtree)(&candidate.summary) {
+                continue;
+            }
+            let candidate_to_space = finite(Self::distance_to_space(
+                point,
+                &candidate.min_bounds,
+                &candidate.max_bounds,
+                distance,
+            ))?;
             if candidate_to_space <= evaluated_dist {
-                pending.push(HeapElement {
-                    distance: candidate_to_space * -A::one(),
+                pending.push(Reverse(HeapElement {
+                    distance: candidate_to_space,
                     element: &**candidate,
-                });
+                }));
             }
         }
 
-        let points = curr.points.as_ref().unwrap().iter();
-        let bucket = curr.bucket.as_ref().unwrap().iter();
-        let iter = points.zip(bucket).map(|(p, d)| HeapElement {
-            distance: distance(point, p.as_ref()),
-            element: d,
-        });
-        for element in iter {
-            if element.distance <= max_dist {
+        let mut offer = |p: &U, element: HeapElement<D, &'b T>| -> Result<(), ErrorKind> {
+            if finite(element.distance)? <= max_dist && (filter.entry)(p, element.element) {
                 if evaluated.len() < num {
                     evaluated.push(element);
-                } else if element < *evaluated.peek().unwrap() {
+                } else if evaluated.peek().is_some_and(|worst| element < *worst) {
                     evaluated.pop();
                     evaluated.push(element);
                 }
             }
+            Ok(())
+        };
+        let points = curr.points.as_ref().unwrap();
+        let bucket = curr.bucket.as_ref().unwrap();
+        if !curr.coincident
+            && curr.columns.len() == bucket.len()
+            && distance.distances_to_columns(point, &curr.columns, scratch)
+        {
+            for ((p, data), &dist) in points.iter().zip(bucket).zip(scratch.iter()) {
+                offer(
+                    p,
+                    HeapElement {
+                        distance: dist,
+                        element: data,
+                    },
+                )?;
+            }
+            return Ok(());
+        }
+        let rows = Self::rows(points, &curr.packed, curr.dimensions);
+        let iter = Self::leaf_distances(rows, curr.coincident, bucket.iter(), point, distance);
+        for (p, element) in points.iter().cycle().zip(iter) {
+            offer(p, element)?;
         }
+        Ok(())
+    }
+
+    /// Pairs every entry of a leaf with its distance to `point`. Coincident
+    /// leaves evaluate the metric once for the whole bucket.
+    fn leaf_distances<'p, E, D, F>(
+        rows: Rows<'p, A, U>,
+        coincident: bool,
+        bucket: impl Iterator<Item = E> + 'p,
+        point: &'p [A],
+        distance: &'p F,
+    ) -> impl Iterator<Item = HeapElement<D, E>> + 'p
+    where
+        D: Distance + 'p,
+        F: Metric<A, D>,
+    {
+        let mut shared = None;
+        rows.cycle().zip(bucket).map(move |(p, d)| {
+            let dist = if coincident {
+                *shared.get_or_insert_with(|| distance.distance(point, p))
+            } else {
+                distance.distance(point, p)
+            };
+            HeapElement {
+                distance: dist,
+                element: d,
+            }
+        })
     }
 
-    fn distance_to_space<F, V>(p1: &[V], min_bounds: &[V], max_bounds: &[V], distance: &F) -> V
+    pub(crate) fn distance_to_space<D, F>(p1: &[A], min_bounds: &[A], max_bounds: &[A], distance: &F) -> D
     where
-        F: Fn(&[V], &[V]) -> V,
-        V: Float,
+        F: Metric<A, D>,
     {
-        let mut p2 = vec![V::nan(); p1.len()];
+        let mut p2 = p1.to_vec();
         for i in 0..p1.len() {
             if p1[i] > max_bounds[i] {
                 p2[i] = max_bounds[i];
             } else if p1[i] < min_bounds[i] {
                 p2[i] = min_bounds[i];
-            } else {
-                p2[i] = p1[i];
             }
         }
-        distance(p1, &p2[..])
+        distance.distance(p1, &p2[..])
     }
 
     // ============================================================================
-    // === WITHIN QUERIES ===
+    // === FARTHEST QUERIES ===
     // ============================================================================
-    pub fn within<F>(&self, point: &[A], radius: A, distance: &F) -> Result<Vec<(A, &T)>, ErrorKind>
+    /// Returns the `num` points farthest from `point`, farthest first
+    pub fn farthest<D, F>(&self, point: &[A], num: usize, distance: &F) -> Result<Vec<(D, &T)>, ErrorKind>
     where
-        F: Fn(&[A], &[A]) -> A,
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
     {
         self.check_point(point)?;
-        if self.size == 0 {
+        let num = std::cmp::min(num, self.size);
+        if num == 0 {
             return Ok(vec![]);
         }
-        let evaluated = self.evaluated_heap(point, radius, distance);
-        Ok(evaluated.into_iter().map(Into::into).collect())
+        let mut pending = BinaryHeap::new();
+        let mut evaluated = ClosestFirst::<D, &T>::new();
+        pending.push(HeapElement {
+            distance: D::MAX,
+            element: self,
+        });
+        while let Some(HeapElement {
+            distance: bound,
+            element: curr,
+        }) = pending.pop()
+        {
+            if evaluated.len() == num && evaluated.peek().is_some_and(|nearest| bound < nearest.0.distance) {
+                break;
+            }
+            if !curr.is_leaf() {
+                for child in [curr.left.as_ref().unwrap(), curr.right.as_ref().unwrap()] {
+                    if child.size > 0 {
+                        pending.push(HeapElement {
+                            distance: finite(Self::distance_to_farthest_corner(
+                                point,
+                                &child.min_bounds,
+                                &child.max_bounds,
+                                distance,
+                            ))?,
+                            element: &**child,
+                        });
+                    }
+                }
+                continue;
+            }
+            let rows = Self::rows(curr.points.as_ref().unwrap(), &curr.packed, curr.dimensions);
+            let bucket = curr.bucket.as_ref().unwrap().iter();
+            for element in Self::leaf_distances(rows, curr.coincident, bucket, point, distance) {
+                finite(element.distance)?;
+                if evaluated.len() < num {
+                    evaluated.push(Reverse(element));
+                } else if evaluated.peek().is_some_and(|nearest| element > nearest.0) {
+                    evaluated.pop();
+                    evaluated.push(Reverse(element));
+                }
+            }
+        }
+        Ok(evaluated
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(e)| e.into())
+            .collect())
     }
 
-    pub fn within_count<F>(&self, point: &[A], radius: A, distance: &F) -> Result<usize, ErrorKind>
+    /// Iterates over all points from the farthest to the closest to `point`. Subtrees are
+    /// expanded in order of the distance to the farthest corner of their bounding box.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`KdTree::iter_nearest`] for an invalid `point`. A non-finite distance ends
+    /// iteration early and is reported by [`FarthestIter::error`].
+    pub fn iter_farthest<'a, D, F>(
+        &'a self,
+        point: &'a [A],
+        distance: &'a F,
+    ) -> Result<FarthestIter<'a, A, T, U, F, D, S>, ErrorKind>
     where
-        F: Fn(&[A], &[A]) -> A,
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
     {
         self.check_point(point)?;
-        if self.size == 0 {
-            return Ok(0);
+        let mut pending = BinaryHeap::new();
+        if self.size > 0 {
+            pending.push(HeapElement {
+                distance: D::MAX,
+                element: self,
+            });
         }
-        let evaluated = self.evaluated_heap(point, radius, distance);
+        Ok(FarthestIter {
+            point,
+            pending,
+            evaluated: BinaryHeap::new(),
+            distance,
+            error: None,
+        })
+    }
+
+    pub(crate) fn distance_to_farthest_corner<D, F>(p1: &[A], min_bounds: &[A], max_bounds: &[A], distance: &F) -> D
+    where
+        F: Fn(&[A], &[A]) -> D,
+    {
+        let mut p2 = min_bounds.to_vec();
+        for i in 0..p1.len() {
+            if p1[i].abs_diff(max_bounds[i]) > p1[i].abs_diff(min_bounds[i]) {
+                p2[i] = max_bounds[i];
+            }
+        }
+        distance(p1, &p2[..])
+    }
+
+    // ============================================================================
+    // === REVERSE NEAREST QUERIES ===
+    // ============================================================================
+    /// Returns the entries that would have `point` among their `k` nearest neighbours,
+    /// counting every other stored entry but not themselves, in no particular order.
+    /// Subtrees with more than `k` entries are skipped when `point` is farther from them
+    /// than their bounding box is wide, since each entry inside has `k` neighbours closer
+    /// than that. Every remaining candidate runs a nearest query of its own; to answer
+    /// many queries, compute those once with [`KdTree::knn_radii`].
+    pub fn reverse_nearest<D, F>(&self, point: &[A], k: usize, distance: &F) -> Result<Vec<(&U, &T)>, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        self.check_point(point)?;
+        let mut found = vec![];
+        if k == 0 {
+            return Ok(found);
+        }
+        let mut pending = vec![self];
+        while let Some(curr) = pending.pop() {
+            if curr.size == 0 {
+                continue;
+            }
+            if curr.size > k {
+                let to_space = finite(Self::distance_to_space(
+                    point,
+                    &curr.min_bounds,
+                    &curr.max_bounds,
+                    distance,
+                ))?;
+                if to_space > finite(distance(&curr.min_bounds, &curr.max_bounds))? {
+                    continue;
+                }
+            }
+            if !curr.is_leaf() {
+                pending.push(curr.left.as_ref().unwrap());
+                pending.push(curr.right.as_ref().unwrap());
+                continue;
+            }
+            let points = curr.points.as_ref().unwrap();
+            let mut shared = None;
+            for (p, data) in points.iter().cycle().zip(curr.bucket.as_ref().unwrap()) {
+                let radius = match shared {
+                    Some(radius) => radius,
+                    None => self.knn_radius(p.as_ref(), k, distance)?,
+                };
+                if curr.coincident {
+                    shared = Some(radius);
+                }
+                if finite(distance(point, p.as_ref()))? <= radius {
+                    found.push((p, data));
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    /// Like [`KdTree::reverse_nearest`], using the radii computed beforehand by
+    /// [`KdTree::knn_radii`]. Subtrees are skipped when `point` is farther from them than
+    /// the largest radius inside.
+    pub fn reverse_nearest_cached<D, F>(
+        &self,
+        point: &[A],
+        radii: &KnnRadii<D>,
+        distance: &F,
+    ) -> Result<Vec<(&U, &T)>, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        self.check_point(point)?;
+        if radii.entries.len() != self.size {
+            return Err(ErrorKind::StaleKnnRadii);
+        }
+        let mut found = vec![];
+        if radii.k == 0 {
+            return Ok(found);
+        }
+        let mut pending = vec![(self, 0)];
+        while let Some((curr, index)) = pending.pop() {
+            let node = radii.nodes.get(index).ok_or(ErrorKind::StaleKnnRadii)?;
+            if curr.size == 0
+                || finite(Self::distance_to_space(
+                    point,
+                    &curr.min_bounds,
+                    &curr.max_bounds,
+                    distance,
+                ))? > node.max
+            {
+                continue;
+            }
+            if !curr.is_leaf() {
+                // the right subtree follows the left one in pre-order
+                let right = radii.nodes.get(index + 1).ok_or(ErrorKind::StaleKnnRadii)?.end;
+                pending.push((curr.left.as_ref().unwrap(), index + 1));
+                pending.push((curr.right.as_ref().unwrap(), right));
+                continue;
+            }
+            let entries = radii
+                .entries
+                .get(node.first..node.first + curr.size)
+                .ok_or(ErrorKind::StaleKnnRadii)?;
+            let points = curr.points.as_ref().unwrap().iter().cycle();
+            for ((p, data), &radius) in points.zip(curr.bucket.as_ref().unwrap()).zip(entries) {
+                if finite(distance(point, p.as_ref()))? <= radius {
+                    found.push((p, data));
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    /// Computes the distance from every entry to its `k`-th nearest other entry, the
+    /// all-kNN pass behind [`KdTree::reverse_nearest_cached`]. The radii describe the
+    /// tree as it is now and must be recomputed after it changes.
+    pub fn knn_radii<D, F>(&self, k: usize, distance: &F) -> Result<KnnRadii<D>, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        let mut radii = KnnRadii {
+            k,
+            nodes: vec![],
+            entries: Vec::with_capacity(self.size),
+        };
+        self.collect_radii(self, k, distance, &mut radii)?;
+        Ok(radii)
+    }
+
+    /// Appends the radii of this subtree in pre-order, returning the largest
+    fn collect_radii<D, F>(&self, root: &Self, k: usize, distance: &F, radii: &mut KnnRadii<D>) -> Result<D, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        let index = radii.nodes.len();
+        radii.nodes.push(NodeRadii {
+            max: D::ZERO,
+            end: 0,
+            first: radii.entries.len(),
+        });
+        let mut max = D::ZERO;
+        if self.is_leaf() {
+            let points = self.points.as_ref().unwrap();
+            let mut shared = None;
+            for p in points.iter().cycle().take(self.size) {
+                let radius = match shared {
+                    Some(radius) => radius,
+                    None => root.knn_radius(p.as_ref(), k, distance)?,
+                };
+                if self.coincident {
+                    shared = Some(radius);
+                }
+                if radius > max {
+                    max = radius;
+                }
+                radii.entries.push(radius);
+            }
+        } else {
+            for child in [self.left.as_ref().unwrap(), self.right.as_ref().unwrap()] {
+                let radius = child.collect_radii(root, k, distance, radii)?;
+                if radius > max {
+                    max = radius;
+                }
+            }
+        }
+        radii.nodes[index].max = max;
+        radii.nodes[index].end = radii.nodes.len();
+        Ok(max)
+    }
+
+    /// Distance from the stored point `point` to its `k`-th nearest other entry, or the
+    /// largest distance when there aren't `k` others
+    fn knn_radius<D, F>(&self, point: &[A], k: usize, distance: &F) -> Result<D, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        let nearest = self.nearest(point, k + 1, distance)?;
+        Ok(nearest.get(k).map_or(D::MAX, |&(d, _)| d))
+    }
+
+    // ============================================================================
+    // === WITHIN QUERIES ===
+    // ============================================================================
+    pub fn within<D, F>(&self, point: &[A], radius: D, distance: &F) -> Result<Vec<(D, &T)>, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        self.check_point(point)?;
+        if self.size == 0 {
+            return Ok(vec![]);
+        }
+        let evaluated = self.evaluated_heap(point, radius, distance, &Filter::all())?;
+        Ok(evaluated.into_iter().map(Into::into).collect())
+    }
+
+    pub fn within_count<D, F>(&self, point: &[A], radius: D, distance: &F) -> Result<usize, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        self.check_point(point)?;
+        if self.size == 0 {
+            return Ok(0);
+        }
+        let evaluated = self.evaluated_heap(point, radius, distance, &Filter::all())?;
         Ok(evaluated.len())
     }
 
+    /// Returns every point whose distance to `point` lies in `min_radius..=max_radius`, in
+    /// no particular order. Subtrees entirely inside the inner radius are skipped like those
+    /// entirely outside the outer one.
+    pub fn within_range<D, F>(
+        &self,
+        point: &[A],
+        min_radius: D,
+        max_radius: D,
+        distance: &F,
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        let mut found = vec![];
+        self.range_scan(point, min_radius, max_radius, distance, None, &mut found)?;
+        Ok(found)
+    }
+
+    /// Counts the points [`KdTree::within_range`] would return. Subtrees that lie entirely
+    /// within the range are counted without visiting their points.
+    pub fn within_range_count<D, F>(
+        &self,
+        point: &[A],
+        min_radius: D,
+        max_radius: D,
+        distance: &F,
+    ) -> Result<usize, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        let mut whole = 0;
+        let mut found = vec![];
+        self.range_scan(point, min_radius, max_radius, distance, Some(&mut whole), &mut found)?;
+        Ok(whole + found.len())
+    }
+
+    /// Collects the points within the range into `found`. With `whole`, subtrees that
+    /// lie entirely within the range only add their size to it.
+    fn range_scan<'s, D, F>(
+        &'s self,
+        point: &[A],
+        min_radius: D,
+        max_radius: D,
+        distance: &F,
+        mut whole: Option<&mut usize>,
+        found: &mut Vec<(D, &'s T)>,
+    ) -> Result<(), ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        self.check_point(point)?;
+        if min_radius > max_radius {
+            return Err(ErrorKind::InvalidDistanceRange);
+        }
+        let mut pending = vec![self];
+        while let Some(curr) = pending.pop() {
+            if curr.size == 0 {
+                continue;
+            }
+            let nearest = finite(Self::distance_to_space(
+                point,
+                &curr.min_bounds,
+                &curr.max_bounds,
+                distance,
+            ))?;
+            if nearest > max_radius {
+                continue;
+            }
+            let farthest = finite(Self::distance_to_farthest_corner(
+                point,
+                &curr.min_bounds,
+                &curr.max_bounds,
+                distance,
+            ))?;
+            if farthest < min_radius {
+                continue;
+            }
+            let inside = nearest >= min_radius && farthest <= max_radius;
+            if let (true, Some(count)) = (inside, whole.as_deref_mut()) {
+                *count += curr.size;
+                continue;
+            }
+            if !curr.is_leaf() {
+                pending.push(curr.left.as_ref().unwrap());
+                pending.push(curr.right.as_ref().unwrap());
+                continue;
+            }
+            let rows = Self::rows(curr.points.as_ref().unwrap(), &curr.packed, curr.dimensions);
+            let bucket = curr.bucket.as_ref().unwrap().iter();
+            for element in Self::leaf_distances(rows, curr.coincident, bucket, point, distance) {
+                let dist = finite(element.distance)?;
+                if min_radius <= dist && dist <= max_radius {
+                    found.push(element.into());
+                }
+            }
+        }
+        Ok(())
+    }
+
     // ============================================================================
     // === BOUNDING BOX ===
     // ============================================================================
 
     pub fn bounding_box(&self, min_bounds: &[A], max_bounds: &[A]) -> Result<Vec<&T>, ErrorKind> {
-        self.check_point(min_bounds)?;
-        self.check_point(max_bounds)?;
+        let filter = Filter::all();
+        self.bounding_box_internal(min_bounds, max_bounds, &filter)
+    }
+
+    fn bounding_box_internal<N, P>(
+        &self,
+        min_bounds: &[A],
+        max_bounds: &[A],
+        filter: &Filter<N, P>,
+    ) -> Result<Vec<&T>, ErrorKind>
+    where
+        N: Fn(&S) -> bool,
+        P: Fn(&U, &T) -> bool,
+    {
+        self.check_bounding_box(min_bounds, max_bounds)?;
         if self.size == 0 {
             return Ok(vec![]);
         }
@@ -431,11 +1437,21 @@ impl<A: Float + Zero + One, T, U: AsRef<[A]>> KdTree<A, T, U> {
         let mut evaluated = vec![];
         pending.push(self);
         while let Some(curr) = pending.pop() {
+            if !(filter.subtree)(&curr.summary) {
+                continue;
+            }
             if curr.is_leaf() {
-                let points = curr.points.as_ref().unwrap().iter();
-                let bucket = curr.bucket.as_ref().unwrap().iter();
-                for (p, b) in points.zip(bucket) {
-                    if Self::in_bounding_box(p.as_ref(), min_bounds, max_bounds) {
+                let mut rows = Self::rows(curr.points.as_ref().unwrap(), &curr.packed, curr.dimensions);
+                let bucket = curr.bucket.as_ref().unwrap();
+                let points = curr.points.as_ref().unwrap().iter().cycle();
+                if curr.coincident {
+                    if Self::in_bounding_box(rows.next().unwrap(), min_bounds, max_bounds) {
+                        evaluated.extend(points.zip(bucket).filter(|(p, b)| (filter.entry)(p, b)).map(|(_, b)| b));
+                    }
+                    continue;
+                }
+                for ((p, u), b) in rows.zip(points).zip(bucket.iter()) {
+                    if Self::in_bounding_box(p, min_bounds, max_bounds) && (filter.entry)(u, b) {
                         evaluated.push(b);
                     }
                 }
@@ -452,10 +1468,16 @@ impl<A: Float + Zero + One, T, U: AsRef<[A]>> KdTree<A, T, U> {
         Ok(evaluated)
     }
 
-    fn in_bounding_box<V>(p: &[V], min_bounds: &[V], max_bounds: &[V]) -> bool
-    where
-        V: Float,
-    {
+    fn check_bounding_box(&self, min_bounds: &[A], max_bounds: &[A]) -> Result<(), ErrorKind> {
+        self.check_point(min_bounds)?;
+        self.check_point(max_bounds)?;
+        match min_bounds.iter().zip(max_bounds).position(|(l, h)| l > h) {
+            Some(axis) => Err(ErrorKind::InvalidBoundingBox { axis }),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn in_bounding_box(p: &[A], min_bounds: &[A], max_bounds: &[A]) -> bool {
         for ((l, h), v) in min_bounds.iter().zip(max_bounds.iter()).zip(p) {
             if v < l || v > h {
                 return false;
@@ -464,66 +1486,928 @@ impl<A: Float + Zero + One, T, U: AsRef<[A]>> KdTree<A, T, U> {
         true
     }
 
+    // ============================================================================
+    // === REGION QUERIES ===
+    // ============================================================================
+    /// Returns every point inside `region`, in no particular order
+    pub fn query_region<R: Region<A>>(&self, region: &R) -> Result<Vec<&T>, ErrorKind> {
+        Ok(self.iter_region(region)?.collect())
+    }
+
+    /// Iterates over every point inside `region`, in no particular order
+    pub fn iter_region<'a, 'r, R: Region<A>>(
+        &'a self,
+        region: &'r R,
+    ) -> Result<RegionIter<'a, 'r, A, T, U, R, S>, ErrorKind> {
+        self.check_dimensions(region.dimensions())?;
+        Ok(RegionIter {
+            region,
+            pending: vec![(self, false)],
+            leaf: None,
+        })
+    }
+
+    /// Counts the points inside `region`. Subtrees the region contains entirely are
+    /// counted without visiting their points.
+    pub fn count_region<R: Region<A>>(&self, region: &R) -> Result<usize, ErrorKind> {
+        self.check_dimensions(region.dimensions())?;
+        let mut count = 0;
+        let mut pending = vec![self];
+        while let Some(curr) = pending.pop() {
+            if curr.size == 0 || !region.intersects_box(&curr.min_bounds, &curr.max_bounds) {
+                continue;
+            }
+            if region.contains_box(&curr.min_bounds, &curr.max_bounds) {
+                count += curr.size;
+            } else if !curr.is_leaf() {
+                pending.push(curr.left.as_ref().unwrap());
+                pending.push(curr.right.as_ref().unwrap());
+            } else {
+                let mut rows = Self::rows(curr.points.as_ref().unwrap(), &curr.packed, curr.dimensions);
+                if curr.coincident {
+                    if region.contains(rows.next().unwrap()) {
+                        count += curr.size;
+                    }
+                } else {
+                    count += rows.filter(|p| region.contains(p)).count();
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// Checks the dimensions a region or shape is defined for, if it names any
+    fn check_dimensions(&self, dimensions: Option<usize>) -> Result<(), ErrorKind> {
+        match dimensions {
+            Some(actual) if actual != self.dimensions => Err(ErrorKind::WrongDimension),
+            _ => Ok(()),
+        }
+    }
+
+    // ============================================================================
+    // === SHAPE QUERIES ===
+    // ============================================================================
+    /// Returns the `num` points nearest to `shape`, nearest first, as measured by the
+    /// shape itself. Subtrees are pruned by the shape's distance to their bounding box.
+    pub fn nearest_to_shape<Q: Shape<A>>(&self, shape: &Q, num: usize) -> Result<Vec<(Q::Distance, &T)>, ErrorKind> {
+        self.check_dimensions(shape.dimensions())?;
+        self.shape_scan(shape, num, Q::Distance::MAX)
+    }
+
+    /// Returns every point whose distance to `shape` is at most `radius`, nearest first
+    pub fn within_shape<Q: Shape<A>>(
+        &self,
+        shape: &Q,
+        radius: Q::Distance,
+    ) -> Result<Vec<(Q::Distance, &T)>, ErrorKind> {
+        self.check_dimensions(shape.dimensions())?;
+        self.shape_scan(shape, self.size, radius)
+    }
+
+    fn shape_scan<Q: Shape<A>>(
+        &self,
+        shape: &Q,
+        num: usize,
+        radius: Q::Distance,
+    ) -> Result<Vec<(Q::Distance, &T)>, ErrorKind> {
+        let num = std::cmp::min(num, self.size);
+        if num == 0 {
+            return Ok(vec![]);
+        }
+        let mut pending = ClosestFirst::<Q::Distance, &Self>::new();
+        let mut evaluated = BinaryHeap::<HeapElement<Q::Distance, &T>>::new();
+        pending.push(Reverse(HeapElement {
+            distance: Q::Distance::ZERO,
+            element: self,
+        }));
+        while let Some(Reverse(HeapElement {
+            distance: bound,
+            element: curr,
+        })) = pending.pop()
+        {
+            let worst = match evaluated.peek() {
+                Some(worst) if evaluated.len() == num && worst.distance < radius => worst.distance,
+                _ => radius,
+            };
+            if bound > worst {
+                break;
+            }
+            if !curr.is_leaf() {
+                for child in [curr.left.as_ref().unwrap(), curr.right.as_ref().unwrap()] {
+                    if child.size == 0 {
+                        continue;
+                    }
+                    let bound = finite(shape.min_distance_to_box(&child.min_bounds, &child.max_bounds))?;
+                    if bound <= worst {
+                        pending.push(Reverse(HeapElement {
+                            distance: bound,
+                            element: &**child,
+                        }));
+                    }
+                }
+                continue;
+            }
+            let rows = Self::rows(curr.points.as_ref().unwrap(), &curr.packed, curr.dimensions);
+            let mut shared = None;
+            for (p, data) in rows.cycle().zip(curr.bucket.as_ref().unwrap()) {
+                let dist = match shared {
+                    Some(dist) => dist,
+                    None => finite(shape.distance_to_point(p))?,
+                };
+                if curr.coincident {
+                    shared = Some(dist);
+                }
+                if dist > radius {
+                    continue;
+                }
+                let element = HeapElement {
+                    distance: dist,
+                    element: data,
+                };
+                if evaluated.len() < num {
+                    evaluated.push(element);
+                } else if evaluated.peek().is_some_and(|worst| element < *worst) {
+                    evaluated.pop();
+                    evaluated.push(element);
+                }
+            }
+        }
+        Ok(evaluated.into_sorted_vec().into_iter().map(Into::into).collect())
+    }
+
+    // ============================================================================
+    // === SUMMARY QUERIES ===
+    // ============================================================================
+    /// Like [`KdTree::nearest_filtered`], but subtrees whose [`Summary`] fails `subtree`
+    /// are skipped without visiting their entries. `subtree` must return `true` for any
+    /// subtree that may hold an entry passing `entry`.
+    pub fn nearest_pruned<D, F, N, P>(
+        &self,
+        point: &[A],
+        num: usize,
+        distance: &F,
+        subtree: N,
+        entry: P,
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+        N: Fn(&S) -> bool,
+        P: Fn(&U, &T) -> bool,
+    {
+        self.nearest_within_radius_internal(point, num, D::MAX, distance, Filter { subtree, entry })
+    }
+
+    /// Like [`KdTree::within`], returning only entries passing `entry` and skipping
+    /// subtrees whose [`Summary`] fails `subtree`
+    pub fn within_pruned<D, F, N, P>(
+        &self,
+        point: &[A],
+        radius: D,
+        distance: &F,
+        subtree: N,
+        entry: P,
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+        N: Fn(&S) -> bool,
+        P: Fn(&U, &T) -> bool,
+    {
+        self.check_point(point)?;
+        if self.size == 0 {
+            return Ok(vec![]);
+        }
+        let evaluated = self.evaluated_heap(point, radius, distance, &Filter { subtree, entry })?;
+        Ok(evaluated.into_iter().map(Into::into).collect())
+    }
+
+    /// Like [`KdTree::bounding_box`], returning only entries passing `entry` and skipping
+    /// subtrees whose [`Summary`] fails `subtree`
+    pub fn bounding_box_pruned<N, P>(
+        &self,
+        min_bounds: &[A],
+        max_bounds: &[A],
+        subtree: N,
+        entry: P,
+    ) -> Result<Vec<&T>, ErrorKind>
+    where
+        N: Fn(&S) -> bool,
+        P: Fn(&U, &T) -> bool,
+    {
+        self.bounding_box_internal(min_bounds, max_bounds, &Filter { subtree, entry })
+    }
+
+    /// Summary of the payloads whose points lie in the box. Subtrees entirely inside
+    /// the box contribute their stored summary without visiting their entries.
+    pub fn summarize_box(&self, min_bounds: &[A], max_bounds: &[A]) -> Result<S, ErrorKind> {
+        self.aggregate_box(
+            min_bounds,
+            max_bounds,
+            S::empty(),
+            |summary, subtree, _| {
+                summary.merge(subtree);
+                true
+            },
+            |summary, data| summary.add(data),
+        )
+    }
+
+    // ============================================================================
+    // === AGGREGATE QUERIES ===
+    // ============================================================================
+    /// Folds every entry within `radius` of `point` into `init`, in no particular order.
+    /// Each subtree lying entirely within the radius is first offered to `subtree` with
+    /// its [`Summary`] and number of entries; when that returns `false` its entries are
+    /// folded one at a time by `entry`, along with their distance.
+    pub fn aggregate_within<B, D, F, N, E>(
+        &self,
+        point: &[A],
+        radius: D,
+        distance: &F,
+        init: B,
+        mut subtree: N,
+        mut entry: E,
+    ) -> Result<B, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+        N: FnMut(&mut B, &S, usize) -> bool,
+        E: FnMut(&mut B, D, &T),
+    {
+        self.check_point(point)?;
+        let mut acc = init;
+        let mut pending = vec![self];
+        while let Some(curr) = pending.pop() {
+            if curr.size == 0
+                || finite(Self::distance_to_space(
+                    point,
+                    &curr.min_bounds,
+                    &curr.max_bounds,
+                    distance,
+                ))? > radius
+            {
+                continue;
+            }
+            let farthest = finite(Self::distance_to_farthest_corner(
+                point,
+                &curr.min_bounds,
+                &curr.max_bounds,
+                distance,
+            ))?;
+            if farthest <= radius && subtree(&mut acc, &curr.summary, curr.size) {
+                continue;
+            }
+            if !curr.is_leaf() {
+                pending.push(curr.left.as_ref().unwrap());
+                pending.push(curr.right.as_ref().unwrap());
+                continue;
+            }
+            let rows = Self::rows(curr.points.as_ref().unwrap(), &curr.packed, curr.dimensions);
+            let bucket = curr.bucket.as_ref().unwrap().iter();
+            for element in Self::leaf_distances(rows, curr.coincident, bucket, point, distance) {
+                let dist = finite(element.distance)?;
+                if dist <= radius {
+                    entry(&mut acc, dist, element.element);
+                }
+            }
+        }
+        Ok(acc)
+    }
+
+    /// Folds every entry inside the box into `init`, in no particular order. Each
+    /// subtree lying entirely inside is first offered to `subtree` with its [`Summary`]
+    /// and number of entries; when that returns `false` its entries are folded one at a
+    /// time by `entry`.
+    pub fn aggregate_box<B, N, E>(
+        &self,
+        min_bounds: &[A],
+        max_bounds: &[A],
+        init: B,
+        mut subtree: N,
+        mut entry: E,
+    ) -> Result<B, ErrorKind>
+    where
+        N: FnMut(&mut B, &S, usize) -> bool,
+        E: FnMut(&mut B, &T),
+    {
+        self.check_bounding_box(min_bounds, max_bounds)?;
+        let mut acc = init;
+        let mut pending = vec![self];
+        while let Some(curr) = pending.pop() {
+            if curr.size == 0 || !Self::boxes_overlap(&curr.min_bounds, &curr.max_bounds, min_bounds, max_bounds) {
+                continue;
+            }
+            let inside = Self::in_bounding_box(&curr.min_bounds, min_bounds, max_bounds)
+                && Self::in_bounding_box(&curr.max_bounds, min_bounds, max_bounds);
+            if inside && subtree(&mut acc, &curr.summary, curr.size) {
+                continue;
+            }
+            if !curr.is_leaf() {
+                pending.push(curr.left.as_ref().unwrap());
+                pending.push(curr.right.as_ref().unwrap());
+                continue;
+            }
+            let bucket = curr.bucket.as_ref().unwrap();
+            let mut rows = Self::rows(curr.points.as_ref().unwrap(), &curr.packed, curr.dimensions);
+            if curr.coincident {
+                if Self::in_bounding_box(rows.next().unwrap(), min_bounds, max_bounds) {
+                    bucket.iter().for_each(|data| entry(&mut acc, data));
+                }
+                continue;
+            }
+            for (p, data) in rows.zip(bucket) {
+                if Self::in_bounding_box(p, min_bounds, max_bounds) {
+                    entry(&mut acc, data);
+                }
+            }
+        }
+        Ok(acc)
+    }
+
+    /// Counts the points inside the box without collecting them
+    pub fn bounding_box_count(&self, min_bounds: &[A], max_bounds: &[A]) -> Result<usize, ErrorKind> {
+        self.aggregate_box(
+            min_bounds,
+            max_bounds,
+            0,
+            |count, _, size| {
+                *count += size;
+                true
+            },
+            |count, _| *count += 1,
+        )
+    }
+
+    /// Kernel density estimate at `point`: the sum of `weight(data)` scaled by the kernel
+    /// over every entry, unnormalised. `distance` must return squared distances, such as
+    /// [`squared_euclidean`](crate::distance::squared_euclidean), which the kernel
+    /// measures in squared `bandwidth`s. Entries beyond the kernel's cutoff are skipped.
+    ///
+    /// Fails with [`ErrorKind::InvalidBandwidth`] unless `bandwidth` and a Gaussian
+    /// kernel's cutoff are finite and positive.
+    pub fn kernel_density<F, W>(
+        &self,
+        point: &[A],
+        bandwidth: f64,
+        kernel: DensityKernel,
+        distance: &F,
+        weight: W,
+    ) -> Result<f64, ErrorKind>
+    where
+        F: Fn(&[A], &[A]) -> f64,
+        W: Fn(&T) -> f64,
+    {
+        let cutoff = match kernel {
+            DensityKernel::Gaussian { cutoff } => cutoff,
+            DensityKernel::Epanechnikov => 1.0,
+        };
+        if ![bandwidth, cutoff].iter().all(|v| v.is_finite() && *v > 0.0) {
+            return Err(ErrorKind::InvalidBandwidth);
+        }
+        let scale = bandwidth * bandwidth;
+        self.aggregate_within(
+            point,
+            kernel.support() * scale,
+            distance,
+            0.0,
+            |_, _, _| false,
+            |density, dist, data| *density += weight(data) * kernel.evaluate(dist / scale),
+        )
+    }
+
+    fn boxes_overlap(min1: &[A], max1: &[A], min2: &[A], max2: &[A]) -> bool {
+        let mut axes = min1.iter().zip(max1).zip(min2.iter().zip(max2));
+        axes.all(|((l1, h1), (l2, h2))| l1 <= h2 && h1 >= l2)
+    }
+
     // ============================================================================
     // === SHARED TRAVERSAL UTILITIES ===
     // ============================================================================
     #[inline(always)]
-    fn evaluated_heap<F>(&self, point: &[A], radius: A, distance: &F) -> BinaryHeap<HeapElement<A, &T>>
+    fn evaluated_heap<D, F, N, P>(
+        &self,
+        point: &[A],
+        radius: D,
+        distance: &F,
+        filter: &Filter<N, P>,
+    ) -> Result<BinaryHeap<HeapElement<D, &T>>, ErrorKind>
     where
-        F: Fn(&[A], &[A]) -> A,
+        D: Distance,
+        F: Metric<A, D>,
+        N: Fn(&S) -> bool,
+        P: Fn(&U, &T) -> bool,
     {
         let mut pending = BinaryHeap::new();
-        let mut evaluated = BinaryHeap::<HeapElement<A, &T>>::new();
-        pending.push(HeapElement {
-            distance: A::zero(),
+        let mut evaluated = BinaryHeap::<HeapElement<D, &T>>::new();
+        let mut scratch = Vec::new();
+        pending.push(Reverse(HeapElement {
+            distance: D::ZERO,
             element: self,
-        });
-        while !pending.is_empty() && (-pending.peek().unwrap().distance <= radius) {
-            self.nearest_step(point, self.size, radius, distance, &mut pending, &mut evaluated);
+        }));
+        while pending.peek().is_some_and(|next| next.0.distance <= radius) {
+            Self::nearest_step(
+                point,
+                self.size,
+                radius,
+                distance,
+                filter,
+                &mut pending,
+                &mut evaluated,
+                &mut scratch,
+            )?;
         }
-        evaluated
+        Ok(evaluated)
     }
 
     fn belongs_in_left(&self, point: &[A]) -> bool {
-        if self.min_bounds[self.split_dimension.unwrap()] == self.split_value.unwrap() {
-            point[self.split_dimension.unwrap()] <= self.split_value.unwrap()
+        point[self.split_dimension.unwrap()] < self.split_value.unwrap()
+    }
+
+    pub(crate) fn extend(&mut self, point: &[A]) {
+        let min = self.min_bounds.iter_mut();
+        let max = self.max_bounds.iter_mut();
+        for ((l, h), v) in min.zip(max).zip(point.iter()) {
+            if v < l {
+                *l = *v
+            }
+            if v > h {
+                *h = *v
+            }
+        }
+    }
+
+    /// Recomputes bounds from the points of a leaf or the bounds of a stem's children
+    pub(crate) fn refresh_bounds(&mut self) {
+        self.min_bounds.fill(A::HIGHEST);
+        self.max_bounds.fill(A::LOWEST);
+        if let Some(points) = self.points.take() {
+            for p in points.iter() {
+                self.extend(p.as_ref());
+            }
+            self.points = Some(points);
+        } else if let (Some(left), Some(right)) = (self.left.take(), self.right.take()) {
+            for child in [&left, &right].into_iter().filter(|child| child.size > 0) {
+                self.extend(&child.min_bounds);
+                self.extend(&child.max_bounds);
+            }
+            self.left = Some(left);
+            self.right = Some(right);
+        }
+    }
+
+    pub(crate) fn is_leaf(&self) -> bool {
+        self.bucket.is_some()
+            && self.points.is_some()
+            && self.split_value.is_none()
+            && self.split_dimension.is_none()
+            && self.left.is_none()
+            && self.right.is_none()
+    }
+
+    fn empty_child(&self) -> Self {
+        let mut child = KdTree::with_summary(self.dimensions, self.capacity);
+        child.pack_leaves = self.pack_leaves;
+        child
+    }
+
+    /// Recomputes the summary from the payloads of a leaf or the summaries of a stem's children
+    pub(crate) fn resummarize(&mut self) {
+        let mut summary = S::empty();
+        if let Some(bucket) = self.bucket.as_ref() {
+            for data in bucket.iter() {
+                summary.add(data);
+            }
+        } else {
+            for child in [self.left.as_ref(), self.right.as_ref()].into_iter().flatten() {
+                summary.merge(&child.summary);
+            }
+        }
+        self.summary = summary;
+    }
+
+    /// Rebuilds the packed copies of a leaf's points after they changed
+    pub(crate) fn repack(&mut self) {
+        self.packed.clear();
+        self.columns = Columns::default();
+        if !self.pack_leaves {
+            return;
+        }
+        if let Some(points) = self.points.take() {
+            for p in points.iter() {
+                self.pack(p.as_ref());
+            }
+            self.points = Some(points);
+        }
+    }
+
+    /// Appends a point to the packed copies of a leaf
+    fn pack(&mut self, point: &[A]) {
+        if self.columns.dimensions() != self.dimensions {
+            self.columns = Columns::new(self.dimensions);
+        }
+        self.packed.extend_from_slice(point);
+        self.columns.push_unchecked(point.iter().copied());
+    }
+
+    /// Coordinates of a leaf's points, read from the packed block when there is one
+    pub(crate) fn rows<'a>(points: &'a [U], packed: &'a [A], dimensions: usize) -> Rows<'a, A, U> {
+        if packed.is_empty() {
+            Rows::Points(points.iter())
         } else {
-            point[self.split_dimension.unwrap()] < self.split_value.unwrap()
+            Rows::Packed(packed.chunks_exact(dimensions))
+        }
+    }
+}
+
+impl<T, U: AsRef<[f32]>, S: Summary<T>> KdTree<f32, T, U, S> {
+    // ============================================================================
+    // === KERNEL QUERIES ===
+    // ============================================================================
+    /// Like [`KdTree::nearest`], measuring with a [`Kernel`] that evaluates the packed
+    /// leaves of a tree created with [`KdTree::with_packed_leaves`] a whole leaf at a time.
+    /// Leaves that aren't packed are measured one point at a time with the same results.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`KdTree::nearest`], and with [`ErrorKind::NotADistance`] for
+    /// [`Kernel::Dot`].
+    pub fn nearest_kernel(&self, point: &[f32], num: usize, kernel: Kernel) -> Result<Vec<(f32, &T)>, ErrorKind> {
+        kernel.check_distance()?;
+        self.nearest_within_radius_internal(point, num, f32::MAX, &kernel, Filter::all())
+    }
+
+    /// Like [`KdTree::within`], measuring with a [`Kernel`] like [`KdTree::nearest_kernel`]
+    pub fn within_kernel(&self, point: &[f32], radius: f32, kernel: Kernel) -> Result<Vec<(f32, &T)>, ErrorKind> {
+        kernel.check_distance()?;
+        self.check_point(point)?;
+        if self.size == 0 {
+            return Ok(vec![]);
+        }
+        let evaluated = self.evaluated_heap(point, radius, &kernel, &Filter::all())?;
+        Ok(evaluated.into_iter().map(Into::into).collect())
+    }
+
+    /// Like [`KdTree::within_count`], measuring with a [`Kernel`] like [`KdTree::nearest_kernel`]
+    pub fn within_count_kernel(&self, point: &[f32], radius: f32, kernel: Kernel) -> Result<usize, ErrorKind> {
+        kernel.check_distance()?;
+        self.check_point(point)?;
+        if self.size == 0 {
+            return Ok(0);
+        }
+        let evaluated = self.evaluated_heap(point, radius, &kernel, &Filter::all())?;
+        Ok(evaluated.len())
+    }
+}
+
+pub(crate) enum Rows<'a, A, U> {
+    Points(std::slice::Iter<'a, U>),
+    Packed(std::slice::ChunksExact<'a, A>),
+}
+
+impl<A, U> Clone for Rows<'_, A, U> {
+    fn clone(&self) -> Self {
+        match self {
+            Rows::Points(points) => Rows::Points(points.clone()),
+            Rows::Packed(packed) => Rows::Packed(packed.clone()),
+        }
+    }
+}
+
+impl<'a, A, U: AsRef<[A]>> Iterator for Rows<'a, A, U> {
+    type Item = &'a [A];
+
+    fn next(&mut self) -> Option<&'a [A]> {
+        match self {
+            Rows::Points(points) => points.next().map(AsRef::as_ref),
+            Rows::Packed(packed) => packed.next(),
+        }
+    }
+}
+
+/// Predicates of a pruned query: `subtree` rules out whole subtrees by their summary,
+/// `entry` decides for each remaining entry
+struct Filter<N, P> {
+    subtree: N,
+    entry: P,
+}
+
+impl Filter<(), ()> {
+    fn all<S, U, T>() -> Filter<impl Fn(&S) -> bool, impl Fn(&U, &T) -> bool> {
+        Filter {
+            subtree: |_: &S| true,
+            entry: |_: &U, _: &T| true,
+        }
+    }
+
+    fn entries<S, P>(entry: P) -> Filter<impl Fn(&S) -> bool, P> {
+        Filter {
+            subtree: |_: &S| true,
+            entry,
+        }
+    }
+}
+
+impl<A: Coordinate, T, U: AsRef<[A]>> KdTree<A, T, U> {
+    // ============================================================================
+    // === MUTABLE PAYLOAD ACCESS ===
+    // ============================================================================
+    // Only trees without a summary hand out mutable payloads, a summary couldn't
+    // follow changes made through them.
+
+    /// Mutable counterpart of [`KdTree::get`].
+    pub fn get_mut(&mut self, point: &[A]) -> Result<Option<&mut T>, ErrorKind> {
+        self.check_point(point)?;
+        Ok(self.lookup_mut(point))
+    }
+
+    /// Gets the entry at exactly `point` for in-place manipulation, similar to `HashMap::entry`.
+    pub fn entry(&mut self, point: U) -> Result<Entry<'_, A, T, U>, ErrorKind> {
+        if self.capacity == 0 {
+            return Err(ErrorKind::ZeroCapacity);
         }
+        self.check_point(point.as_ref())?;
+        // a shared lookup first, so that the exclusive borrow is taken once by whichever entry results
+        match self.leaf_for(point.as_ref()).position_in_leaf(point.as_ref()) {
+            Some(index) => {
+                let leaf = self.leaf_for_mut(point.as_ref());
+                Ok(Entry::Occupied(OccupiedEntry {
+                    data: &mut leaf.bucket.as_mut().unwrap()[index],
+                }))
+            }
+            None => Ok(Entry::Vacant(VacantEntry { tree: self, point })),
+        }
+    }
+
+    /// Mutable counterpart of [`KdTree::iter_nearest`].
+    ///
+    /// # Errors
+    ///
+    /// As for [`KdTree::iter_nearest`]; a non-finite distance ends iteration early and is
+    /// reported by [`NearestIterMut::error`].
+    pub fn iter_nearest_mut<'a, D, F>(
+        &'a mut self,
+        point: &'a [A],
+        distance: &'a F,
+    ) -> Result<NearestIterMut<'a, A, T, U, F, D>, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        let radius_iter = self.iter_nearest_within_radius_mut(point, None, distance)?;
+        Ok(NearestIterMut { inner: radius_iter })
+    }
+
+    /// Mutable counterpart of [`KdTree::iter_nearest_within_radius`].
+    ///
+    /// # Errors
+    ///
+    /// As for [`KdTree::iter_nearest`]; a non-finite distance ends iteration early and is
+    /// reported by [`NearestWithinRadiusIterMut::error`].
+    pub fn iter_nearest_within_radius_mut<'a, D, F>(
+        &'a mut self,
+        point: &'a [A],
+        radius: Option<D>,
+        distance: &'a F,
+    ) -> Result<NearestWithinRadiusIterMut<'a, A, T, U, F, D>, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        self.check_point(point)?;
+        let mut pending = BinaryHeap::new();
+        let evaluated = BinaryHeap::<Reverse<HeapElement<D, &mut T>>>::new();
+        pending.push(Reverse(HeapElement {
+            distance: D::ZERO,
+            element: self,
+        }));
+        Ok(NearestWithinRadiusIterMut {
+            point,
+            pending,
+            evaluated,
+            distance,
+            radius: radius.unwrap_or(D::MAX),
+            error: None,
+        })
     }
+}
+
+impl<A: Coordinate + Into<f64>, T, U: AsRef<[A]>, S: Summary<T>> KdTree<A, T, U, S> {
+    // ============================================================================
+    // === SIMILARITY QUERIES ===
+    // ============================================================================
+    /// Returns the `num` points with the largest dot product with `point`, largest first.
+    /// Subtrees are skipped when the largest dot product any point in their bounding box
+    /// could reach is below the scores already found.
+    pub fn max_inner_product(&self, point: &[A], num: usize) -> Result<Vec<(f64, &T)>, ErrorKind> {
+        self.check_point(point)?;
+        let query = point.iter().map(|&v| v.into()).collect::<Vec<f64>>();
+        self.most_similar(
+            num,
+            |p| Some(dot(&query, p)),
+            |min, max| dot_upper_bound(&query, min, max),
+        )
+    }
+
+    /// Returns the `num` points with the largest cosine similarity to `point`, largest first.
+    /// Stored points at the origin have no direction and are never returned, a query at the
+    /// origin fails with [`ErrorKind::ZeroVector`].
+    pub fn nearest_cosine(&self, point: &[A], num: usize) -> Result<Vec<(f64, &T)>, ErrorKind> {
+        self.check_point(point)?;
+        let query = point.iter().map(|&v| v.into()).collect::<Vec<f64>>();
+        let query_norm = norm(query.iter().copied());
+        if query_norm == 0.0 {
+            return Err(ErrorKind::ZeroVector);
+        }
+        self.most_similar(
+            num,
+            |p| {
+                let p_norm = norm(p.iter().map(|&v| v.into()));
+                (p_norm > 0.0).then(|| dot(&query, p) / (query_norm * p_norm))
+            },
+            |min, max| {
+                let bound = dot_upper_bound(&query, min, max);
+                // the point of the box closest to the origin gives the largest positive cosine,
+                // the farthest corner the largest negative one
+                let norms = min.iter().zip(max).map(|(&l, &h)| {
+                    let (l, h): (f64, f64) = (l.into(), h.into());
+                    (l.max(0.0).min(h), if -l > h { l } else { h })
+                });
+                let (closest, farthest): (Vec<f64>, Vec<f64>) = norms.unzip();
+                if bound >= 0.0 {
+                    let closest = norm(closest.into_iter());
+                    // a box touching the origin holds points of every direction it spans, and
+                    // rounding can put their cosine just above one
+                    if closest == 0.0 {
+                        f64::MAX
+                    } else {
+                        bound / (query_norm * closest)
+                    }
+                } else {
+                    bound / (query_norm * norm(farthest.into_iter()))
+                }
+            },
+        )
+    }
+
+    /// Best-first search for the highest scores, `bound` must not be below the score
+    /// of any point inside the box it is given. Points scored `None` are skipped.
+    fn most_similar<G, B>(&self, num: usize, score: G, bound: B) -> Result<Vec<(f64, &T)>, ErrorKind>
+    where
+        G: Fn(&[A]) -> Option<f64>,
+        B: Fn(&[A], &[A]) -> f64,
+    {
+        let num = std::cmp::min(num, self.size);
+        if num == 0 {
+            return Ok(vec![]);
+        }
+        let mut pending = BinaryHeap::new();
+        let mut evaluated = ClosestFirst::<f64, &T>::new();
+        pending.push(HeapElement {
+            distance: f64::INFINITY,
+            element: self,
+        });
+        while let Some(HeapElement {
+            distance,
+            element: curr,
+        }) = pending.pop()
+        {
+            if evaluated.len() == num && evaluated.peek().is_some_and(|worst| distance < worst.0.distance) {
+                break;
+            }
+            if !curr.is_leaf() {
+                for child in [curr.left.as_ref().unwrap(), curr.right.as_ref().unwrap()] {
+                    if child.size > 0 {
+                        pending.push(HeapElement {
+                            distance: finite(bound(&child.min_bounds, &child.max_bounds))?,
+                            element: &**child,
+                        });
+                    }
+                }
+                continue;
+            }
+            let rows = Self::rows(curr.points.as_ref().unwrap(), &curr.packed, curr.dimensions);
+            let mut shared = None;
+            for (p, element) in rows.cycle().zip(curr.bucket.as_ref().unwrap()) {
+                let similarity = if curr.coincident {
+                    *shared.get_or_insert_with(|| score(p))
+                } else {
+                    score(p)
+                };
+                let Some(similarity) = similarity else {
+                    continue;
+                };
+                let candidate = Reverse(HeapElement {
+                    distance: finite(similarity)?,
+                    element,
+                });
+                if evaluated.len() < num {
+                    evaluated.push(candidate);
+                } else if evaluated.peek().is_some_and(|worst| candidate < *worst) {
+                    evaluated.pop();
+                    evaluated.push(candidate);
+                }
+            }
+        }
+        Ok(evaluated
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(e)| e.into())
+            .collect())
+    }
+}
+
+fn dot<A: Copy + Into<f64>>(query: &[f64], p: &[A]) -> f64 {
+    query.iter().zip(p).map(|(&q, &v)| q * v.into()).sum()
+}
+
+/// Largest dot product of `query` with any point of the box, taking the larger
+/// product of each axis independently
+fn dot_upper_bound<A: Copy + Into<f64>>(query: &[f64], min_bounds: &[A], max_bounds: &[A]) -> f64 {
+    let terms = query.iter().zip(min_bounds.iter().zip(max_bounds));
+    terms.map(|(&q, (&l, &h))| (q * l.into()).max(q * h.into())).sum()
+}
+
+fn norm(values: impl Iterator<Item = f64>) -> f64 {
+    values.map(|v| v * v).sum::<f64>().sqrt()
+}
+
+/// Iterator over the points inside a [`Region`], see [`KdTree::iter_region`]
+pub struct RegionIter<'a, 'r, A: Coordinate, T, U: AsRef<[A]>, R: Region<A>, S = ()> {
+    region: &'r R,
+    // subtrees left to visit, flagged when the region is known to contain them
+    pending: Vec<(Subtree<'a, A, T, U, S>, bool)>,
+    // remaining entries of the current leaf, with its points when they still need to be checked
+    leaf: Option<LeafEntries<'a, A, T, U>>,
+}
+
+type LeafEntries<'a, A, T, U> = (Option<Rows<'a, A, U>>, std::slice::Iter<'a, T>);
+
+type Subtree<'a, A, T, U, S> = &'a KdTree<A, T, U, S>;
+
+/// Distance from every entry of a tree to its `k`-th nearest other entry, computed by
+/// [`KdTree::knn_radii`]
+#[derive(Clone, Debug)]
+pub struct KnnRadii<D> {
+    k: usize,
+    // one per node in pre-order
+    nodes: Vec<NodeRadii<D>>,
+    // one per entry, leaf by leaf in pre-order
+    entries: Vec<D>,
+}
 
-    fn extend(&mut self, point: &[A]) {
-        let min = self.min_bounds.iter_mut();
-        let max = self.max_bounds.iter_mut();
-        for ((l, h), v) in min.zip(max).zip(point.iter()) {
-            if v < l {
-                *l = *v
-            }
-            if v > h {
-                *h = *v
-            }
-        }
+impl<D> KnnRadii<D> {
+    pub fn k(&self) -> usize {
+        self.k
     }
+}
 
-    fn is_leaf(&self) -> bool {
-        self.bucket.is_some()
-            && self.points.is_some()
-            && self.split_value.is_none()
-            && self.split_dimension.is_none()
-            && self.left.is_none()
-            && self.right.is_none()
-    }
+#[derive(Clone, Debug)]
+struct NodeRadii<D> {
+    // largest radius in the subtree
+    max: D,
+    // index of the first node after the subtree
+    end: usize,
+    // index of the subtree's first entry
+    first: usize,
+}
 
-    fn check_point(&self, point: &[A]) -> Result<(), ErrorKind> {
-        if self.dimensions != point.len() {
-            return Err(ErrorKind::WrongDimension);
-        }
-        for n in point {
-            if !n.is_finite() {
-                return Err(ErrorKind::NonFiniteCoordinate);
+impl<'a, A: Coordinate, T, U: AsRef<[A]>, R: Region<A>, S: Summary<T>> Iterator for RegionIter<'a, '_, A, T, U, R, S> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            if let Some((rows, bucket)) = self.leaf.as_mut() {
+                let region = self.region;
+                let next = match rows {
+                    Some(rows) => rows
+                        .zip(bucket.by_ref())
+                        .find(|(p, _)| region.contains(p))
+                        .map(|(_, d)| d),
+                    None => bucket.next(),
+                };
+                if next.is_some() {
+                    return next;
+                }
+                self.leaf = None;
+            }
+            let (curr, contained) = self.pending.pop()?;
+            if curr.size == 0 || !(contained || self.region.intersects_box(&curr.min_bounds, &curr.max_bounds)) {
+                continue;
+            }
+            let inside = contained || self.region.contains_box(&curr.min_bounds, &curr.max_bounds);
+            if !curr.is_leaf() {
+                self.pending.push((curr.left.as_ref().unwrap(), inside));
+                self.pending.push((curr.right.as_ref().unwrap(), inside));
+                continue;
+            }
+            let bucket = curr.bucket.as_ref().unwrap().iter();
+            let mut rows = KdTree::<A, T, U>::rows(curr.points.as_ref().unwrap(), &curr.packed, curr.dimensions);
+            if inside || (curr.coincident && self.region.contains(rows.next().unwrap())) {
+                self.leaf = Some((None, bucket));
+            } else if !curr.coincident {
+                self.leaf = Some((Some(rows), bucket));
             }
         }
-        Ok(())
     }
 }
 
@@ -531,56 +2415,130 @@ impl<A: Float + Zero + One, T, U: AsRef<[A]>> KdTree<A, T, U> {
 // === NEAREST ITERATOR TYPES ===
 // ============================================================================
 
-pub struct NearestIter<'a, A: Float, T, U: AsRef<[A]>, F: Fn(&[A], &[A]) -> A> {
-    inner: NearestWithinRadiusIter<'a, A, T, U, F>,
+/// Heap that pops the element with the smallest distance first
+pub(crate) type ClosestFirst<D, E> = BinaryHeap<Reverse<HeapElement<D, E>>>;
+
+/// Iterator returned by [`KdTree::iter_nearest`]
+///
+/// # Errors
+///
+/// Returns `None` early when the metric yields a NaN or infinite distance, so a `for` loop
+/// over it may see only some of the points. [`NearestIter::error`] tells the two endings apart.
+pub struct NearestIter<
+    'a,
+    A: Coordinate,
+    T,
+    U: AsRef<[A]>,
+    F: Fn(&[A], &[A]) -> D,
+    D: Distance = <A as Coordinate>::Distance,
+    S = (),
+> {
+    inner: NearestWithinRadiusIter<'a, A, T, U, F, D, S>,
+}
+
+impl<'a, A: Coordinate, T, U: AsRef<[A]>, F, D: Distance, S: Summary<T>> NearestIter<'a, A, T, U, F, D, S>
+where
+    F: Fn(&[A], &[A]) -> D,
+{
+    /// The error that ended iteration early, if any
+    pub fn error(&self) -> Option<ErrorKind> {
+        self.inner.error()
+    }
 }
 
-impl<'a, A: Float + Zero + One, T, U: AsRef<[A]>, F> Iterator for NearestIter<'a, A, T, U, F>
+impl<'a, A: Coordinate, T, U: AsRef<[A]>, F, D: Distance, S: Summary<T>> Iterator for NearestIter<'a, A, T, U, F, D, S>
 where
-    F: Fn(&[A], &[A]) -> A,
+    F: Fn(&[A], &[A]) -> D,
 {
-    type Item = (A, &'a T);
-    fn next(&mut self) -> Option<(A, &'a T)> {
+    type Item = (D, &'a T);
+    fn next(&mut self) -> Option<(D, &'a T)> {
         self.inner.next()
     }
 }
 
-pub struct NearestIterMut<'a, A: Float, T, U: AsRef<[A]>, F: Fn(&[A], &[A]) -> A> {
-    inner: NearestWithinRadiusIterMut<'a, A, T, U, F>,
+/// Iterator returned by [`KdTree::iter_nearest_mut`]
+///
+/// # Errors
+///
+/// Returns `None` early when the metric yields a NaN or infinite distance, so a `for` loop
+/// over it may see only some of the points. [`NearestIterMut::error`] tells the two endings apart.
+pub struct NearestIterMut<
+    'a,
+    A: Coordinate,
+    T,
+    U: AsRef<[A]>,
+    F: Fn(&[A], &[A]) -> D,
+    D: Distance = <A as Coordinate>::Distance,
+> {
+    inner: NearestWithinRadiusIterMut<'a, A, T, U, F, D>,
+}
+
+impl<'a, A: Coordinate, T, U: AsRef<[A]>, F, D: Distance> NearestIterMut<'a, A, T, U, F, D>
+where
+    F: Fn(&[A], &[A]) -> D,
+{
+    /// The error that ended iteration early, if any
+    pub fn error(&self) -> Option<ErrorKind> {
+        self.inner.error()
+    }
 }
 
-impl<'a, A: Float + Zero + One, T, U: AsRef<[A]>, F> Iterator for NearestIterMut<'a, A, T, U, F>
+impl<'a, A: Coordinate, T, U: AsRef<[A]>, F, D: Distance> Iterator for NearestIterMut<'a, A, T, U, F, D>
 where
-    F: Fn(&[A], &[A]) -> A,
+    F: Fn(&[A], &[A]) -> D,
 {
-    type Item = (A, &'a mut T);
-    fn next(&mut self) -> Option<(A, &'a mut T)> {
+    type Item = (D, &'a mut T);
+    fn next(&mut self) -> Option<(D, &'a mut T)> {
         self.inner.next()
     }
 }
 
-pub struct NearestWithinRadiusIter<'a, A: Float, T, U: AsRef<[A]>, F: Fn(&[A], &[A]) -> A> {
+/// Iterator returned by [`KdTree::iter_nearest_within_radius`]
+///
+/// # Errors
+///
+/// Returns `None` early when the metric yields a NaN or infinite distance, so a `for` loop
+/// over it may see only some of the points. [`NearestWithinRadiusIter::error`] tells the two endings apart.
+pub struct NearestWithinRadiusIter<
+    'a,
+    A: Coordinate,
+    T,
+    U: AsRef<[A]>,
+    F: Fn(&[A], &[A]) -> D,
+    D: Distance = <A as Coordinate>::Distance,
+    S = (),
+> {
     point: &'a [A],
-    pending: BinaryHeap<HeapElement<A, &'a KdTree<A, T, U>>>,
-    evaluated: BinaryHeap<HeapElement<A, &'a T>>,
+    pending: ClosestFirst<D, &'a KdTree<A, T, U, S>>,
+    evaluated: ClosestFirst<D, &'a T>,
     distance: &'a F,
-    radius: A,
+    radius: D,
+    error: Option<ErrorKind>,
 }
 
-impl<'a, A: Float + Zero + One, T, U: AsRef<[A]>, F> Iterator for NearestWithinRadiusIter<'a, A, T, U, F>
+impl<'a, A: Coordinate, T, U: AsRef<[A]>, F, D: Distance, S: Summary<T>> NearestWithinRadiusIter<'a, A, T, U, F, D, S>
 where
-    F: Fn(&[A], &[A]) -> A,
+    F: Fn(&[A], &[A]) -> D,
 {
-    type Item = (A, &'a T);
-    fn next(&mut self) -> Option<(A, &'a T)> {
+    /// The error that ended iteration early, if any. Iteration stops as soon as the metric
+    /// returns a non-finite distance, which is reported here as [`ErrorKind::NonFiniteDistance`].
+    pub fn error(&self) -> Option<ErrorKind> {
+        self.error
+    }
+
+    fn advance(&mut self) -> Result<(), ErrorKind> {
         let distance = self.distance;
         let point = self.point;
         let radius_limit = self.radius;
-        while !self.pending.is_empty()
-            && (-self.pending.peek().unwrap().distance <= radius_limit)
-            && (self.evaluated.peek().map_or(A::max_value(), |x| -x.distance) >= -self.pending.peek().unwrap().distance)
-        {
-            let mut curr = self.pending.pop().unwrap().element;
+        loop {
+            let Some(next) = self.pending.peek_mut() else {
+                break;
+            };
+            let bound = next.0.distance;
+            if bound > radius_limit || self.evaluated.peek().is_some_and(|x| x.0.distance < bound) {
+                break;
+            }
+            let mut curr = PeekMut::pop(next).0.element;
             while !curr.is_leaf() {
                 let candidate;
                 if curr.belongs_in_left(point) {
@@ -590,55 +2548,183 @@ where
                     candidate = curr.left.as_ref().unwrap();
                     curr = curr.right.as_ref().unwrap();
                 }
-                let candidate_distance =
-                    KdTree::<A, T, U>::distance_to_space(point, &candidate.min_bounds, &candidate.max_bounds, distance);
+                if candidate.size == 0 {
+                    continue;
+                }
+                let candidate_distance = finite(KdTree::<A, T, U>::distance_to_space(
+                    point,
+                    &candidate.min_bounds,
+                    &candidate.max_bounds,
+                    distance,
+                ))?;
                 if candidate_distance <= radius_limit {
-                    self.pending.push(HeapElement {
-                        distance: -candidate_distance,
+                    self.pending.push(Reverse(HeapElement {
+                        distance: candidate_distance,
                         element: &**candidate,
-                    });
+                    }));
                 }
             }
-            let points = curr.points.as_ref().unwrap().iter();
+            let rows = KdTree::<A, T, U>::rows(curr.points.as_ref().unwrap(), &curr.packed, curr.dimensions);
             let bucket = curr.bucket.as_ref().unwrap().iter();
-            self.evaluated.extend(points.zip(bucket).filter_map(|(p, d)| {
-                let dist = distance(point, p.as_ref());
-                if dist <= radius_limit {
-                    Some(HeapElement {
-                        distance: -dist,
-                        element: d,
-                    })
-                } else {
-                    None
+            for e in KdTree::<A, T, U>::leaf_distances(rows, curr.coincident, bucket, point, distance) {
+                if finite(e.distance)? <= radius_limit {
+                    self.evaluated.push(Reverse(e));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a, A: Coordinate, T, U: AsRef<[A]>, F, D: Distance, S: Summary<T>> Iterator
+    for NearestWithinRadiusIter<'a, A, T, U, F, D, S>
+where
+    F: Fn(&[A], &[A]) -> D,
+{
+    type Item = (D, &'a T);
+    fn next(&mut self) -> Option<(D, &'a T)> {
+        if let Err(error) = self.advance() {
+            self.pending.clear();
+            self.evaluated.clear();
+            self.error = Some(error);
+        }
+        self.evaluated.pop().map(|Reverse(x)| x.into())
+    }
+}
+
+/// Iterator returned by [`KdTree::iter_farthest`]
+///
+/// # Errors
+///
+/// Returns `None` early when the metric yields a NaN or infinite distance, so a `for` loop
+/// over it may see only some of the points. [`FarthestIter::error`] tells the two endings apart.
+pub struct FarthestIter<
+    'a,
+    A: Coordinate,
+    T,
+    U: AsRef<[A]>,
+    F: Fn(&[A], &[A]) -> D,
+    D: Distance = <A as Coordinate>::Distance,
+    S = (),
+> {
+    point: &'a [A],
+    pending: BinaryHeap<HeapElement<D, Subtree<'a, A, T, U, S>>>,
+    evaluated: BinaryHeap<HeapElement<D, &'a T>>,
+    distance: &'a F,
+    error: Option<ErrorKind>,
+}
+
+impl<'a, A: Coordinate, T, U: AsRef<[A]>, F, D: Distance, S: Summary<T>> FarthestIter<'a, A, T, U, F, D, S>
+where
+    F: Fn(&[A], &[A]) -> D,
+{
+    /// The error that ended iteration early, if any. Iteration stops as soon as the metric
+    /// returns a non-finite distance, which is reported here as [`ErrorKind::NonFiniteDistance`].
+    pub fn error(&self) -> Option<ErrorKind> {
+        self.error
+    }
+
+    fn advance(&mut self) -> Result<(), ErrorKind> {
+        let distance = self.distance;
+        let point = self.point;
+        loop {
+            let Some(next) = self.pending.peek_mut() else {
+                break;
+            };
+            let bound = next.distance;
+            if self.evaluated.peek().is_some_and(|x| x.distance > bound) {
+                break;
+            }
+            let curr = PeekMut::pop(next).element;
+            if !curr.is_leaf() {
+                for child in [curr.left.as_ref().unwrap(), curr.right.as_ref().unwrap()] {
+                    if child.size == 0 {
+                        continue;
+                    }
+                    let child_distance = finite(KdTree::<A, T, U>::distance_to_farthest_corner(
+                        point,
+                        &child.min_bounds,
+                        &child.max_bounds,
+                        distance,
+                    ))?;
+                    self.pending.push(HeapElement {
+                        distance: child_distance,
+                        element: &**child,
+                    });
                 }
-            }));
+                continue;
+            }
+            let rows = KdTree::<A, T, U>::rows(curr.points.as_ref().unwrap(), &curr.packed, curr.dimensions);
+            let bucket = curr.bucket.as_ref().unwrap().iter();
+            for e in KdTree::<A, T, U>::leaf_distances(rows, curr.coincident, bucket, point, distance) {
+                finite(e.distance)?;
+                self.evaluated.push(e);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a, A: Coordinate, T, U: AsRef<[A]>, F, D: Distance, S: Summary<T>> Iterator for FarthestIter<'a, A, T, U, F, D, S>
+where
+    F: Fn(&[A], &[A]) -> D,
+{
+    type Item = (D, &'a T);
+    fn next(&mut self) -> Option<(D, &'a T)> {
+        if let Err(error) = self.advance() {
+            self.pending.clear();
+            self.evaluated.clear();
+            self.error = Some(error);
         }
-        self.evaluated.pop().map(|x| (-x.distance, x.element))
+        self.evaluated.pop().map(Into::into)
     }
 }
 
-pub struct NearestWithinRadiusIterMut<'a, A: Float, T, U: AsRef<[A]>, F: Fn(&[A], &[A]) -> A> {
+/// Iterator returned by [`KdTree::iter_nearest_within_radius_mut`]
+///
+/// # Errors
+///
+/// Returns `None` early when the metric yields a NaN or infinite distance, so a `for` loop
+/// over it may see only some of the points. [`NearestWithinRadiusIterMut::error`] tells the two endings apart.
+pub struct NearestWithinRadiusIterMut<
+    'a,
+    A: Coordinate,
+    T,
+    U: AsRef<[A]>,
+    F: Fn(&[A], &[A]) -> D,
+    D: Distance = <A as Coordinate>::Distance,
+> {
     point: &'a [A],
-    pending: BinaryHeap<HeapElement<A, &'a mut KdTree<A, T, U>>>,
-    evaluated: BinaryHeap<HeapElement<A, &'a mut T>>,
+    pending: ClosestFirst<D, &'a mut KdTree<A, T, U>>,
+    evaluated: ClosestFirst<D, &'a mut T>,
     distance: &'a F,
-    radius: A,
+    radius: D,
+    error: Option<ErrorKind>,
 }
 
-impl<'a, A: Float + Zero + One, T, U: AsRef<[A]>, F> Iterator for NearestWithinRadiusIterMut<'a, A, T, U, F>
+impl<'a, A: Coordinate, T, U: AsRef<[A]>, F, D: Distance> NearestWithinRadiusIterMut<'a, A, T, U, F, D>
 where
-    F: Fn(&[A], &[A]) -> A,
+    F: Fn(&[A], &[A]) -> D,
 {
-    type Item = (A, &'a mut T);
-    fn next(&mut self) -> Option<(A, &'a mut T)> {
+    /// The error that ended iteration early, if any. Iteration stops as soon as the metric
+    /// returns a non-finite distance, which is reported here as [`ErrorKind::NonFiniteDistance`].
+    pub fn error(&self) -> Option<ErrorKind> {
+        self.error
+    }
+
+    fn advance(&mut self) -> Result<(), ErrorKind> {
         let distance = self.distance;
         let point = self.point;
         let radius_limit = self.radius;
-        while !self.pending.is_empty()
-            && (-self.pending.peek().unwrap().distance <= radius_limit)
-            && (self.evaluated.peek().map_or(A::max_value(), |x| -x.distance) >= -self.pending.peek().unwrap().distance)
-        {
-            let mut curr = &mut *self.pending.pop().unwrap().element;
+        loop {
+            let Some(next) = self.pending.peek_mut() else {
+                break;
+            };
+            let bound = next.0.distance;
+            if bound > radius_limit || self.evaluated.peek().is_some_and(|x| x.0.distance < bound) {
+                break;
+            }
+            let mut curr = PeekMut::pop(next).0.element;
             while !curr.is_leaf() {
                 let candidate;
                 if curr.belongs_in_left(point) {
@@ -648,30 +2734,348 @@ where
                     candidate = curr.left.as_mut().unwrap();
                     curr = curr.right.as_mut().unwrap();
                 }
-                let candidate_distance =
-                    KdTree::<A, T, U>::distance_to_space(point, &candidate.min_bounds, &candidate.max_bounds, distance);
+                if candidate.size == 0 {
+                    continue;
+                }
+                let candidate_distance = finite(KdTree::<A, T, U>::distance_to_space(
+                    point,
+                    &candidate.min_bounds,
+                    &candidate.max_bounds,
+                    distance,
+                ))?;
                 if candidate_distance <= radius_limit {
-                    self.pending.push(HeapElement {
-                        distance: -candidate_distance,
+                    self.pending.push(Reverse(HeapElement {
+                        distance: candidate_distance,
                         element: &mut **candidate,
-                    });
+                    }));
                 }
             }
-            let points = curr.points.as_ref().unwrap().iter();
+            let rows = KdTree::<A, T, U>::rows(curr.points.as_ref().unwrap(), &curr.packed, curr.dimensions);
             let bucket = curr.bucket.as_mut().unwrap().iter_mut();
-            self.evaluated.extend(points.zip(bucket).filter_map(|(p, d)| {
-                let dist = distance(point, p.as_ref());
-                if dist <= radius_limit {
-                    Some(HeapElement {
-                        distance: -dist,
-                        element: d,
-                    })
-                } else {
-                    None
+            for e in KdTree::<A, T, U>::leaf_distances(rows, curr.coincident, bucket, point, distance) {
+                if finite(e.distance)? <= radius_limit {
+                    self.evaluated.push(Reverse(e));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a, A: Coordinate, T, U: AsRef<[A]>, F, D: Distance> Iterator for NearestWithinRadiusIterMut<'a, A, T, U, F, D>
+where
+    F: Fn(&[A], &[A]) -> D,
+{
+    type Item = (D, &'a mut T);
+    fn next(&mut self) -> Option<(D, &'a mut T)> {
+        if let Err(error) = self.advance() {
+            self.pending.clear();
+            self.evaluated.clear();
+            self.error = Some(error);
+        }
+        self.evaluated.pop().map(|Reverse(x)| x.into())
+    }
+}
+
+/// Deepest stem nesting accepted when loading a tree of `size` entries. Splits halve a leaf's
+/// extent, so real trees stay within a small multiple of the `log2` of their leaf
+/// count, while deeper chains would overflow the stack of the recursive tree methods.
+pub(crate) fn max_depth(size: usize, capacity: usize) -> usize {
+    let leaves = size.div_ceil(capacity.max(1)).max(1);
+    8 * (usize::BITS - leaves.leading_zeros()) as usize + 64
+}
+
+pub(crate) fn check_point<A: Coordinate>(dimensions: usize, point: &[A]) -> Result<(), PointError> {
+    if dimensions != point.len() {
+        return Err(PointError::WrongDimension {
+            expected: dimensions,
+            actual: point.len(),
+        });
+    }
+    for (axis, n) in point.iter().enumerate() {
+        if let Some(value) = n.non_finite() {
+            return Err(PointError::NonFiniteCoordinate { axis, value });
+        }
+    }
+    Ok(())
+}
+
+// ============================================================================
+// === DESERIALIZATION ===
+// ============================================================================
+
+/// Unchecked mirror of `KdTree` that serde fills in before the structure is validated
+#[cfg(feature = "serialize")]
+#[derive(Deserialize)]
+#[serde(
+    bound = "A: Coordinate + serde::Deserialize<'de>, T: serde::Deserialize<'de>, U: serde::Deserialize<'de>, \
+                 S: Summary<T>"
+)]
+struct RawKdTree<A, T, U: AsRef<[A]>, S> {
+    left: Option<Box<KdTree<A, T, U, S>>>,
+    right: Option<Box<KdTree<A, T, U, S>>>,
+    dimensions: usize,
+    capacity: usize,
+    size: usize,
+    min_bounds: Box<[A]>,
+    max_bounds: Box<[A]>,
+    split_value: Option<A>,
+    split_dimension: Option<usize>,
+    points: Option<Vec<U>>,
+    bucket: Option<Vec<T>>,
+    #[serde(default)]
+    coincident: bool,
+    #[serde(default)]
+    pack_leaves: bool,
+}
+
+#[cfg(feature = "serialize")]
+impl<A: Coordinate, T, U: AsRef<[A]>, S: Summary<T>> TryFrom<RawKdTree<A, T, U, S>> for KdTree<A, T, U, S> {
+    type Error = String;
+
+    fn try_from(raw: RawKdTree<A, T, U, S>) -> Result<Self, Self::Error> {
+        let mut tree = KdTree {
+            left: raw.left,
+            right: raw.right,
+            dimensions: raw.dimensions,
+            capacity: raw.capacity,
+            size: raw.size,
+            min_bounds: raw.min_bounds,
+            max_bounds: raw.max_bounds,
+            summary: S::empty(),
+            split_value: raw.split_value,
+            split_dimension: raw.split_dimension,
+            points: raw.points,
+            bucket: raw.bucket,
+            coincident: raw.coincident,
+            pack_leaves: raw.pack_leaves,
+            packed: vec![],
+            columns: Columns::default(),
+        };
+        tree.migrate_inclusive_split();
+        tree.validate_node()?;
+        tree.repack();
+        tree.resummarize();
+        Ok(tree)
+    }
+}
+
+#[cfg(feature = "serialize")]
+impl<A: Coordinate, T, U: AsRef<[A]>, S: Summary<T>> KdTree<A, T, U, S> {
+    /// Trees written by 0.8 sent values equal to a split that had rounded onto the stem's
+    /// lower bound to the left child, where `belongs_in_left` no longer looks for them. Such a
+    /// split moves up to the right child's minimum, or the stem is replaced by its left child
+    /// when the right one is empty.
+    fn migrate_inclusive_split(&mut self) {
+        let (Some(left), Some(right), Some(split_value), Some(dim)) =
+            (&self.left, &self.right, self.split_value, self.split_dimension)
+        else {
+            return;
+        };
+        let inclusive = self.min_bounds.get(dim) == Some(&split_value)
+            && left.size > 0
+            && left.max_bounds.get(dim) == Some(&split_value);
+        if !inclusive {
+            return;
+        }
+        if right.size > 0 {
+            self.split_value = right.min_bounds.get(dim).copied();
+        } else {
+            *self = *self.left.take().unwrap();
+        }
+    }
+
+    /// Checks the invariants queries rely on for a single node. Children are validated
+    /// while they are deserialized, so this only relates a node to its direct children.
+    fn validate_node(&self) -> Result<(), String> {
+        let dims = self.dimensions;
+        if self.min_bounds.len() != dims || self.max_bounds.len() != dims {
+            return Err(format!(
+                "bounds have {} and {} dimensions, expected {}",
+                self.min_bounds.len(),
+                self.max_bounds.len(),
+                dims
+            ));
+        }
+        match (&self.left, &self.right, &self.points, &self.bucket) {
+            (None, None, Some(points), Some(bucket))
+                if self.split_value.is_none() && self.split_dimension.is_none() =>
+            {
+                if self.coincident && bucket.len() < 2 {
+                    return Err(String::from("coincident leaf holds fewer than two entries"));
+                }
+                if !self.coincident && bucket.len() > self.capacity {
+                    return Err(format!(
+                        "leaf holds {} entries, more than its capacity {}",
+                        bucket.len(),
+                        self.capacity
+                    ));
+                }
+                let rows = if self.coincident { 1 } else { bucket.len() };
+                if points.len() != rows {
+                    return Err(format!("leaf has {} points for {} entries", points.len(), bucket.len()));
+                }
+                if self.size != bucket.len() {
+                    return Err(format!(
+                        "size {} does not match bucket length {}",
+                        self.size,
+                        bucket.len()
+                    ));
+                }
+                for p in points.iter().map(AsRef::as_ref) {
+                    if p.len() != dims {
+                        return Err(format!("point has {} dimensions, expected {}", p.len(), dims));
+                    }
+                    if p.iter().any(|v| v.non_finite().is_some()) {
+                        return Err(String::from("point has a non-finite coordinate"));
+                    }
+                    if !Self::in_bounding_box(p, &self.min_bounds, &self.max_bounds) {
+                        return Err(String::from("point lies outside of its leaf bounds"));
+                    }
+                }
+            }
+            (Some(left), Some(right), None, None) => {
+                let (Some(split_value), Some(split_dimension)) = (self.split_value, self.split_dimension) else {
+                    return Err(String::from("stem is missing its split"));
+                };
+                if self.coincident {
+                    return Err(String::from("stem is marked as coincident"));
+                }
+                if split_dimension >= dims {
+                    return Err(format!(
+                        "split dimension {} exceeds {} dimensions",
+                        split_dimension, dims
+                    ));
+                }
+                if split_value.non_finite().is_some() {
+                    return Err(String::from("split value is not finite"));
+                }
+                for child in [left, right] {
+                    if child.dimensions != dims || child.capacity != self.capacity {
+                        return Err(String::from(
+                            "children disagree with their parent on dimensions or capacity",
+                        ));
+                    }
+                }
+                if left.size.checked_add(right.size) != Some(self.size) {
+                    return Err(format!(
+                        "size {} does not match children sizes {} + {}",
+                        self.size, left.size, right.size
+                    ));
+                }
+                if (left.size > 0 && left.max_bounds[split_dimension] >= split_value)
+                    || (right.size > 0 && right.min_bounds[split_dimension] < split_value)
+                {
+                    return Err(String::from("children are not partitioned by the split value"));
+                }
+                for child in [left, right].into_iter().filter(|child| child.size > 0) {
+                    if !Self::in_bounding_box(&child.min_bounds, &self.min_bounds, &self.max_bounds)
+                        || !Self::in_bounding_box(&child.max_bounds, &self.min_bounds, &self.max_bounds)
+                    {
+                        return Err(String::from("child bounds exceed their parent"));
+                    }
                 }
-            }));
+            }
+            _ => return Err(String::from("node is neither a complete leaf nor a complete stem")),
+        }
+        Ok(())
+    }
+}
+
+/// Rejects NaN and infinite distances returned by a user supplied metric, which would
+/// otherwise silently corrupt heap ordering
+pub(crate) fn finite<D: Distance>(distance: D) -> Result<D, ErrorKind> {
+    if distance.is_finite() {
+        Ok(distance)
+    } else {
+        Err(ErrorKind::NonFiniteDistance)
+    }
+}
+
+enum Relocation<T> {
+    Missing,
+    Moved,
+    Detached(T),
+}
+
+// ============================================================================
+// === ENTRY TYPES ===
+// ============================================================================
+
+pub enum Entry<'a, A: Coordinate, T, U: AsRef<[A]>> {
+    Occupied(OccupiedEntry<'a, T>),
+    Vacant(VacantEntry<'a, A, T, U>),
+}
+
+impl<'a, A: Coordinate, T, U: AsRef<[A]>> Entry<'a, A, T, U> {
+    pub fn or_insert(self, default: T) -> &'a mut T {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> T>(self, default: F) -> &'a mut T {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
         }
-        self.evaluated.pop().map(|x| (-x.distance, x.element))
+    }
+
+    pub fn or_default(self) -> &'a mut T
+    where
+        T: Default,
+    {
+        self.or_insert_with(T::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut T)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+pub struct OccupiedEntry<'a, T> {
+    data: &'a mut T,
+}
+
+impl<'a, T> OccupiedEntry<'a, T> {
+    pub fn get(&self) -> &T {
+        self.data
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data
+    }
+
+    pub fn into_mut(self) -> &'a mut T {
+        self.data
+    }
+
+    /// Replaces the stored data, returning the previous value
+    pub fn insert(&mut self, data: T) -> T {
+        std::mem::replace(self.data, data)
+    }
+}
+
+pub struct VacantEntry<'a, A: Coordinate, T, U: AsRef<[A]>> {
+    tree: &'a mut KdTree<A, T, U>,
+    point: U,
+}
+
+impl<'a, A: Coordinate, T, U: AsRef<[A]>> VacantEntry<'a, A, T, U> {
+    pub fn point(&self) -> &U {
+        &self.point
+    }
+
+    pub fn into_point(self) -> U {
+        self.point
+    }
+
+    pub fn insert(self, data: T) -> &'a mut T {
+        self.tree.add_unchecked(self.point, data)
     }
 }
 
@@ -702,6 +3106,130 @@ mod tests {
         assert!(tree.left.is_some() && tree.right.is_some());
     }
 
+    #[test]
+    fn it_stops_splitting_identical_points() {
+        let mut tree: KdTree<f64, i32, [f64; 2]> = KdTree::with_capacity(2, 2);
+        for i in 0..10 {
+            tree.add([1.0, 1.0], i).unwrap();
+        }
+        assert!(tree.is_leaf() && tree.coincident);
+        assert_eq!(tree.points.as_ref().unwrap().len(), 1);
+        assert_eq!(tree.bucket.as_ref().unwrap().len(), 10);
+
+        tree.add([2.0, 1.0], 10).unwrap();
+        assert!(!tree.is_leaf() && !tree.coincident);
+        assert!(tree.left.as_ref().unwrap().coincident);
+        assert_eq!(tree.left.as_ref().unwrap().size(), 10);
+        assert_eq!(tree.right.as_ref().unwrap().size(), 1);
+    }
+
+    #[test]
+    fn it_tightens_bounds_when_updating_positions() {
+        let mut tree: KdTree<f64, i32, [f64; 2]> = KdTree::with_capacity(2, 2);
+        for i in 0..8 {
+            tree.add([i as f64, 0.0], i).unwrap();
+        }
+        assert!(tree.update_position(&[7.0, 0.0], [3.5, 1.0], |&d| d == 7).unwrap());
+        assert_eq!(&*tree.min_bounds, &[0.0, 0.0]);
+        assert_eq!(&*tree.max_bounds, &[6.0, 1.0]);
+
+        assert!(tree.update_position(&[0.0, 0.0], [0.5, 0.0], |&d| d == 0).unwrap());
+        assert_eq!(&*tree.min_bounds, &[0.5, 0.0]);
+        assert_eq!(tree.size(), 8);
+    }
+
+    fn assert_packed_matches_points(tree: &KdTree<f64, i32, Vec<f64>>) {
+        match tree.points.as_ref() {
+            Some(points) => {
+                assert_eq!(tree.packed, points.concat());
+                assert_eq!(tree.columns.len(), points.len());
+                for axis in (0..tree.dimensions).filter(|_| !points.is_empty()) {
+                    let column = points.iter().map(|p| p[axis]).collect::<Vec<_>>();
+                    assert_eq!(tree.columns.column(axis), &column[..]);
+                }
+            }
+            None => {
+                assert!(tree.packed.is_empty() && tree.columns.is_empty());
+                assert_packed_matches_points(tree.left.as_ref().unwrap());
+                assert_packed_matches_points(tree.right.as_ref().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn it_keeps_packed_leaves_in_sync() {
+        let mut tree: KdTree<f64, i32, Vec<f64>> = KdTree::with_packed_leaves(2, 2);
+        for i in 0..12 {
+            tree.add(vec![(i % 5) as f64, 0.0], i).unwrap();
+        }
+        assert_packed_matches_points(&tree);
+        assert!(tree.update_position(&[4.0, 0.0], vec![1.5, 2.0], |&d| d == 4).unwrap());
+        assert!(tree.update_position(&[1.0, 0.0], vec![1.0, 0.5], |&d| d == 1).unwrap());
+        assert_eq!(tree.remove(&vec![0.0, 0.0], &5).unwrap(), 1);
+        assert_packed_matches_points(&tree);
+
+        let mut unpacked: KdTree<f64, i32, Vec<f64>> = KdTree::with_capacity(2, 2);
+        unpacked.add(vec![0.0, 0.0], 0).unwrap();
+        assert!(unpacked.packed.is_empty());
+        unpacked.pack_leaves();
+        assert_packed_matches_points(&unpacked);
+    }
+
+    /// Sum of the payloads below a node, with their count
+    #[derive(Clone, Debug, PartialEq)]
+    struct Total(i32, usize);
+
+    impl crate::summary::Summary<i32> for Total {
+        fn empty() -> Self {
+            Total(0, 0)
+        }
+
+        fn add(&mut self, data: &i32) {
+            self.0 += data;
+            self.1 += 1;
+        }
+
+        fn merge(&mut self, other: &Self) {
+            self.0 += other.0;
+            self.1 += other.1;
+        }
+    }
+
+    fn assert_summaries_match_payloads(tree: &KdTree<f64, i32, [f64; 2], Total>) -> Total {
+        let expected = match tree.bucket.as_ref() {
+            Some(bucket) => Total(bucket.iter().sum(), bucket.len()),
+            None => {
+                let left = assert_summaries_match_payloads(tree.left.as_ref().unwrap());
+                let right = assert_summaries_match_payloads(tree.right.as_ref().unwrap());
+                Total(left.0 + right.0, left.1 + right.1)
+            }
+        };
+        assert_eq!(tree.summary, expected);
+        assert_eq!(tree.summary.1, tree.size);
+        expected
+    }
+
+    #[test]
+    fn it_keeps_summaries_in_sync() {
+        let mut tree: KdTree<f64, i32, [f64; 2], Total> = KdTree::with_summary(2, 2);
+        for i in 0..12 {
+            tree.add([(i % 5) as f64, 0.0], i).unwrap();
+        }
+        // identical points end up in a coincident leaf
+        for i in 20..25 {
+            tree.add([7.0, 7.0], i).unwrap();
+        }
+        assert_summaries_match_payloads(&tree);
+        assert_eq!(tree.summary(), &Total(66 + 110, 17));
+
+        assert!(tree.update_position(&[4.0, 0.0], [1.5, 2.0], |&d| d == 4).unwrap());
+        assert!(tree.update_position(&[7.0, 7.0], [9.0, 9.0], |&d| d == 22).unwrap());
+        assert_eq!(tree.remove(&[0.0, 0.0], &5).unwrap(), 1);
+        assert_eq!(tree.insert_or_replace([1.5, 2.0], 40).unwrap(), Some(4));
+        assert_summaries_match_payloads(&tree);
+        assert_eq!(tree.summary(), &Total(66 + 110 - 5 + 36, 16));
+    }
+
     #[test]
     fn test_normal_distance_to_space() {
         use crate::distance::squared_euclidean;
@@ -773,4 +3301,42 @@ mod tests {
         let result = tree.bounding_box(&[2.5, 2.5], &[3.0, 3.0]).unwrap();
         assert_eq!(result.len(), 0);
     }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn archived_queries_reject_inconsistent_nodes() {
+        use super::{ArchivedKdTree, ErrorKind};
+        use crate::distance::squared_euclidean;
+
+        fn query(tree: &KdTree<f64, i32, [f64; 2]>) -> Result<usize, ErrorKind> {
+            let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(tree).unwrap();
+            let archived = rkyv::access::<ArchivedKdTree<f64, i32, [f64; 2]>, rkyv::rancor::Error>(&bytes).unwrap();
+            archived.nearest(&[0.0, 0.0], 4, &squared_euclidean)?;
+            archived
+                .bounding_box(&[-9.0, -9.0], &[9.0, 9.0])
+                .map(|found| found.len())
+        }
+
+        let mut tree: KdTree<f64, i32, [f64; 2]> = KdTree::with_capacity(2, 1);
+        for (i, point) in [[0.0, 0.0], [1.0, 1.0], [2.0, 2.0]].into_iter().enumerate() {
+            tree.add(point, i as i32).unwrap();
+        }
+        assert_eq!(query(&tree), Ok(3));
+
+        let mut corrupted = tree.clone();
+        corrupted.split_dimension = Some(7);
+        assert!(matches!(query(&corrupted), Err(ErrorKind::Corrupted(_))));
+
+        let mut corrupted = tree.clone();
+        corrupted.split_value = None;
+        assert!(matches!(query(&corrupted), Err(ErrorKind::Corrupted(_))));
+
+        let mut corrupted = tree.clone();
+        corrupted.left.as_mut().unwrap().points.as_mut().unwrap().clear();
+        assert!(matches!(query(&corrupted), Err(ErrorKind::Corrupted(_))));
+
+        let mut corrupted = tree;
+        corrupted.left.as_mut().unwrap().bucket = None;
+        assert!(matches!(query(&corrupted), Err(ErrorKind::Corrupted(_))));
+    }
 }
@@ -0,0 +1,280 @@
+//! Distance kernels that evaluate a whole block of points at once.
+//!
+//! Queries on a [`KdTree`](crate::KdTree) call the metric once per point, which
+//! keeps the metric flexible but leaves nothing for the compiler to vectorise.
+//! The kernels here instead work on [`Columns`], a structure-of-arrays block that
+//! stores each dimension contiguously, and accumulate the distances of every point
+//! one dimension at a time. With the `simd` feature the inner loops process eight
+//! points per instruction using [`wide`](https://docs.rs/wide), without it they
+//! fall back to plain loops that produce the same results.
+//!
+//! Columns store `f32` coordinates, or `half::f16` with the `f16` feature to halve
+//! memory again; distances are always computed in `f32`.
+//!
+//! Trees created with [`KdTree::with_packed_leaves`](crate::KdTree::with_packed_leaves)
+//! keep the points of every leaf in columns too. The `f32` trees' kernel queries, like
+//! [`KdTree::nearest_kernel`](crate::KdTree::nearest_kernel), evaluate each of those
+//! leaves with one kernel call, with the same results as
+//! [`squared_euclidean`](crate::distance::squared_euclidean) and
+//! [`manhattan`](crate::distance::manhattan) give:
+//!
+//! ```
+//! use kdtree::KdTree;
+//! use kdtree::distance::squared_euclidean;
+//! use kdtree::kernels::Kernel;
+//!
+//! let mut tree: KdTree<f32, usize, [f32; 2]> = KdTree::with_packed_leaves(2, 16);
+//! for i in 0..100 {
+//!     tree.add([(i % 10) as f32, (i / 10) as f32], i).unwrap();
+//! }
+//! assert_eq!(
+//!     tree.nearest_kernel(&[2.2, 3.9], 3, Kernel::SquaredEuclidean).unwrap(),
+//!     tree.nearest(&[2.2, 3.9], 3, &squared_euclidean).unwrap()
+//! );
+//! ```
+//!
+//! ```
+//! use kdtree::kernels::{Columns, Kernel};
+//!
+//! let mut block: Columns = Columns::new(2);
+//! block.push(&[0.0, 0.0]).unwrap();
+//! block.push(&[3.0, 4.0]).unwrap();
+//!
+//! let mut distances = Vec::new();
+//! Kernel::SquaredEuclidean.evaluate(&block, &[3.0, 0.0], &mut distances).unwrap();
+//! assert_eq!(distances, vec![9.0, 16.0]);
+//! ```
+
+use crate::coordinate::Coordinate;
+use crate::distance::Metric;
+use crate::kdtree::{ErrorKind, check_point};
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Storage type of a [`Columns`] block
+pub trait Lane: Copy + sealed::Sealed {
+    fn from_f32(value: f32) -> Self;
+
+    fn to_f32(self) -> f32;
+
+    /// Converts a chunk of values at once
+    #[inline(always)]
+    fn widen(values: &[Self; 8]) -> [f32; 8] {
+        values.map(Self::to_f32)
+    }
+}
+
+impl sealed::Sealed for f32 {}
+
+impl Lane for f32 {
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+
+    fn to_f32(self) -> f32 {
+        self
+    }
+}
+
+#[cfg(feature = "f16")]
+impl sealed::Sealed for half::f16 {}
+
+#[cfg(feature = "f16")]
+impl Lane for half::f16 {
+    fn from_f32(value: f32) -> Self {
+        half::f16::from_f32(value)
+    }
+
+    fn to_f32(self) -> f32 {
+        half::f16::to_f32(self)
+    }
+
+    #[inline(always)]
+    fn widen(values: &[Self; 8]) -> [f32; 8] {
+        // converts in bulk with hardware instructions where available
+        let mut out = [0.0; 8];
+        half::slice::HalfFloatSliceExt::convert_to_f32_slice(&values[..], &mut out);
+        out
+    }
+}
+
+/// Points of a fixed dimension stored as one contiguous column per dimension. Kernels
+/// evaluate blocks of a [`Lane`] type; the leaves of a tree keep their coordinates in
+/// a block of the tree's coordinate type.
+#[derive(Clone, Debug)]
+pub struct Columns<S = f32> {
+    len: usize,
+    columns: Box<[Vec<S>]>,
+}
+
+impl<S> Default for Columns<S> {
+    fn default() -> Self {
+        Columns {
+            len: 0,
+            columns: Box::new([]),
+        }
+    }
+}
+
+impl<S: Lane> Columns<S> {
+    /// Appends a point, rejecting it if a coordinate does not fit the storage type
+    pub fn push(&mut self, point: &[f32]) -> Result<(), ErrorKind> {
+        check_point(self.dimensions(), point)?;
+        let stored = point.iter().map(|&v| S::from_f32(v));
+        if stored.clone().any(|v| v.to_f32().non_finite().is_some()) {
+            return Err(ErrorKind::NonFiniteCoordinate);
+        }
+        self.push_unchecked(stored);
+        Ok(())
+    }
+
+    /// Coordinates of the point at `index` as they are stored
+    pub fn point(&self, index: usize) -> Vec<f32> {
+        self.columns.iter().map(|column| column[index].to_f32()).collect()
+    }
+}
+
+impl<S: Copy> Columns<S> {
+    pub fn new(dimensions: usize) -> Self {
+        Columns::with_capacity(dimensions, 0)
+    }
+
+    pub fn with_capacity(dimensions: usize, capacity: usize) -> Self {
+        Columns {
+            len: 0,
+            columns: (0..dimensions).map(|_| Vec::with_capacity(capacity)).collect(),
+        }
+    }
+
+    /// Appends a point whose coordinates have already been checked
+    pub(crate) fn push_unchecked(&mut self, point: impl IntoIterator<Item = S>) {
+        for (column, v) in self.columns.iter_mut().zip(point) {
+            column.push(v);
+        }
+        self.len += 1;
+    }
+
+    pub fn column(&self, axis: usize) -> &[S] {
+        &self.columns[axis]
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+        for column in self.columns.iter_mut() {
+            column.clear();
+        }
+    }
+}
+
+/// Metrics with a block kernel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kernel {
+    SquaredEuclidean,
+    Manhattan,
+    /// Dot product, larger values mean more similar points
+    Dot,
+}
+
+impl Kernel {
+    /// Writes the value of the metric between `query` and every point of `block`
+    /// to `out`, in the order the points were pushed
+    pub fn evaluate<S: Lane>(self, block: &Columns<S>, query: &[f32], out: &mut Vec<f32>) -> Result<(), ErrorKind> {
+        check_point(block.dimensions(), query)?;
+        out.clear();
+        out.resize(block.len(), 0.0);
+        let mut run = |kernel: Kernel| {
+            for (column, &q) in block.columns.iter().zip(query) {
+                kernel.accumulate(column, q, out);
+            }
+        };
+        // a constant kernel per arm keeps the match out of the inner loop
+        match self {
+            Kernel::SquaredEuclidean => run(Kernel::SquaredEuclidean),
+            Kernel::Manhattan => run(Kernel::Manhattan),
+            Kernel::Dot => run(Kernel::Dot),
+        }
+        Ok(())
+    }
+
+    /// Rejects the dot product, which can't rank points by distance
+    pub(crate) fn check_distance(self) -> Result<(), ErrorKind> {
+        match self {
+            Kernel::SquaredEuclidean | Kernel::Manhattan => Ok(()),
+            Kernel::Dot => Err(ErrorKind::NotADistance),
+        }
+    }
+
+    /// Value of the metric for a single pair of points, as the kernel computes it
+    pub fn scalar(self, a: &[f32], b: &[f32]) -> f32 {
+        debug_assert_eq!(a.len(), b.len());
+        a.iter().zip(b).map(|(&x, &y)| self.term(x, y)).sum()
+    }
+
+    fn term(self, v: f32, q: f32) -> f32 {
+        match self {
+            Kernel::SquaredEuclidean => (v - q) * (v - q),
+            Kernel::Manhattan => (v - q).abs(),
+            Kernel::Dot => v * q,
+        }
+    }
+
+    /// Adds the term of every value in `column` against `q` to the matching entry of `out`
+    #[inline(always)]
+    fn accumulate<S: Lane>(self, column: &[S], q: f32, out: &mut [f32]) {
+        let mut values = column.chunks_exact(8);
+        let mut sums = out.chunks_exact_mut(8);
+        for (chunk, sum) in (&mut values).zip(&mut sums) {
+            let chunk = S::widen(chunk.try_into().unwrap());
+            self.accumulate_chunk(&chunk, q, sum.try_into().unwrap());
+        }
+        for (v, sum) in values.remainder().iter().zip(sums.into_remainder()) {
+            *sum += self.term(v.to_f32(), q);
+        }
+    }
+
+    #[cfg(feature = "simd")]
+    #[inline(always)]
+    fn accumulate_chunk(self, values: &[f32; 8], q: f32, sum: &mut [f32; 8]) {
+        use wide::f32x8;
+
+        let (v, q) = (f32x8::from(*values), f32x8::splat(q));
+        let term = match self {
+            Kernel::SquaredEuclidean => (v - q) * (v - q),
+            Kernel::Manhattan => (v - q).abs(),
+            Kernel::Dot => v * q,
+        };
+        *sum = (f32x8::from(*sum) + term).to_array();
+    }
+
+    #[cfg(not(feature = "simd"))]
+    #[inline(always)]
+    fn accumulate_chunk(self, values: &[f32; 8], q: f32, sum: &mut [f32; 8]) {
+        for (s, &v) in sum.iter_mut().zip(values) {
+            *s += self.term(v, q);
+        }
+    }
+}
+
+impl Metric<f32, f32> for Kernel {
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        self.scalar(a, b)
+    }
+
+    fn distances_to_columns(&self, point: &[f32], columns: &Columns<f32>, out: &mut Vec<f32>) -> bool {
+        self.evaluate(columns, point, out).is_ok()
+    }
+}
@@ -63,19 +63,91 @@
 //! still truncates by `k` and only filters out items whose distance exceeds the
 //! optional `radius`. Mutating results remains an iterator-only operation
 //! (e.g. `iter_nearest_within_radius_mut`) so the tree never hands out overlapping
-//! mutable borrows.
+//! mutable borrows. `nearest_filtered` (and `nearest_filtered_within_radius`) only
+//! admit points whose point and payload pass a predicate, so they still return `k`
+//! results when enough points match. `nearest_to_set` finds the `k` points closest to
+//! any of several query points in one traversal, reporting which query each is nearest.
 //!
 //! `within` (and `within_count`) produce unordered batches that include every point
 //! inside the requested radius—sort the returned vector manually if you need a
 //! deterministic order. Use `bounding_box` for axis-aligned range queries when you
-//! only need raw `&T` references without ordering guarantees.
+//! only need raw `&T` references without ordering guarantees. `within_range` (and
+//! `within_range_count`) return the points whose distance lies between two radii.
+//! Other shapes are queried with `query_region` (and `iter_region`, `count_region`)
+//! through the [`region::Region`] trait. `nearest_to_shape` and `within_shape` measure
+//! distances to a query object such as a segment, ray or box instead of a point, see
+//! the [`shape`] module.
+//!
+//! Points with the wrong number of dimensions or a NaN or infinite coordinate are
+//! rejected with `ErrorKind::WrongDimension` or `ErrorKind::NonFiniteCoordinate`;
+//! `check_point` returns a [`PointError`] naming the dimension or axis at fault.
+//!
+//! Queries fail with `ErrorKind::NonFiniteDistance` when the metric returns NaN or an
+//! infinite distance instead of returning misordered results; the nearest iterators
+//! stop early in that case and report the error through their `error()` method.
+//!
+//! Points that are added more than `capacity` times at the exact same coordinate
+//! are kept in a single leaf that stores the coordinate once alongside all of its
+//! payloads, so duplicate-heavy data does not degrade `add` or query performance.
+//!
+//! Coordinates can be any [`Coordinate`]: floats, or integers such as `i64` whose
+//! distances are measured in a wider type (`u128`) so that they can't overflow.
+//! Queries return whatever distance type the metric returns, e.g.
+//! `distance::squared_euclidean_f64` ranks `f32` points by `f64` distances.
+//!
+//! `farthest` and `iter_farthest` return points in descending distance instead,
+//! pruning subtrees by the farthest corner of their bounding box.
+//!
+//! `reverse_nearest` finds the entries that would have a query point among their `k`
+//! nearest neighbours; `knn_radii` precomputes what `reverse_nearest_cached` needs to
+//! answer many such queries.
+//!
+//! The [`algorithms`] module builds clustering on top of the tree: a Euclidean minimum
+//! spanning tree (`emst`), single-linkage clusters cut from it, and DBSCAN.
+//!
+//! `max_inner_product` and `nearest_cosine` rank points by similarity instead of
+//! distance, returning the largest scores first, e.g. for embedding vectors.
+//!
+//! Trees created with `with_summary` keep a [`summary::Summary`] of the payloads in
+//! every subtree, which `nearest_pruned`, `within_pruned` and `bounding_box_pruned`
+//! use to skip subtrees without a match and `summarize_box` combines over a box.
+//! `aggregate_within` and `aggregate_box` fold the entries in a radius or box into
+//! any value, taking whole subtrees at once where possible; `bounding_box_count` and
+//! `kernel_density` (see the [`density`] module) are built on them.
+//!
+//! The [`kernels`] module evaluates a metric over a whole block of `f32` (or, with
+//! the `f16` feature, half precision) points stored as columns, vectorised with the
+//! `simd` feature.
+//!
+//! `write_to` and `read_from` store a tree in a compact, versioned binary format
+//! that is validated on load; see the [`binary`] module for the layout. A
+//! [`view::KdTreeView`] queries that format in place, e.g. from a memory map.
+//! With the `serialize` feature, the `portable` module offers a serde form that
+//! stores a flat list of entries instead of the internal node layout. With the
+//! `rkyv` feature, archived trees can be queried in place (see the `archived` module).
 
 #[cfg(feature = "serialize")]
 #[cfg_attr(feature = "serialize", macro_use)]
 extern crate serde_derive;
 
+pub mod algorithms;
+#[cfg(feature = "rkyv")]
+pub mod archived;
+pub mod binary;
+pub mod coordinate;
+pub mod density;
 pub mod distance;
 mod heap_element;
 pub mod kdtree;
+pub mod kernels;
+#[cfg(feature = "serialize")]
+pub mod portable;
+pub mod region;
+pub mod shape;
+pub mod summary;
+pub mod view;
+pub use crate::coordinate::{Coordinate, Distance};
 pub use crate::kdtree::ErrorKind;
 pub use crate::kdtree::KdTree;
+pub use crate::kdtree::NonFinite;
+pub use crate::kdtree::PointError;
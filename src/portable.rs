@@ -0,0 +1,441 @@
+//! Portable serde representation of a [`KdTree`].
+//!
+//! The derived serde implementation mirrors the internal node layout, so stored
+//! data is tied to this crate's internals. The functions in this module instead
+//! write a flat list of entries that other tools can read and that survives
+//! changes to the tree structure:
+//!
+//! ```json
+//! {"dimensions": 2, "capacity": 16, "entries": [[[0.0, 1.0], "a"], [[2.0, 3.0], "b"]]}
+//! ```
+//!
+//! The tree is rebuilt from the entries when it is deserialized, splitting each
+//! node at the median of its widest axis so that the rebuilt tree is balanced. Use
+//! [`with_structure`] to also store the split decisions of every node, which
+//! lets loading skip the rebuild. With self-describing formats such as JSON,
+//! [`with_structure`] also reads the plain form and `deserialize` ignores a
+//! stored structure. A structure that does not match the entries, e.g. one
+//! written by a version that splits differently, or one nested far deeper than
+//! splits reach, is ignored in favour of a rebuild.
+//!
+//! Use the modules with `#[serde(with = "kdtree::portable")]` on a field, or call
+//! [`serialize`] and [`deserialize`] directly with any serde format:
+//!
+//! ```
+//! use kdtree::KdTree;
+//!
+//! let mut tree: KdTree<f64, String, [f64; 2]> = KdTree::new(2);
+//! tree.add([0.0, 1.0], String::from("a")).unwrap();
+//!
+//! let mut json = Vec::new();
+//! kdtree::portable::serialize(&tree, &mut serde_json::Serializer::new(&mut json)).unwrap();
+//! assert_eq!(
+//!     String::from_utf8(json.clone()).unwrap(),
+//!     r#"{"dimensions":2,"capacity":16,"entries":[[[0.0,1.0],"a"]]}"#
+//! );
+//!
+//! let restored: KdTree<f64, String, [f64; 2]> =
+//!     kdtree::portable::deserialize(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+//! assert_eq!(restored.size(), 1);
+//! ```
+
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::coordinate::{Coordinate, Distance};
+use crate::kdtree::{KdTree, check_point, max_depth};
+
+pub fn serialize<A, T, U, S>(tree: &KdTree<A, T, U>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    A: Coordinate + Serialize,
+    T: Serialize,
+    U: AsRef<[A]>,
+    S: Serializer,
+{
+    FlatRef::new(tree, false).serialize(serializer)
+}
+
+pub fn deserialize<'de, A, T, U, D>(deserializer: D) -> Result<KdTree<A, T, U>, D::Error>
+where
+    A: Coordinate + Deserialize<'de>,
+    T: Deserialize<'de>,
+    U: AsRef<[A]> + for<'a> TryFrom<&'a [A]>,
+    D: Deserializer<'de>,
+{
+    FlatWithStructure::from(Flat::deserialize(deserializer)?).into_tree()
+}
+
+/// Portable representation that additionally stores the split of every node
+pub mod with_structure {
+    use super::*;
+
+    pub fn serialize<A, T, U, S>(tree: &KdTree<A, T, U>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        A: Coordinate + Serialize,
+        T: Serialize,
+        U: AsRef<[A]>,
+        S: Serializer,
+    {
+        FlatRef::new(tree, true).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, A, T, U, D>(deserializer: D) -> Result<KdTree<A, T, U>, D::Error>
+    where
+        A: Coordinate + Deserialize<'de>,
+        T: Deserialize<'de>,
+        U: AsRef<[A]> + for<'a> TryFrom<&'a [A]>,
+        D: Deserializer<'de>,
+    {
+        FlatWithStructure::deserialize(deserializer)?.into_tree()
+    }
+}
+
+/// Node of the stored structure, listed in pre-order. Entries are stored in the
+/// order of the leaves that hold them.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Node<A> {
+    Stem {
+        split_dimension: usize,
+        split_value: A,
+    },
+    Leaf {
+        len: usize,
+        #[serde(default)]
+        coincident: bool,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(bound = "A: Coordinate + Serialize, T: Serialize")]
+struct FlatRef<'a, A, T, U: AsRef<[A]>> {
+    dimensions: usize,
+    capacity: usize,
+    entries: Entries<'a, A, T, U>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    structure: Option<Vec<Node<A>>>,
+}
+
+impl<'a, A: Coordinate, T, U: AsRef<[A]>> FlatRef<'a, A, T, U> {
+    fn new(tree: &'a KdTree<A, T, U>, with_structure: bool) -> Self {
+        let nodes = preorder(tree);
+        let structure = with_structure.then(|| {
+            nodes
+                .iter()
+                .map(|node| match (node.split_dimension, node.split_value) {
+                    (Some(split_dimension), Some(split_value)) => Node::Stem {
+                        split_dimension,
+                        split_value,
+                    },
+                    _ => Node::Leaf {
+                        len: node.size,
+                        coincident: node.coincident,
+                    },
+                })
+                .collect()
+        });
+        FlatRef {
+            dimensions: tree.dimensions,
+            capacity: tree.capacity,
+            entries: Entries(nodes),
+            structure,
+        }
+    }
+}
+
+struct Entries<'a, A, T, U: AsRef<[A]>>(Vec<&'a KdTree<A, T, U>>);
+
+impl<A: Coordinate + Serialize, T: Serialize, U: AsRef<[A]>> Serialize for Entries<'_, A, T, U> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let len = self.0.first().map_or(0, |root| root.size);
+        let mut seq = serializer.serialize_seq(Some(len))?;
+        for node in self.0.iter().filter(|node| node.is_leaf()) {
+            let points = node.points.as_ref().unwrap();
+            for (p, data) in points.iter().cycle().zip(node.bucket.as_ref().unwrap()) {
+                seq.serialize_element(&(p.as_ref(), data))?;
+            }
+        }
+        seq.end()
+    }
+}
+
+// Formats that are not self-describing, like bincode, can't skip a missing trailing
+// field, so each module reads exactly the fields its `serialize` writes.
+#[derive(Deserialize)]
+#[serde(bound = "A: Deserialize<'de>, T: Deserialize<'de>")]
+struct Flat<A, T> {
+    dimensions: usize,
+    capacity: usize,
+    entries: Vec<(Vec<A>, T)>,
+}
+
+#[derive(Deserialize)]
+#[serde(bound = "A: Deserialize<'de>, T: Deserialize<'de>")]
+struct FlatWithStructure<A, T> {
+    dimensions: usize,
+    capacity: usize,
+    entries: Vec<(Vec<A>, T)>,
+    #[serde(default)]
+    structure: Option<Vec<Node<A>>>,
+}
+
+impl<A, T> From<Flat<A, T>> for FlatWithStructure<A, T> {
+    fn from(flat: Flat<A, T>) -> Self {
+        FlatWithStructure {
+            dimensions: flat.dimensions,
+            capacity: flat.capacity,
+            entries: flat.entries,
+            structure: None,
+        }
+    }
+}
+
+impl<A: Coordinate, T> FlatWithStructure<A, T> {
+    fn into_tree<U, E>(self) -> Result<KdTree<A, T, U>, E>
+    where
+        U: AsRef<[A]> + for<'a> TryFrom<&'a [A]>,
+        E: serde::de::Error,
+    {
+        if self.capacity == 0 {
+            return Err(E::custom("capacity must be greater than zero"));
+        }
+        let mut points = Vec::with_capacity(self.entries.len());
+        let mut bucket = Vec::with_capacity(self.entries.len());
+        for (p, data) in self.entries {
+            check_point(self.dimensions, &p).map_err(E::custom)?;
+            points.push(U::try_from(&p).map_err(|_| E::custom("coordinates do not fit the point type"))?);
+            bucket.push(data);
+        }
+        if let Some(structure) = self.structure {
+            let fits = check(&structure, &points, self.dimensions, self.capacity);
+            if fits == Some((points.len(), structure.len())) {
+                let mut entries = points.into_iter().zip(bucket);
+                return Ok(build(self.dimensions, self.capacity, structure, &mut entries));
+            }
+        }
+        // no structure, or one written by a version that partitions the points differently
+        let entries = points.into_iter().zip(bucket).collect();
+        Ok(balanced(self.dimensions, self.capacity, entries))
+    }
+}
+
+/// Bounds of the points a checked subtree holds, next to their number
+type Extent<A> = (usize, Vec<A>, Vec<A>);
+
+/// Checks that the pre-order `nodes` lay out a prefix of `points` the way `add` would,
+/// returning the number of points and nodes the tree spans. The structure is untrusted, so it is walked with an
+/// explicit stack of the stems whose subtrees are still being checked.
+fn check<A: Coordinate, U: AsRef<[A]>>(
+    nodes: &[Node<A>],
+    points: &[U],
+    dimensions: usize,
+    capacity: usize,
+) -> Option<(usize, usize)> {
+    let mut stems: Vec<(usize, A, Option<Extent<A>>)> = Vec::new();
+    let mut start = 0usize;
+    for (index, node) in nodes.iter().enumerate() {
+        let mut done = match *node {
+            Node::Stem {
+                split_dimension,
+                split_value,
+            } => {
+                if split_dimension >= dimensions || split_value.non_finite().is_some() {
+                    return None;
+                }
+                if stems.len() == max_depth(points.len(), capacity) {
+                    return None;
+                }
+                stems.push((split_dimension, split_value, None));
+                continue;
+            }
+            Node::Leaf { len, coincident } => {
+                let leaf = points.get(start..start.checked_add(len)?)?;
+                start += len;
+                if coincident && (len < 2 || leaf.iter().any(|p| p.as_ref() != leaf[0].as_ref())) {
+                    return None;
+                }
+                if !coincident && len > capacity {
+                    return None;
+                }
+                let mut min = vec![A::HIGHEST; dimensions];
+                let mut max = vec![A::LOWEST; dimensions];
+                for p in leaf {
+                    for ((l, h), &v) in min.iter_mut().zip(max.iter_mut()).zip(p.as_ref()) {
+                        if v < *l {
+                            *l = v;
+                        }
+                        if v > *h {
+                            *h = v;
+                        }
+                    }
+                }
+                (len, min, max)
+            }
+        };
+        // a finished subtree completes every stem it is the right child of
+        loop {
+            match stems.last_mut() {
+                None => return Some((done.0, index + 1)),
+                Some((.., left @ None)) => {
+                    *left = Some(done);
+                    break;
+                }
+                Some(_) => {
+                    let (split_dimension, split_value, left) = stems.pop().unwrap();
+                    let (left, left_min, left_max) = left.unwrap();
+                    let (right, right_min, right_max) = done;
+                    if (left > 0 && left_max[split_dimension] >= split_value)
+                        || (right > 0 && right_min[split_dimension] < split_value)
+                    {
+                        return None;
+                    }
+                    let min = left_min
+                        .iter()
+                        .zip(&right_min)
+                        .map(|(&l, &r)| if r < l { r } else { l })
+                        .collect();
+                    let max = left_max
+                        .iter()
+                        .zip(&right_max)
+                        .map(|(&l, &r)| if r > l { r } else { l })
+                        .collect();
+                    done = (left + right, min, max);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Rebuilds a tree from pre-order nodes that passed `check`, using an explicit stack
+/// like `check` does
+fn build<A: Coordinate, T, U: AsRef<[A]>>(
+    dimensions: usize,
+    capacity: usize,
+    nodes: impl IntoIterator<Item = Node<A>>,
+    entries: &mut impl Iterator<Item = (U, T)>,
+) -> KdTree<A, T, U> {
+    let mut stems = Vec::new();
+    for node in nodes {
+        let mut tree = KdTree::with_capacity(dimensions, capacity);
+        match node {
+            Node::Stem {
+                split_dimension,
+                split_value,
+            } => {
+                stems.push((split_dimension, split_value, None));
+                continue;
+            }
+            Node::Leaf { len, coincident } => {
+                let (mut points, bucket): (Vec<U>, Vec<T>) = entries.take(len).unzip();
+                for p in points.iter() {
+                    tree.extend(p.as_ref());
+                }
+                if coincident {
+                    points.truncate(1);
+                }
+                tree.size = len;
+                tree.coincident = coincident;
+                tree.points = Some(points);
+                tree.bucket = Some(bucket);
+            }
+        }
+        loop {
+            match stems.last_mut() {
+                None => return tree,
+                Some((.., left @ None)) => {
+                    *left = Some(tree);
+                    break;
+                }
+                Some(_) => {
+                    let (split_dimension, split_value, left) = stems.pop().unwrap();
+                    let left = left.unwrap();
+                    let right = tree;
+                    tree = KdTree::with_capacity(dimensions, capacity);
+                    tree.size = left.size + right.size;
+                    tree.split_dimension = Some(split_dimension);
+                    tree.split_value = Some(split_value);
+                    tree.left = Some(Box::new(left));
+                    tree.right = Some(Box::new(right));
+                    tree.points = None;
+                    tree.bucket = None;
+                    tree.refresh_bounds();
+                }
+            }
+        }
+    }
+    unreachable!("structure was checked against the entries")
+}
+
+/// Builds a tree of `entries` by splitting every node at the median of its widest axis.
+/// Ties stay on one side of the split, so the depth only grows with the logarithm of the
+/// number of entries, whatever order they come in.
+fn balanced<A: Coordinate, T, U: AsRef<[A]>>(
+    dimensions: usize,
+    capacity: usize,
+    mut entries: Vec<(U, T)>,
+) -> KdTree<A, T, U> {
+    let mut tree = KdTree::with_capacity(dimensions, capacity);
+    for (p, _) in entries.iter() {
+        tree.extend(p.as_ref());
+    }
+    tree.size = entries.len();
+    let mut widest = None;
+    let mut max = A::Distance::ZERO;
+    for dim in 0..dimensions {
+        let diff = tree.max_bounds[dim].abs_diff(tree.min_bounds[dim]);
+        if diff > max {
+            max = diff;
+            widest = Some(dim);
+        }
+    }
+    match widest {
+        Some(dim) if entries.len() > capacity => {
+            let coordinate = |entry: &(U, T)| entry.0.as_ref()[dim];
+            entries.sort_by(|a, b| coordinate(a).partial_cmp(&coordinate(b)).unwrap());
+            let len = entries.len();
+            let median = coordinate(&entries[len / 2]);
+            // split just before or just after the entries tied with the median, whichever
+            // is more even, and never leave a side empty
+            let before = entries.partition_point(|e| coordinate(e) < median);
+            let after = entries.partition_point(|e| coordinate(e) <= median);
+            let at = if before > 0 && (after == len || before.min(len - before) >= after.min(len - after)) {
+                before
+            } else {
+                after
+            };
+            let right = entries.split_off(at);
+            tree.split_dimension = Some(dim);
+            tree.split_value = Some(coordinate(&right[0]));
+            tree.left = Some(Box::new(balanced(dimensions, capacity, entries)));
+            tree.right = Some(Box::new(balanced(dimensions, capacity, right)));
+            tree.points = None;
+            tree.bucket = None;
+        }
+        _ => {
+            // a leaf over capacity has no widest axis, so all of its points are identical
+            let coincident = entries.len() > capacity;
+            let (mut points, bucket): (Vec<U>, Vec<T>) = entries.into_iter().unzip();
+            if coincident {
+                points.truncate(1);
+            }
+            tree.coincident = coincident;
+            tree.points = Some(points);
+            tree.bucket = Some(bucket);
+        }
+    }
+    tree
+}
+
+fn preorder<A, T, U: AsRef<[A]>>(tree: &KdTree<A, T, U>) -> Vec<&KdTree<A, T, U>> {
+    let mut nodes = vec![];
+    let mut pending = vec![tree];
+    while let Some(node) = pending.pop() {
+        nodes.push(node);
+        if let (Some(left), Some(right)) = (&node.left, &node.right) {
+            pending.push(right);
+            pending.push(left);
+        }
+    }
+    nodes
+}
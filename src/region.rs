@@ -0,0 +1,266 @@
+//! Shapes that can be queried with [`KdTree::query_region`](crate::KdTree::query_region).
+//!
+//! A [`Region`] answers whether it contains a point and how it relates to the
+//! bounding box of a subtree, which lets queries skip subtrees outside of it and
+//! take subtrees inside of it as a whole. Besides the built-in [`AxisBox`],
+//! [`Ball`], [`HalfSpace`] and [`Polygon`], any shape can be queried by
+//! implementing the trait.
+//!
+//! ```
+//! use kdtree::KdTree;
+//! use kdtree::region::Polygon;
+//!
+//! let mut tree: KdTree<f64, &str, [f64; 2]> = KdTree::new(2);
+//! tree.add([0.5, 0.5], "inside").unwrap();
+//! tree.add([3.0, 1.0], "outside").unwrap();
+//!
+//! let triangle = Polygon::new([[0.0, 0.0], [2.0, 0.0], [0.0, 2.0]]);
+//! assert_eq!(tree.query_region(&triangle).unwrap(), vec![&"inside"]);
+//! ```
+
+use crate::coordinate::{Coordinate, Distance};
+use crate::distance::squared_euclidean;
+use crate::kdtree::{ErrorKind, check_point};
+
+/// A set of points that can be tested against the bounding boxes of a tree
+pub trait Region<A> {
+    /// Number of dimensions of the points the region is defined for, `None` if it accepts any
+    fn dimensions(&self) -> Option<usize> {
+        None
+    }
+
+    fn contains(&self, point: &[A]) -> bool;
+
+    /// Whether the box may hold a point of the region. This must not return `false`
+    /// when it does, but may return `true` when it doesn't.
+    fn intersects_box(&self, min_bounds: &[A], max_bounds: &[A]) -> bool;
+
+    /// Whether every point of the box is in the region. This must not return `true`
+    /// when one isn't, but may return `false` when they all are.
+    fn contains_box(&self, min_bounds: &[A], max_bounds: &[A]) -> bool {
+        let _ = (min_bounds, max_bounds);
+        false
+    }
+}
+
+/// Axis-aligned box, including its boundary
+#[derive(Clone, Debug, PartialEq)]
+pub struct AxisBox<A> {
+    min_bounds: Vec<A>,
+    max_bounds: Vec<A>,
+}
+
+impl<A: Coordinate> AxisBox<A> {
+    pub fn new(min_bounds: Vec<A>, max_bounds: Vec<A>) -> Result<Self, ErrorKind> {
+        check_point(min_bounds.len(), &max_bounds)?;
+        check_point(min_bounds.len(), &min_bounds)?;
+        if let Some(axis) = min_bounds.iter().zip(&max_bounds).position(|(l, h)| l > h) {
+            return Err(ErrorKind::InvalidBoundingBox { axis });
+        }
+        Ok(AxisBox { min_bounds, max_bounds })
+    }
+
+    pub(crate) fn min_bounds(&self) -> &[A] {
+        &self.min_bounds
+    }
+
+    pub(crate) fn max_bounds(&self) -> &[A] {
+        &self.max_bounds
+    }
+}
+
+impl<A: Coordinate> Region<A> for AxisBox<A> {
+    fn dimensions(&self) -> Option<usize> {
+        Some(self.min_bounds.len())
+    }
+
+    fn contains(&self, point: &[A]) -> bool {
+        self.contains_box(point, point)
+    }
+
+    fn intersects_box(&self, min_bounds: &[A], max_bounds: &[A]) -> bool {
+        let mut axes = self
+            .min_bounds
+            .iter()
+            .zip(&self.max_bounds)
+            .zip(min_bounds.iter().zip(max_bounds));
+        axes.all(|((l, h), (min, max))| min <= h && max >= l)
+    }
+
+    fn contains_box(&self, min_bounds: &[A], max_bounds: &[A]) -> bool {
+        let mut axes = self
+            .min_bounds
+            .iter()
+            .zip(&self.max_bounds)
+            .zip(min_bounds.iter().zip(max_bounds));
+        axes.all(|((l, h), (min, max))| min >= l && max <= h)
+    }
+}
+
+/// Points whose Euclidean distance to `center` is at most `radius`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ball<A: Coordinate> {
+    center: Vec<A>,
+    squared_radius: A::Distance,
+}
+
+impl<A: Coordinate> Ball<A> {
+    pub fn new(center: Vec<A>, radius: A::Distance) -> Self {
+        Ball {
+            center,
+            squared_radius: radius.saturating_mul(radius),
+        }
+    }
+}
+
+impl<A: Coordinate> Region<A> for Ball<A> {
+    fn dimensions(&self) -> Option<usize> {
+        Some(self.center.len())
+    }
+
+    fn contains(&self, point: &[A]) -> bool {
+        squared_euclidean(&self.center, point) <= self.squared_radius
+    }
+
+    fn intersects_box(&self, min_bounds: &[A], max_bounds: &[A]) -> bool {
+        let closest = self
+            .center
+            .iter()
+            .zip(min_bounds.iter().zip(max_bounds))
+            .map(|(&c, (&l, &h))| {
+                if c < l {
+                    l
+                } else if c > h {
+                    h
+                } else {
+                    c
+                }
+            });
+        self.contains(&closest.collect::<Vec<_>>())
+    }
+
+    fn contains_box(&self, min_bounds: &[A], max_bounds: &[A]) -> bool {
+        let farthest = self
+            .center
+            .iter()
+            .zip(min_bounds.iter().zip(max_bounds))
+            .map(|(&c, (&l, &h))| if c.abs_diff(h) > c.abs_diff(l) { h } else { l });
+        self.contains(&farthest.collect::<Vec<_>>())
+    }
+}
+
+/// Points `p` with `normal · p <= offset`, computed in `f64`
+#[derive(Clone, Debug, PartialEq)]
+pub struct HalfSpace {
+    normal: Vec<f64>,
+    offset: f64,
+}
+
+impl HalfSpace {
+    pub fn new(normal: Vec<f64>, offset: f64) -> Self {
+        HalfSpace { normal, offset }
+    }
+
+    /// Smallest and largest value of `normal · p` over the box
+    fn extent<A: Copy + Into<f64>>(&self, min_bounds: &[A], max_bounds: &[A]) -> (f64, f64) {
+        let terms = self.normal.iter().zip(min_bounds.iter().zip(max_bounds));
+        terms.fold((0.0, 0.0), |(low, high), (&n, (&l, &h))| {
+            let (a, b) = (n * l.into(), n * h.into());
+            (low + a.min(b), high + a.max(b))
+        })
+    }
+}
+
+impl<A: Copy + Into<f64>> Region<A> for HalfSpace {
+    fn dimensions(&self) -> Option<usize> {
+        Some(self.normal.len())
+    }
+
+    fn contains(&self, point: &[A]) -> bool {
+        self.extent(point, point).0 <= self.offset
+    }
+
+    fn intersects_box(&self, min_bounds: &[A], max_bounds: &[A]) -> bool {
+        self.extent(min_bounds, max_bounds).0 <= self.offset
+    }
+
+    fn contains_box(&self, min_bounds: &[A], max_bounds: &[A]) -> bool {
+        self.extent(min_bounds, max_bounds).1 <= self.offset
+    }
+}
+
+/// Simple polygon in the plane, computed in `f64`. Whether a point lies inside is
+/// decided by the even-odd rule, points exactly on an edge may fall either way.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polygon {
+    vertices: Vec<[f64; 2]>,
+}
+
+impl Polygon {
+    pub fn new(vertices: impl IntoIterator<Item = [f64; 2]>) -> Self {
+        Polygon {
+            vertices: vertices.into_iter().collect(),
+        }
+    }
+
+    fn edges(&self) -> impl Iterator<Item = ([f64; 2], [f64; 2])> + '_ {
+        let next = self.vertices.iter().cycle().skip(1);
+        self.vertices.iter().copied().zip(next.copied())
+    }
+
+    fn contains_xy(&self, [x, y]: [f64; 2]) -> bool {
+        let mut inside = false;
+        for ([x1, y1], [x2, y2]) in self.edges() {
+            if (y1 > y) != (y2 > y) && x < x1 + (y - y1) / (y2 - y1) * (x2 - x1) {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    /// Whether any edge has a point in the closed box, clipping each edge to it
+    fn edge_meets_box(&self, min: [f64; 2], max: [f64; 2]) -> bool {
+        self.edges().any(|(a, b)| {
+            let (mut enter, mut exit) = (0.0f64, 1.0f64);
+            for axis in 0..2 {
+                let delta = b[axis] - a[axis];
+                if delta == 0.0 {
+                    if a[axis] < min[axis] || a[axis] > max[axis] {
+                        return false;
+                    }
+                    continue;
+                }
+                let (t1, t2) = ((min[axis] - a[axis]) / delta, (max[axis] - a[axis]) / delta);
+                enter = enter.max(t1.min(t2));
+                exit = exit.min(t1.max(t2));
+            }
+            enter <= exit
+        })
+    }
+}
+
+fn xy<A: Copy + Into<f64>>(point: &[A]) -> [f64; 2] {
+    [point[0].into(), point[1].into()]
+}
+
+impl<A: Copy + Into<f64>> Region<A> for Polygon {
+    fn dimensions(&self) -> Option<usize> {
+        Some(2)
+    }
+
+    fn contains(&self, point: &[A]) -> bool {
+        self.contains_xy(xy(point))
+    }
+
+    fn intersects_box(&self, min_bounds: &[A], max_bounds: &[A]) -> bool {
+        let (min, max) = (xy(min_bounds), xy(max_bounds));
+        // either the outline crosses the box or the box lies entirely inside the polygon
+        self.edge_meets_box(min, max) || self.contains_xy(min)
+    }
+
+    fn contains_box(&self, min_bounds: &[A], max_bounds: &[A]) -> bool {
+        let (min, max) = (xy(min_bounds), xy(max_bounds));
+        // without an edge in the box, the box lies entirely on one side of the outline
+        !self.edge_meets_box(min, max) && self.contains_xy(min)
+    }
+}
@@ -0,0 +1,263 @@
+//! Query objects for [`KdTree::nearest_to_shape`](crate::KdTree::nearest_to_shape) and
+//! [`KdTree::within_shape`](crate::KdTree::within_shape).
+//!
+//! A [`Shape`] measures the distance from itself to a stored point and gives a lower
+//! bound on the distance to any point of a subtree's bounding box, which is all the
+//! tree needs to find the points nearest to it. The built-in [`Point`], [`Segment`],
+//! [`Ray`] and [`AxisBox`] measure squared Euclidean distances; other shapes or
+//! metrics can be queried by implementing the trait.
+//!
+//! ```
+//! use kdtree::KdTree;
+//! use kdtree::shape::Segment;
+//!
+//! let mut tree: KdTree<f64, &str, [f64; 2]> = KdTree::new(2);
+//! tree.add([1.0, 1.0], "beside").unwrap();
+//! tree.add([5.0, 0.0], "past the end").unwrap();
+//!
+//! let road = Segment::new(vec![0.0, 0.0], vec![2.0, 0.0]).unwrap();
+//! let nearest = tree.nearest_to_shape(&road, 2).unwrap();
+//! assert_eq!(nearest, vec![(1.0, &"beside"), (9.0, &"past the end")]);
+//! ```
+
+use crate::coordinate::{Coordinate, Distance};
+use crate::distance::squared_euclidean;
+use crate::kdtree::{ErrorKind, check_point};
+pub use crate::region::AxisBox;
+
+/// A query object whose distance to points and boxes can be measured
+pub trait Shape<A> {
+    type Distance: Distance;
+
+    /// Number of dimensions of the points the shape is defined for, `None` if it accepts any
+    fn dimensions(&self) -> Option<usize> {
+        None
+    }
+
+    fn distance_to_point(&self, point: &[A]) -> Self::Distance;
+
+    /// Lower bound on the distance to any point of the box. This must not exceed
+    /// `distance_to_point` for any point inside it.
+    fn min_distance_to_box(&self, min_bounds: &[A], max_bounds: &[A]) -> Self::Distance;
+}
+
+/// A single point, the same query as [`KdTree::nearest`](crate::KdTree::nearest)
+/// with [`squared_euclidean`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Point<A> {
+    coordinates: Vec<A>,
+}
+
+impl<A> Point<A> {
+    pub fn new(coordinates: Vec<A>) -> Self {
+        Point { coordinates }
+    }
+}
+
+impl<A: Coordinate> Shape<A> for Point<A> {
+    type Distance = A::Distance;
+
+    fn dimensions(&self) -> Option<usize> {
+        Some(self.coordinates.len())
+    }
+
+    fn distance_to_point(&self, point: &[A]) -> A::Distance {
+        squared_euclidean(&self.coordinates, point)
+    }
+
+    fn min_distance_to_box(&self, min_bounds: &[A], max_bounds: &[A]) -> A::Distance {
+        box_gap(&self.coordinates, &self.coordinates, min_bounds, max_bounds)
+    }
+}
+
+impl<A: Coordinate> Shape<A> for AxisBox<A> {
+    type Distance = A::Distance;
+
+    fn dimensions(&self) -> Option<usize> {
+        Some(self.min_bounds().len())
+    }
+
+    fn distance_to_point(&self, point: &[A]) -> A::Distance {
+        box_gap(self.min_bounds(), self.max_bounds(), point, point)
+    }
+
+    fn min_distance_to_box(&self, min_bounds: &[A], max_bounds: &[A]) -> A::Distance {
+        box_gap(self.min_bounds(), self.max_bounds(), min_bounds, max_bounds)
+    }
+}
+
+/// Squared Euclidean distance between two boxes, zero when they overlap
+fn box_gap<A: Coordinate>(min1: &[A], max1: &[A], min2: &[A], max2: &[A]) -> A::Distance {
+    let axes = min1.iter().zip(max1).zip(min2.iter().zip(max2));
+    axes.map(|((&l1, &h1), (&l2, &h2))| {
+        let gap = if h1 < l2 {
+            l2.abs_diff(h1)
+        } else if h2 < l1 {
+            l1.abs_diff(h2)
+        } else {
+            A::Distance::ZERO
+        };
+        gap.saturating_mul(gap)
+    })
+    .fold(A::Distance::ZERO, Distance::saturating_add)
+}
+
+/// Line segment between two points, measuring squared Euclidean distances in `f64`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    line: Line,
+}
+
+impl Segment {
+    pub fn new(start: Vec<f64>, end: Vec<f64>) -> Result<Self, ErrorKind> {
+        check_point(start.len(), &end)?;
+        check_point(start.len(), &start)?;
+        let direction = end.iter().zip(&start).map(|(e, s)| e - s).collect();
+        Ok(Segment {
+            line: Line {
+                origin: start,
+                direction,
+                length: 1.0,
+            },
+        })
+    }
+}
+
+/// Half-line from `origin` along `direction`, measuring squared Euclidean distances in `f64`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ray {
+    line: Line,
+}
+
+impl Ray {
+    pub fn new(origin: Vec<f64>, direction: Vec<f64>) -> Result<Self, ErrorKind> {
+        check_point(origin.len(), &direction)?;
+        check_point(origin.len(), &origin)?;
+        Ok(Ray {
+            line: Line {
+                origin,
+                direction,
+                length: f64::INFINITY,
+            },
+        })
+    }
+}
+
+/// Points `origin + t * direction` for `t` in `0..=length`
+#[derive(Clone, Debug, PartialEq)]
+struct Line {
+    origin: Vec<f64>,
+    direction: Vec<f64>,
+    length: f64,
+}
+
+impl Line {
+    fn at(&self, t: f64) -> impl Iterator<Item = f64> + '_ {
+        self.origin.iter().zip(&self.direction).map(move |(o, d)| o + t * d)
+    }
+
+    fn distance_to_point<A: Copy + Into<f64>>(&self, point: &[A]) -> f64 {
+        let offset = point.iter().zip(&self.origin).map(|(&p, o)| p.into() - o);
+        let along = offset.zip(&self.direction).map(|(v, d)| v * d).sum::<f64>();
+        let squared_length = self.direction.iter().map(|d| d * d).sum::<f64>();
+        let t = if squared_length > 0.0 {
+            (along / squared_length).clamp(0.0, self.length)
+        } else {
+            0.0
+        };
+        let closest = self.at(t);
+        closest.zip(point).map(|(c, &p)| (p.into() - c).powi(2)).sum()
+    }
+
+    /// The squared distance to the box is convex in `t` and quadratic between the
+    /// parameters where the line enters or leaves a slab of the box, so it is
+    /// minimised piece by piece.
+    fn distance_to_box<A: Copy + Into<f64>>(&self, min_bounds: &[A], max_bounds: &[A]) -> f64 {
+        let mut breaks = vec![0.0];
+        for ((o, d), (&l, &h)) in self
+            .origin
+            .iter()
+            .zip(&self.direction)
+            .zip(min_bounds.iter().zip(max_bounds))
+        {
+            if *d != 0.0 {
+                for bound in [l.into(), h.into()] {
+                    let t = (bound - o) / d;
+                    if t > 0.0 && t < self.length {
+                        breaks.push(t);
+                    }
+                }
+            }
+        }
+        breaks.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        breaks.push(self.length);
+        let gap = |t: f64| -> f64 {
+            let axes = self.at(t).zip(min_bounds.iter().zip(max_bounds));
+            axes.map(|(x, (&l, &h))| (l.into() - x).max(x - h.into()).max(0.0).powi(2))
+                .sum()
+        };
+        let mut best = f64::INFINITY;
+        for piece in breaks.windows(2) {
+            let (start, end) = (piece[0], piece[1]);
+            let probe = if end.is_finite() {
+                (start + end) / 2.0
+            } else {
+                start + 1.0
+            };
+            // on this piece every axis outside its slab contributes (x - bound)^2
+            let (mut linear, mut quadratic) = (0.0, 0.0);
+            let axes = self.at(probe).zip(&self.origin).zip(&self.direction);
+            for (((x, o), d), (&l, &h)) in axes.zip(min_bounds.iter().zip(max_bounds)) {
+                let (l, h) = (l.into(), h.into());
+                let bound = if x < l {
+                    l
+                } else if x > h {
+                    h
+                } else {
+                    continue;
+                };
+                linear += (o - bound) * d;
+                quadratic += d * d;
+            }
+            let t = if quadratic > 0.0 {
+                (-linear / quadratic).clamp(start, end)
+            } else {
+                start
+            };
+            best = best.min(gap(t));
+        }
+        best
+    }
+}
+
+impl<A: Copy + Into<f64>> Shape<A> for Segment {
+    type Distance = f64;
+
+    fn dimensions(&self) -> Option<usize> {
+        Some(self.line.origin.len())
+    }
+
+    fn distance_to_point(&self, point: &[A]) -> f64 {
+        self.line.distance_to_point(point)
+    }
+
+    fn min_distance_to_box(&self, min_bounds: &[A], max_bounds: &[A]) -> f64 {
+        self.line.distance_to_box(min_bounds, max_bounds)
+    }
+}
+
+impl<A: Copy + Into<f64>> Shape<A> for Ray {
+    type Distance = f64;
+
+    fn dimensions(&self) -> Option<usize> {
+        Some(self.line.origin.len())
+    }
+
+    fn distance_to_point(&self, point: &[A]) -> f64 {
+        self.line.distance_to_point(point)
+    }
+
+    fn min_distance_to_box(&self, min_bounds: &[A], max_bounds: &[A]) -> f64 {
+        self.line.distance_to_box(min_bounds, max_bounds)
+    }
+}
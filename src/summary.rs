@@ -0,0 +1,58 @@
+//! Aggregates of the payloads below every node of a tree.
+//!
+//! A tree created with [`KdTree::with_summary`](crate::KdTree::with_summary) keeps a
+//! [`Summary`] of the payloads in every subtree up to date as entries are added,
+//! removed and moved. Queries such as [`KdTree::nearest_pruned`](crate::KdTree::nearest_pruned)
+//! use it to skip subtrees that can't hold a matching entry, and
+//! [`KdTree::summarize_box`](crate::KdTree::summarize_box) combines it over a box
+//! without visiting the leaves of subtrees that lie entirely inside.
+//!
+//! Trees without a summary use `()`, which costs nothing. Payloads of a summarised
+//! tree can't be borrowed mutably, since the summaries above them would go stale.
+//!
+//! ```
+//! use kdtree::KdTree;
+//! use kdtree::summary::Summary;
+//!
+//! /// Categories present in a subtree, one bit each
+//! #[derive(Clone, Debug, PartialEq)]
+//! struct Categories(u32);
+//!
+//! impl Summary<u32> for Categories {
+//!     fn empty() -> Self {
+//!         Categories(0)
+//!     }
+//!
+//!     fn add(&mut self, category: &u32) {
+//!         self.0 |= 1 << category;
+//!     }
+//!
+//!     fn merge(&mut self, other: &Self) {
+//!         self.0 |= other.0;
+//!     }
+//! }
+//!
+//! let mut tree: KdTree<f64, u32, [f64; 2], Categories> = KdTree::with_summary(2, 4);
+//! tree.add([0.0, 0.0], 1).unwrap();
+//! tree.add([5.0, 5.0], 3).unwrap();
+//! assert_eq!(tree.summary(), &Categories(0b1010));
+//! ```
+
+/// Aggregate of a set of payloads that can be built one payload at a time and
+/// combined with the aggregates of other sets
+pub trait Summary<T>: Clone {
+    /// Summary of a node without entries
+    fn empty() -> Self;
+
+    fn add(&mut self, data: &T);
+
+    fn merge(&mut self, other: &Self);
+}
+
+impl<T> Summary<T> for () {
+    fn empty() -> Self {}
+
+    fn add(&mut self, _: &T) {}
+
+    fn merge(&mut self, _: &Self) {}
+}
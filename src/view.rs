@@ -0,0 +1,366 @@
+//! Read-only trees queried in place from the [binary format](crate::binary).
+//!
+//! [`KdTreeView`] borrows the bytes written by [`KdTree::write_to`], typically
+//! from a memory map, and answers queries without rebuilding boxed nodes.
+//! Coordinates and bounds are read directly from the buffer; payloads are decoded
+//! only for the entries a query returns. Opening a view only checks the header and
+//! the section layout, so it costs the same for any file size.
+//!
+//! ```
+//! use kdtree::KdTree;
+//! use kdtree::distance::squared_euclidean;
+//! use kdtree::view::KdTreeView;
+//!
+//! let mut tree: KdTree<f64, u32, [f64; 2]> = KdTree::new(2);
+//! tree.add([1.0, 2.0], 7).unwrap();
+//! let mut bytes = Vec::new();
+//! tree.write_to(&mut bytes).unwrap();
+//!
+//! // memory maps are page aligned, a plain `Vec<u8>` has to be aligned by hand
+//! let mut buffer = vec![0u8; bytes.len() + 8];
+//! let start = buffer.as_ptr().align_offset(8);
+//! buffer[start..start + bytes.len()].copy_from_slice(&bytes);
+//!
+//! let view: KdTreeView<f64, u32> = KdTreeView::open(&buffer[start..start + bytes.len()]).unwrap();
+//! assert_eq!(view.nearest(&[0.0, 0.0], 1, &squared_euclidean).unwrap(), vec![(5.0, 7)]);
+//! ```
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::marker::PhantomData;
+
+use crate::binary::{FormatError, Layout, NodeRecord, Payload, Scalar, cast_section};
+use crate::coordinate::{Coordinate, Distance};
+use crate::heap_element::HeapElement;
+use crate::kdtree::{ClosestFirst, ErrorKind, KdTree, check_point, finite};
+
+/// Owned tree type used to reach the shared geometric helpers
+type Owned<A> = KdTree<A, (), Vec<A>>;
+
+/// A read-only tree borrowing its nodes, points and payloads from a byte buffer
+///
+/// The buffer must be aligned for `A` (any memory map is) and the host must be
+/// little-endian. [`KdTreeView::open`] leaves the nodes and payloads unchecked until a
+/// query reaches them: a corrupted file then fails that query with
+/// [`ErrorKind::Corrupted`], and may give wrong results, but never panics or loops.
+/// [`KdTreeView::open_validated`] checks everything up front like
+/// [`KdTree::read_from`](crate::KdTree::read_from) does.
+pub struct KdTreeView<'a, A, T> {
+    layout: Layout<'a>,
+    bounds: &'a [A],
+    points: &'a [A],
+    data: PhantomData<fn() -> T>,
+}
+
+impl<'a, A: Coordinate + Scalar, T: Payload> KdTreeView<'a, A, T> {
+    /// Opens a view after checking the header, alignment and the bounds of every section
+    pub fn open(bytes: &'a [u8]) -> Result<Self, FormatError> {
+        let layout = Layout::parse::<A>(bytes)?;
+        let bounds = cast_section(layout.bounds)?;
+        let points = cast_section(layout.points)?;
+        if layout.header.node_count == 0 {
+            return Err(FormatError::Corrupted("missing root node"));
+        }
+        Ok(KdTreeView {
+            layout,
+            bounds,
+            points,
+            data: PhantomData,
+        })
+    }
+
+    /// Opens a view after validating the whole structure and decoding every payload once,
+    /// which reads the entire buffer
+    pub fn open_validated(bytes: &'a [u8]) -> Result<Self, FormatError> {
+        let view = Self::open(bytes)?;
+        view.layout.validate::<A>()?;
+        let layout = &view.layout;
+        if (0..layout.header.entry_count).any(|entry| layout.payload(entry).ok().and_then(T::decode).is_none()) {
+            return Err(FormatError::Corrupted("malformed payload"));
+        }
+        Ok(view)
+    }
+
+    pub fn size(&self) -> usize {
+        self.layout.header.entry_count
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.layout.header.dimensions
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.layout.header.capacity
+    }
+
+    pub fn nearest<D, F>(&self, point: &[A], num: usize, distance: &F) -> Result<Vec<(D, T)>, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        check_point(self.dimensions(), point)?;
+        let num = std::cmp::min(num, self.size());
+        if num == 0 {
+            return Ok(vec![]);
+        }
+        let mut pending = BinaryHeap::new();
+        let mut evaluated = BinaryHeap::<HeapElement<D, usize>>::new();
+        let mut budget = self.layout.header.node_count;
+        pending.push(Reverse(HeapElement {
+            distance: D::ZERO,
+            element: 0,
+        }));
+        while let Some(next) = pending.peek() {
+            let bound = next.0.distance;
+            if evaluated.len() == num && evaluated.peek().is_some_and(|x| bound > x.distance) {
+                break;
+            }
+            self.nearest_step(point, num, D::MAX, distance, &mut pending, &mut evaluated, &mut budget)?;
+        }
+        evaluated
+            .into_sorted_vec()
+            .into_iter()
+            .take(num)
+            .map(|e| Ok((e.distance, self.data(e.element)?)))
+            .collect()
+    }
+
+    pub fn within<D, F>(&self, point: &[A], radius: D, distance: &F) -> Result<Vec<(D, T)>, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        check_point(self.dimensions(), point)?;
+        if self.size() == 0 {
+            return Ok(vec![]);
+        }
+        let evaluated = self.evaluated_heap(point, radius, distance)?;
+        evaluated
+            .into_iter()
+            .map(|e| Ok((e.distance, self.data(e.element)?)))
+            .collect()
+    }
+
+    pub fn within_count<D, F>(&self, point: &[A], radius: D, distance: &F) -> Result<usize, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        check_point(self.dimensions(), point)?;
+        if self.size() == 0 {
+            return Ok(0);
+        }
+        let evaluated = self.evaluated_heap(point, radius, distance)?;
+        Ok(evaluated.len())
+    }
+
+    pub fn bounding_box(&self, min_bounds: &[A], max_bounds: &[A]) -> Result<Vec<T>, ErrorKind> {
+        check_point(self.dimensions(), min_bounds)?;
+        check_point(self.dimensions(), max_bounds)?;
+        if let Some(axis) = min_bounds.iter().zip(max_bounds).position(|(l, h)| l > h) {
+            return Err(ErrorKind::InvalidBoundingBox { axis });
+        }
+        if self.size() == 0 {
+            return Ok(vec![]);
+        }
+        let mut pending = vec![0];
+        let mut evaluated = vec![];
+        let mut budget = self.layout.header.node_count;
+        while let Some(index) = pending.pop() {
+            let node = self.node(index, &mut budget)?;
+            if node.is_leaf() {
+                let entries = node.entry_start as usize..(node.entry_start + node.len) as usize;
+                if node.is_coincident() {
+                    if Owned::<A>::in_bounding_box(self.point(node.point_start as usize), min_bounds, max_bounds) {
+                        for entry in entries {
+                            evaluated.push(self.data(entry)?);
+                        }
+                    }
+                    continue;
+                }
+                for (row, entry) in (node.point_start as usize..).zip(entries) {
+                    if Owned::<A>::in_bounding_box(self.point(row), min_bounds, max_bounds) {
+                        evaluated.push(self.data(entry)?);
+                    }
+                }
+            } else {
+                if self.belongs_in_left(index, &node, min_bounds) {
+                    pending.push(node.left as usize);
+                }
+                if !self.belongs_in_left(index, &node, max_bounds) {
+                    pending.push(node.right as usize);
+                }
+            }
+        }
+        Ok(evaluated)
+    }
+
+    // ============================================================================
+    // === TRAVERSAL HELPERS ===
+    // ============================================================================
+    #[allow(clippy::too_many_arguments)]
+    fn nearest_step<D, F>(
+        &self,
+        point: &[A],
+        num: usize,
+        max_dist: D,
+        distance: &F,
+        pending: &mut ClosestFirst<D, usize>,
+        evaluated: &mut BinaryHeap<HeapElement<D, usize>>,
+        budget: &mut usize,
+    ) -> Result<(), ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        let Some(Reverse(HeapElement { element: mut curr, .. })) = pending.pop() else {
+            return Ok(());
+        };
+        let evaluated_dist = match evaluated.peek() {
+            Some(worst) if evaluated.len() == num && worst.distance < max_dist => worst.distance,
+            _ => max_dist,
+        };
+
+        let mut node = self.node(curr, budget)?;
+        while !node.is_leaf() {
+            let candidate;
+            if self.belongs_in_left(curr, &node, point) {
+                candidate = node.right as usize;
+                curr = node.left as usize;
+            } else {
+                candidate = node.left as usize;
+                curr = node.right as usize;
+            }
+            node = self.node(curr, budget)?;
+            if self.layout.node(candidate).size == 0 {
+                continue;
+            }
+            let candidate_to_space = finite(Owned::<A>::distance_to_space(
+                point,
+                self.min_bounds(candidate),
+                self.max_bounds(candidate),
+                distance,
+            ))?;
+            if candidate_to_space <= evaluated_dist {
+                pending.push(Reverse(HeapElement {
+                    distance: candidate_to_space,
+                    element: candidate,
+                }));
+            }
+        }
+
+        let mut shared = None;
+        let entries = node.entry_start as usize..(node.entry_start + node.len) as usize;
+        for (offset, entry) in entries.enumerate() {
+            let dist = if node.is_coincident() {
+                *shared.get_or_insert_with(|| distance(point, self.point(node.point_start as usize)))
+            } else {
+                distance(point, self.point(node.point_start as usize + offset))
+            };
+            let element = HeapElement {
+                distance: dist,
+                element: entry,
+            };
+            if finite(element.distance)? <= max_dist {
+                if evaluated.len() < num {
+                    evaluated.push(element);
+                } else if evaluated.peek().is_some_and(|worst| element < *worst) {
+                    evaluated.pop();
+                    evaluated.push(element);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn evaluated_heap<D, F>(
+        &self,
+        point: &[A],
+        radius: D,
+        distance: &F,
+    ) -> Result<BinaryHeap<HeapElement<D, usize>>, ErrorKind>
+    where
+        D: Distance,
+        F: Fn(&[A], &[A]) -> D,
+    {
+        let mut pending = BinaryHeap::new();
+        let mut evaluated = BinaryHeap::new();
+        let mut budget = self.layout.header.node_count;
+        pending.push(Reverse(HeapElement {
+            distance: D::ZERO,
+            element: 0,
+        }));
+        while pending.peek().is_some_and(|next| next.0.distance <= radius) {
+            self.nearest_step(
+                point,
+                self.size(),
+                radius,
+                distance,
+                &mut pending,
+                &mut evaluated,
+                &mut budget,
+            )?;
+        }
+        Ok(evaluated)
+    }
+
+    /// Reads a node a query is about to visit, checking what the query relies on since `open`
+    /// doesn't. Children have to come after their parent, as they do in pre-order, so every
+    /// path ends; `budget` counts down from the number of nodes so that a query visits no
+    /// node twice even when corrupted stems share their children.
+    fn node(&self, index: usize, budget: &mut usize) -> Result<NodeRecord, ErrorKind> {
+        let header = &self.layout.header;
+        *budget = budget
+            .checked_sub(1)
+            .ok_or(ErrorKind::Corrupted("subtrees share nodes"))?;
+        let node = self.layout.node(index);
+        if node.is_leaf() {
+            let rows = node.point_start.checked_add(node.rows());
+            let entries = node.entry_start.checked_add(node.len);
+            if rows.is_none_or(|end| end > header.point_rows as u64)
+                || entries.is_none_or(|end| end > header.entry_count as u64)
+            {
+                return Err(ErrorKind::Corrupted("leaf ranges exceed their sections"));
+            }
+        } else {
+            let children = [node.left, node.right];
+            if children
+                .iter()
+                .any(|&child| child <= index as u64 || child >= header.node_count as u64)
+                || node.split_dimension as usize >= header.dimensions
+            {
+                return Err(ErrorKind::Corrupted("stem references are out of range"));
+            }
+        }
+        Ok(node)
+    }
+
+    fn belongs_in_left(&self, index: usize, node: &NodeRecord, point: &[A]) -> bool {
+        point[node.split_dimension as usize] < self.bounds[index * self.bounds_stride()]
+    }
+
+    /// Number of scalars in a node's bounds record: split value, min and max bounds
+    fn bounds_stride(&self) -> usize {
+        2 * self.dimensions() + 1
+    }
+
+    fn min_bounds(&self, index: usize) -> &[A] {
+        let start = index * self.bounds_stride() + 1;
+        &self.bounds[start..start + self.dimensions()]
+    }
+
+    fn max_bounds(&self, index: usize) -> &[A] {
+        let start = index * self.bounds_stride() + 1 + self.dimensions();
+        &self.bounds[start..start + self.dimensions()]
+    }
+
+    fn point(&self, row: usize) -> &[A] {
+        let dims = self.dimensions();
+        &self.points[row * dims..(row + 1) * dims]
+    }
+
+    fn data(&self, entry: usize) -> Result<T, ErrorKind> {
+        let payload = self.layout.payload(entry).ok().and_then(T::decode);
+        payload.ok_or(ErrorKind::Corrupted("malformed payload"))
+    }
+}
@@ -0,0 +1,239 @@
+use kdtree::density::DensityKernel;
+use kdtree::distance::squared_euclidean;
+use kdtree::summary::Summary;
+use kdtree::{ErrorKind, KdTree};
+use rand::Rng;
+
+/// Sum of the weights below a node
+#[derive(Clone, Debug, PartialEq)]
+struct Weight(f64);
+
+impl Summary<f64> for Weight {
+    fn empty() -> Self {
+        Weight(0.0)
+    }
+
+    fn add(&mut self, weight: &f64) {
+        self.0 += weight;
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.0 += other.0;
+    }
+}
+
+type Tree = KdTree<f64, f64, [f64; 2], Weight>;
+
+#[test]
+fn aggregate_within_matches_brute_force() {
+    let mut rng = rand::rng();
+    // whole weights keep the sums exact whatever order they are added in
+    let entries = (0..1000)
+        .map(|_| {
+            let p = [rng.random_range(-10.0..10.0), rng.random_range(-10.0..10.0)];
+            (p, f64::from(rng.random_range(0..10u8)))
+        })
+        .collect::<Vec<_>>();
+    let mut tree = Tree::with_summary(2, 4);
+    for &(p, w) in &entries {
+        tree.add(p, w).unwrap();
+    }
+    for _ in 0..30 {
+        let query = [rng.random_range(-12.0..12.0), rng.random_range(-12.0..12.0)];
+        let radius = rng.random_range(0.0..60.0);
+        let inside = entries
+            .iter()
+            .filter(|(p, _)| squared_euclidean(p, &query) <= radius)
+            .collect::<Vec<_>>();
+
+        let (count, sum) = tree
+            .aggregate_within(
+                &query,
+                radius,
+                &squared_euclidean,
+                (0, 0.0),
+                |(count, sum), subtree, size| {
+                    *count += size;
+                    *sum += subtree.0;
+                    true
+                },
+                |(count, sum), _, w| {
+                    *count += 1;
+                    *sum += w;
+                },
+            )
+            .unwrap();
+        assert_eq!(count, inside.len());
+        assert_eq!(sum, inside.iter().map(|(_, w)| w).sum::<f64>());
+
+        // declining every subtree visits each entry with its distance instead
+        let mut distances = tree
+            .aggregate_within(
+                &query,
+                radius,
+                &squared_euclidean,
+                vec![],
+                |_, _, _| false,
+                |found, d, _| found.push(d),
+            )
+            .unwrap();
+        distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mut expected = inside
+            .iter()
+            .map(|(p, _)| squared_euclidean(p, &query))
+            .collect::<Vec<_>>();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(distances, expected);
+    }
+}
+
+#[test]
+fn aggregate_box_matches_brute_force() {
+    let mut rng = rand::rng();
+    // whole weights keep the sums exact whatever order they are added in
+    let entries = (0..1000)
+        .map(|_| {
+            let p = [rng.random_range(-10.0..10.0), rng.random_range(-10.0..10.0)];
+            (p, f64::from(rng.random_range(0..10u8)))
+        })
+        .collect::<Vec<_>>();
+    let mut tree = Tree::with_summary(2, 4);
+    for &(p, w) in &entries {
+        tree.add(p, w).unwrap();
+    }
+    for _ in 0..30 {
+        let min = [rng.random_range(-12.0..12.0), rng.random_range(-12.0..12.0)];
+        let max = [
+            min[0] + rng.random_range(0.0..15.0),
+            min[1] + rng.random_range(0.0..15.0),
+        ];
+        let inside = entries
+            .iter()
+            .filter(|(p, _)| (0..2).all(|i| min[i] <= p[i] && p[i] <= max[i]))
+            .collect::<Vec<_>>();
+
+        assert_eq!(tree.bounding_box_count(&min, &max).unwrap(), inside.len());
+        let sum = tree
+            .aggregate_box(
+                &min,
+                &max,
+                0.0,
+                |sum, subtree, _| {
+                    *sum += subtree.0;
+                    true
+                },
+                |sum, w| *sum += w,
+            )
+            .unwrap();
+        assert_eq!(sum, inside.iter().map(|(_, w)| w).sum::<f64>());
+        assert_eq!(tree.summarize_box(&min, &max).unwrap(), Weight(sum));
+    }
+    assert_eq!(
+        tree.bounding_box_count(&[1.0, 0.0], &[0.0, 1.0]),
+        Err(ErrorKind::InvalidBoundingBox { axis: 0 })
+    );
+}
+
+#[test]
+fn kernel_density_matches_brute_force() {
+    let mut rng = rand::rng();
+    let entries = (0..500)
+        .map(|_| {
+            let p = [rng.random_range(-10.0..10.0), rng.random_range(-10.0..10.0)];
+            (p, rng.random_range(0.5..2.0))
+        })
+        .collect::<Vec<_>>();
+    let mut tree: KdTree<f64, f64, [f64; 2]> = KdTree::with_capacity(2, 4);
+    for &(p, w) in &entries {
+        tree.add(p, w).unwrap();
+    }
+    let kernels = [DensityKernel::Gaussian { cutoff: 3.0 }, DensityKernel::Epanechnikov];
+    for _ in 0..20 {
+        let query = [rng.random_range(-12.0..12.0), rng.random_range(-12.0..12.0)];
+        let bandwidth = rng.random_range(0.1..4.0);
+        for kernel in kernels {
+            let expected = entries
+                .iter()
+                .map(|(p, w)| w * kernel.evaluate(squared_euclidean(p, &query) / (bandwidth * bandwidth)))
+                .sum::<f64>();
+            let density = tree
+                .kernel_density(&query, bandwidth, kernel, &squared_euclidean, |&w| w)
+                .unwrap();
+            assert!((density - expected).abs() <= 1e-9 * expected.max(1.0));
+        }
+    }
+}
+
+#[test]
+fn aggregates_count_every_entry_of_a_coincident_leaf() {
+    // six copies of one point overflow a leaf of two, which then keeps a single copy
+    let mut tree = Tree::with_summary(2, 2);
+    for w in 1..=6 {
+        tree.add([1.0, 1.0], f64::from(w)).unwrap();
+    }
+    tree.add([4.0, 0.0], 10.0).unwrap();
+    tree.add([0.0, 4.0], 20.0).unwrap();
+
+    let sum_in = |min: &[f64], max: &[f64]| {
+        tree.aggregate_box(min, max, 0.0, |_, _, _| false, |sum, w| *sum += w)
+            .unwrap()
+    };
+    assert_eq!(sum_in(&[0.5, 0.5], &[1.5, 1.5]), 21.0);
+    assert_eq!(sum_in(&[1.5, 0.0], &[4.0, 1.0]), 10.0);
+    assert_eq!(tree.bounding_box_count(&[0.0, 0.0], &[1.0, 1.0]), Ok(6));
+    assert_eq!(tree.bounding_box_count(&[1.5, 1.5], &[3.0, 3.0]), Ok(0));
+
+    let distances = tree
+        .aggregate_within(
+            &[1.0, 1.0],
+            0.5,
+            &squared_euclidean,
+            vec![],
+            |_, _, _| false,
+            |found, d, _| found.push(d),
+        )
+        .unwrap();
+    assert_eq!(distances, vec![0.0; 6]);
+    assert_eq!(
+        tree.kernel_density(
+            &[1.0, 1.0],
+            1.0,
+            DensityKernel::Epanechnikov,
+            &squared_euclidean,
+            |&w| w
+        ),
+        Ok(21.0)
+    );
+}
+
+#[test]
+fn kernel_density_edge_cases() {
+    let kernel = DensityKernel::Gaussian { cutoff: 2.0 };
+    assert_eq!(kernel.evaluate(0.0), 1.0);
+    assert_eq!(kernel.evaluate(4.0), (-2.0f64).exp());
+    assert_eq!(kernel.evaluate(4.5), 0.0);
+    assert_eq!(DensityKernel::Epanechnikov.evaluate(2.0), 0.0);
+
+    let empty: KdTree<f64, f64, [f64; 2]> = KdTree::new(2);
+    assert_eq!(
+        empty.kernel_density(&[0.0, 0.0], 1.0, kernel, &squared_euclidean, |&w| w),
+        Ok(0.0)
+    );
+    for bandwidth in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+        assert_eq!(
+            empty.kernel_density(&[0.0, 0.0], bandwidth, kernel, &squared_euclidean, |&w| w),
+            Err(ErrorKind::InvalidBandwidth)
+        );
+    }
+    for cutoff in [0.0, -3.0, f64::NAN, f64::INFINITY] {
+        let kernel = DensityKernel::Gaussian { cutoff };
+        assert_eq!(
+            empty.kernel_density(&[0.0, 0.0], 1.0, kernel, &squared_euclidean, |&w| w),
+            Err(ErrorKind::InvalidBandwidth)
+        );
+    }
+    assert_eq!(
+        empty.kernel_density(&[0.0], 1.0, kernel, &squared_euclidean, |&w| w),
+        Err(ErrorKind::WrongDimension)
+    );
+}
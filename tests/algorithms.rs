@@ -0,0 +1,154 @@
+use kdtree::ErrorKind;
+use kdtree::algorithms::{dbscan, emst, single_linkage};
+use kdtree::distance::{manhattan, squared_euclidean};
+use rand::Rng;
+
+fn grid_points(rng: &mut impl Rng, count: usize) -> Vec<[f64; 2]> {
+    // a coarse grid produces ties and duplicate points
+    (0..count)
+        .map(|_| {
+            [
+                f64::from(rng.random_range(0..40u8)),
+                f64::from(rng.random_range(0..40u8)),
+            ]
+        })
+        .collect()
+}
+
+/// Total weight of a minimum spanning tree, with Prim's algorithm over the complete graph
+fn prim(points: &[[f64; 2]], distance: fn(&[f64], &[f64]) -> f64) -> f64 {
+    let mut reached = vec![false; points.len()];
+    let mut cost = vec![f64::MAX; points.len()];
+    cost[0] = 0.0;
+    let mut total = 0.0;
+    for _ in 0..points.len() {
+        let next = (0..points.len())
+            .filter(|&i| !reached[i])
+            .min_by(|&a, &b| cost[a].partial_cmp(&cost[b]).unwrap())
+            .unwrap();
+        reached[next] = true;
+        total += cost[next];
+        for (c, p) in cost.iter_mut().zip(points) {
+            *c = c.min(distance(&points[next], p));
+        }
+    }
+    total
+}
+
+/// Labels of the connected components of the graph joining points closer than `within`
+fn components(points: &[[f64; 2]], within: impl Fn(usize, usize) -> bool) -> Vec<usize> {
+    let mut labels = vec![usize::MAX; points.len()];
+    let mut clusters = 0;
+    for start in 0..points.len() {
+        if labels[start] != usize::MAX {
+            continue;
+        }
+        labels[start] = clusters;
+        let mut pending = vec![start];
+        while let Some(i) = pending.pop() {
+            let joined = (0..points.len())
+                .filter(|&j| labels[j] == usize::MAX && within(i, j))
+                .collect::<Vec<_>>();
+            for j in joined {
+                labels[j] = clusters;
+                pending.push(j);
+            }
+        }
+        clusters += 1;
+    }
+    labels
+}
+
+#[test]
+fn emst_matches_brute_force() {
+    let mut rng = rand::rng();
+    for count in [1, 2, 50, 700] {
+        let points = grid_points(&mut rng, count);
+        for distance in [squared_euclidean::<f64>, manhattan::<f64>] {
+            let edges = emst(&points, &distance).unwrap();
+            assert_eq!(edges.len(), count - 1);
+            assert!(edges.windows(2).all(|pair| pair[0].2 <= pair[1].2));
+            for &(a, b, weight) in &edges {
+                assert!(a < b);
+                assert_eq!(weight, distance(&points[a], &points[b]));
+            }
+            // the edges connect every point
+            assert!(single_linkage(count, &edges, f64::MAX).iter().all(|&label| label == 0));
+            assert_eq!(edges.iter().map(|e| e.2).sum::<f64>(), prim(&points, distance));
+        }
+    }
+
+    let random = (0..500)
+        .map(|_| [rng.random_range(-10.0..10.0), rng.random_range(-10.0..10.0)])
+        .collect::<Vec<[f64; 2]>>();
+    let edges = emst(&random, &squared_euclidean).unwrap();
+    let total = edges.iter().map(|e| e.2).sum::<f64>();
+    assert!((total - prim(&random, squared_euclidean)).abs() < 1e-9);
+}
+
+#[test]
+fn single_linkage_matches_brute_force() {
+    let mut rng = rand::rng();
+    let points = grid_points(&mut rng, 300);
+    let edges = emst(&points, &squared_euclidean).unwrap();
+    for cut in [0.0, 1.0, 2.0, 5.0, 10.0] {
+        let expected = components(&points, |i, j| squared_euclidean(&points[i], &points[j]) <= cut);
+        assert_eq!(single_linkage(points.len(), &edges, cut), expected);
+    }
+}
+
+#[test]
+fn dbscan_matches_brute_force() {
+    let mut rng = rand::rng();
+    let points = grid_points(&mut rng, 600);
+    for (radius, min_points) in [(2.0, 3), (5.0, 6), (1.0, 1), (0.0, 2)] {
+        let near = |i: usize, j: usize| squared_euclidean(&points[i], &points[j]) <= radius;
+        let core = (0..points.len())
+            .map(|i| (0..points.len()).filter(|&j| near(i, j)).count() >= min_points)
+            .collect::<Vec<_>>();
+        let clusters = components(&points, |i, j| core[i] && core[j] && near(i, j));
+
+        let labels = dbscan(&points, radius, min_points, &squared_euclidean).unwrap();
+        for i in 0..points.len() {
+            if core[i] {
+                // core points share a label exactly when they are connected
+                for j in (0..points.len()).filter(|&j| core[j]) {
+                    assert_eq!(labels[i] == labels[j], clusters[i] == clusters[j]);
+                }
+                assert!(labels[i].is_some());
+            } else {
+                // border points join the cluster of a core point in reach, the rest is noise
+                let reachable = (0..points.len())
+                    .filter(|&j| core[j] && near(i, j))
+                    .map(|j| labels[j])
+                    .collect::<Vec<_>>();
+                match labels[i] {
+                    Some(_) => assert!(reachable.contains(&labels[i])),
+                    None => assert!(reachable.is_empty()),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn algorithm_edge_cases() {
+    let none: [[f64; 2]; 0] = [];
+    assert_eq!(emst(&none, &squared_euclidean).unwrap(), vec![]);
+    assert_eq!(dbscan(&none, 1.0, 2, &squared_euclidean).unwrap(), vec![]);
+    assert_eq!(single_linkage::<f64>(0, &[], 1.0), Vec::<usize>::new());
+
+    let mixed = [vec![0.0, 0.0], vec![1.0]];
+    assert_eq!(emst(&mixed, &squared_euclidean), Err(ErrorKind::WrongDimension));
+    assert_eq!(
+        dbscan(&[[0.0, f64::NAN]], 1.0, 2, &squared_euclidean),
+        Err(ErrorKind::NonFiniteCoordinate)
+    );
+
+    // integer coordinates measure exact distances
+    let points = [[0i64, 0], [3, 4], [3, 5], [100, 100]];
+    assert_eq!(
+        emst(&points, &squared_euclidean).unwrap(),
+        vec![(1, 2, 1u128), (0, 1, 25), (2, 3, 18434)]
+    );
+}
@@ -0,0 +1,207 @@
+mod __util__;
+
+use __util__::{POINTS, basic_tree};
+use kdtree::KdTree;
+use kdtree::binary::{FormatError, VERSION};
+use kdtree::distance::squared_euclidean;
+use rand::Rng;
+
+fn encode<T: kdtree::binary::Payload, U: AsRef<[f64]>>(tree: &KdTree<f64, T, U>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    tree.write_to(&mut bytes).unwrap();
+    bytes
+}
+
+fn decode(bytes: &[u8]) -> Result<KdTree<f64, usize, [f64; 2]>, FormatError> {
+    KdTree::read_from(bytes)
+}
+
+#[test]
+fn round_trip_preserves_entries_and_queries() {
+    let mut rng = rand::rng();
+    let mut tree: KdTree<f64, usize, Vec<f64>> = KdTree::with_capacity(3, 4);
+    for i in 0..1000 {
+        let point: Vec<f64> = (0..3).map(|_| rng.random()).collect();
+        tree.add(point, i).unwrap();
+    }
+    let bytes = encode(&tree);
+    assert_eq!(bytes.len() % 8, 0);
+    let restored: KdTree<f64, usize, Vec<f64>> = KdTree::read_from(&bytes[..]).unwrap();
+    assert_eq!(restored.size(), tree.size());
+    assert_eq!(restored.bounding_box(&[0.0; 3], &[1.0; 3]).unwrap().len(), 1000);
+    for _ in 0..20 {
+        let query: Vec<f64> = (0..3).map(|_| rng.random()).collect();
+        assert_eq!(
+            restored.nearest(&query, 8, &squared_euclidean).unwrap(),
+            tree.nearest(&query, 8, &squared_euclidean).unwrap()
+        );
+        assert_eq!(
+            restored.within(&query, 0.05, &squared_euclidean).unwrap(),
+            tree.within(&query, 0.05, &squared_euclidean).unwrap()
+        );
+    }
+}
+
+#[test]
+fn round_trip_keeps_tree_usable() {
+    let mut restored = decode(&encode(&basic_tree())).unwrap();
+    for (point, data) in POINTS.iter() {
+        assert_eq!(restored.get(point), Ok(Some(data)));
+    }
+    restored.add([4.0, 4.0], 4).unwrap();
+    assert_eq!(restored.remove(&[0.0, 0.0], &0), Ok(1));
+    assert_eq!(restored.size(), 4);
+}
+
+#[test]
+fn round_trip_empty_tree() {
+    let tree: KdTree<f64, usize, [f64; 2]> = KdTree::new(2);
+    let restored = decode(&encode(&tree)).unwrap();
+    assert_eq!(restored.size(), 0);
+    assert_eq!(restored.nearest(&[0.0, 0.0], 1, &squared_euclidean), Ok(vec![]));
+}
+
+#[test]
+fn round_trip_variable_length_payloads_and_coincident_leaves() {
+    let mut tree: KdTree<f64, String, [f64; 2]> = KdTree::with_capacity(2, 2);
+    for i in 0..10 {
+        tree.add([1.0, 1.0], "x".repeat(i)).unwrap();
+    }
+    tree.add([2.0, 0.0], String::from("other")).unwrap();
+    let restored: KdTree<f64, String, [f64; 2]> = KdTree::read_from(&encode(&tree)[..]).unwrap();
+    let mut found = restored
+        .within(&[1.0, 1.0], 0.0, &squared_euclidean)
+        .unwrap()
+        .into_iter()
+        .map(|(_, data)| data.len())
+        .collect::<Vec<_>>();
+    found.sort_unstable();
+    assert_eq!(found, (0..10).collect::<Vec<_>>());
+    assert_eq!(restored.get(&[2.0, 0.0]), Ok(Some(&String::from("other"))));
+}
+
+#[test]
+fn rejects_bad_magic() {
+    let mut bytes = encode(&basic_tree());
+    bytes[0] = b'X';
+    assert!(matches!(decode(&bytes), Err(FormatError::BadMagic)));
+    assert!(matches!(decode(b"{}"), Err(FormatError::BadMagic)));
+}
+
+#[test]
+fn rejects_other_versions() {
+    let mut bytes = encode(&basic_tree());
+    bytes[8..12].copy_from_slice(&(VERSION + 1).to_le_bytes());
+    assert!(matches!(
+        decode(&bytes),
+        Err(FormatError::UnsupportedVersion { found, expected }) if found == VERSION + 1 && expected == VERSION
+    ));
+}
+
+#[test]
+fn rejects_scalar_mismatch() {
+    let bytes = encode(&basic_tree());
+    let result: Result<KdTree<f32, usize, [f32; 2]>, _> = KdTree::read_from(&bytes[..]);
+    assert!(matches!(
+        result,
+        Err(FormatError::ScalarMismatch { found: 2, expected: 1 })
+    ));
+}
+
+#[test]
+fn rejects_truncated_files() {
+    let bytes = encode(&basic_tree());
+    for len in [10, 63, 64, bytes.len() - 9] {
+        assert!(
+            matches!(decode(&bytes[..len]), Err(FormatError::Corrupted(_))),
+            "len {}",
+            len
+        );
+    }
+}
+
+#[test]
+fn rejects_corrupted_structure() {
+    let bytes = encode(&basic_tree());
+    // root split dimension out of range
+    let mut corrupted = bytes.clone();
+    corrupted[68..72].copy_from_slice(&7u32.to_le_bytes());
+    assert!(matches!(decode(&corrupted), Err(FormatError::Corrupted(_))));
+
+    // root size no longer matches its children
+    let mut corrupted = bytes.clone();
+    corrupted[88..96].copy_from_slice(&5u64.to_le_bytes());
+    assert!(matches!(decode(&corrupted), Err(FormatError::Corrupted(_))));
+
+    // root points at itself
+    let mut corrupted = bytes.clone();
+    corrupted[72..80].copy_from_slice(&0u64.to_le_bytes());
+    assert!(matches!(decode(&corrupted), Err(FormatError::Corrupted(_))));
+}
+
+#[test]
+fn rejects_corrupted_coordinates_and_payloads() {
+    let bytes = encode(&basic_tree());
+    // four 8 byte payloads, preceded by five payload offsets and the point coordinates
+    let payloads = bytes.len() - 32;
+    let offsets = payloads - 40;
+
+    let mut corrupted = bytes.clone();
+    corrupted[offsets - 8..offsets].copy_from_slice(&f64::NAN.to_le_bytes());
+    assert!(matches!(decode(&corrupted), Err(FormatError::Corrupted(_))));
+
+    let mut corrupted = bytes.clone();
+    corrupted[offsets + 8..offsets + 16].copy_from_slice(&4u64.to_le_bytes());
+    assert!(matches!(
+        decode(&corrupted),
+        Err(FormatError::Corrupted("malformed payload"))
+    ));
+
+    let mut corrupted = bytes.clone();
+    corrupted[offsets + 8..offsets + 16].copy_from_slice(&40u64.to_le_bytes());
+    assert!(matches!(decode(&corrupted), Err(FormatError::Corrupted(_))));
+}
+
+/// A file of `depth` stems, each with an empty left leaf, above a single entry at the origin
+fn chain(depth: usize) -> Vec<u8> {
+    let node_count = 2 * depth + 1;
+    let mut bytes = b"KDTREE\0\0".to_vec();
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&[2, 0, 0, 0]);
+    for field in [1, 1, node_count, 1, 1, 8] {
+        bytes.extend_from_slice(&(field as u64).to_le_bytes());
+    }
+    for index in 0..node_count {
+        let (flags, left, right, size) = match index {
+            _ if index + 1 == node_count => (1u32, 0, 0, 1u64),
+            _ if index % 2 == 1 => (1, 0, 0, 0),
+            _ => (0, index + 1, index + 2, 1),
+        };
+        bytes.extend_from_slice(&flags.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        let len = if flags == 1 { size } else { 0 };
+        for value in [left as u64, right as u64, size, 0, 0, len] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    // split value, min and max bound of every node, then the point, offsets and payload
+    let scalars = std::iter::repeat_n(0.0f64, 3 * node_count + 1);
+    bytes.extend(scalars.flat_map(f64::to_le_bytes));
+    for value in [0u64, 8, 7] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
+
+#[test]
+fn rejects_trees_deeper_than_splits_reach() {
+    let tree: KdTree<f64, usize, [f64; 1]> = KdTree::read_from(&chain(3)[..]).unwrap();
+    assert_eq!(tree.nearest(&[1.0], 1, &squared_euclidean).unwrap(), vec![(1.0, &7)]);
+
+    // a chain this deep would overflow the stack on the first `add` or `clone`
+    let result: Result<KdTree<f64, usize, [f64; 1]>, _> = KdTree::read_from(&chain(200_000)[..]);
+    assert!(matches!(
+        result,
+        Err(FormatError::Corrupted("stems are nested deeper than splits reach"))
+    ));
+}
@@ -0,0 +1,106 @@
+mod __util__;
+
+use __util__::{POINT_A, POINT_B, POINT_C, POINT_D, basic_tree};
+use kdtree::kdtree::Entry;
+use kdtree::{ErrorKind, KdTree};
+
+#[test]
+fn get_finds_exact_points_only() {
+    let tree = basic_tree();
+    assert_eq!(tree.get(&POINT_A.0), Ok(Some(&0)));
+    assert_eq!(tree.get(&POINT_D.0), Ok(Some(&3)));
+    assert_eq!(tree.get(&[0.5, 0.5]), Ok(None));
+    assert_eq!(tree.contains_point(&POINT_C.0), Ok(true));
+    assert_eq!(tree.contains_point(&[2.0, 2.5]), Ok(false));
+    assert_eq!(tree.get(&[0.0]), Err(ErrorKind::WrongDimension));
+}
+
+#[test]
+fn get_mut_updates_in_place() {
+    let mut tree = basic_tree();
+    *tree.get_mut(&POINT_B.0).unwrap().unwrap() = 10;
+    assert_eq!(tree.get(&POINT_B.0), Ok(Some(&10)));
+    assert_eq!(tree.get_mut(&[9.0, 9.0]), Ok(None));
+}
+
+#[test]
+fn insert_or_replace_keeps_one_entry_per_point() {
+    let mut tree = basic_tree();
+    assert_eq!(tree.insert_or_replace(POINT_C.0, 20), Ok(Some(2)));
+    assert_eq!(tree.size(), 4);
+    assert_eq!(tree.get(&POINT_C.0), Ok(Some(&20)));
+
+    assert_eq!(tree.insert_or_replace([5.0, 5.0], 5), Ok(None));
+    assert_eq!(tree.size(), 5);
+    assert_eq!(tree.get(&[5.0, 5.0]), Ok(Some(&5)));
+}
+
+#[test]
+fn entry_inserts_and_modifies() {
+    let mut tree: KdTree<f64, usize, [f64; 2]> = KdTree::with_capacity(2, 2);
+    for i in 0..50 {
+        let point = [(i % 7) as f64, (i % 5) as f64];
+        *tree.entry(point).unwrap().or_insert(0) += 1;
+    }
+    assert_eq!(tree.size(), 35);
+    assert_eq!(tree.get(&[0.0, 0.0]), Ok(Some(&2)));
+    assert_eq!(tree.get(&[6.0, 4.0]), Ok(Some(&1)));
+
+    tree.entry([6.0, 4.0]).unwrap().and_modify(|v| *v = 100).or_default();
+    assert_eq!(tree.get(&[6.0, 4.0]), Ok(Some(&100)));
+    assert_eq!(*tree.entry([9.0, 9.0]).unwrap().or_default(), 0);
+    assert_eq!(tree.size(), 36);
+
+    match tree.entry([9.0, 9.0]).unwrap() {
+        Entry::Occupied(mut entry) => assert_eq!(entry.insert(7), 0),
+        Entry::Vacant(_) => panic!("expected an occupied entry"),
+    }
+    match tree.entry([9.0, 8.0]).unwrap() {
+        Entry::Occupied(_) => panic!("expected a vacant entry"),
+        Entry::Vacant(entry) => assert_eq!(entry.point(), &[9.0, 8.0]),
+    }
+    assert_eq!(tree.get(&[9.0, 9.0]), Ok(Some(&7)));
+}
+
+#[test]
+fn vacant_insert_returns_the_new_entry() {
+    let mut tree: KdTree<f64, usize, [f64; 2]> = KdTree::with_capacity(2, 2);
+    // a coincident leaf at the origin is split by the first distinct point inserted next to it
+    for _ in 0..5 {
+        tree.add([0.0, 0.0], 0).unwrap();
+    }
+    for i in 1..200 {
+        let point = [(i % 13) as f64, (i / 13) as f64];
+        match tree.entry(point).unwrap() {
+            Entry::Vacant(entry) => *entry.insert(i) += 1000,
+            Entry::Occupied(_) => panic!("expected a vacant entry"),
+        }
+    }
+    assert_eq!(tree.size(), 204);
+    for i in 1..200 {
+        assert_eq!(tree.get(&[(i % 13) as f64, (i / 13) as f64]), Ok(Some(&(i + 1000))));
+    }
+}
+
+#[test]
+fn entry_rejects_invalid_points() {
+    let mut tree: KdTree<f64, usize, [f64; 2]> = KdTree::with_capacity(2, 0);
+    assert!(matches!(tree.entry([0.0, 0.0]), Err(ErrorKind::ZeroCapacity)));
+    let mut tree: KdTree<f64, usize, Vec<f64>> = KdTree::new(2);
+    assert!(matches!(tree.entry(vec![0.0]), Err(ErrorKind::WrongDimension)));
+}
+
+#[test]
+fn get_follows_splits_between_adjacent_values() {
+    let low = 1.0f64;
+    let high = f64::from_bits(low.to_bits() + 1);
+    let mut tree = KdTree::with_capacity(1, 1);
+    tree.add([low], 0).unwrap();
+    tree.add([high], 1).unwrap();
+    tree.add([0.0], 2).unwrap();
+    tree.add([2.0], 3).unwrap();
+
+    assert_eq!(tree.get(&[low]), Ok(Some(&0)));
+    assert_eq!(tree.get(&[high]), Ok(Some(&1)));
+    assert_eq!(tree.bounding_box(&[low], &[low]).unwrap(), vec![&0]);
+}
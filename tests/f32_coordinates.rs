@@ -0,0 +1,75 @@
+use kdtree::KdTree;
+use kdtree::distance::{manhattan_f64, squared_euclidean, squared_euclidean_f64};
+use kdtree::view::KdTreeView;
+use rand::seq::SliceRandom;
+
+/// Points one f32 step apart along the first axis, far enough from the origin
+/// that their squared distances to it collide in f32
+fn dense_tree() -> KdTree<f32, u32, [f32; 3]> {
+    let mut steps = (0..32).collect::<Vec<u32>>();
+    steps.shuffle(&mut rand::rng());
+    let mut tree = KdTree::with_capacity(3, 4);
+    for step in steps {
+        tree.add([f32::from_bits(3000f32.to_bits() + step), 3000.0, 3000.0], step)
+            .unwrap();
+    }
+    tree
+}
+
+#[test]
+fn f64_distances_rank_dense_f32_points() {
+    let tree = dense_tree();
+    let origin = [0.0f32; 3];
+
+    let narrow = tree.nearest(&origin, 32, &squared_euclidean).unwrap();
+    assert!(narrow.windows(2).any(|w| w[0].0 == w[1].0));
+
+    let wide = tree.nearest(&origin, 32, &squared_euclidean_f64).unwrap();
+    assert!(wide.windows(2).all(|w| w[0].0 < w[1].0));
+    assert_eq!(
+        wide.iter().map(|&(_, &i)| i).collect::<Vec<_>>(),
+        (0..32).collect::<Vec<_>>()
+    );
+
+    let iterated = tree
+        .iter_nearest(&origin, &squared_euclidean_f64)
+        .unwrap()
+        .map(|(_, &i)| i)
+        .collect::<Vec<_>>();
+    assert_eq!(iterated, (0..32).collect::<Vec<_>>());
+
+    let ranked = tree.nearest(&origin, 1, &manhattan_f64).unwrap();
+    assert_eq!(ranked, vec![(9000.0f64, &0)]);
+}
+
+#[test]
+fn f64_radius_applies_to_f32_trees() {
+    let tree = dense_tree();
+    let origin = [0.0f32; 3];
+    let fifth = tree.nearest(&origin, 5, &squared_euclidean_f64).unwrap()[4].0;
+    assert_eq!(tree.within_count(&origin, fifth, &squared_euclidean_f64).unwrap(), 5);
+    assert_eq!(
+        tree.nearest_within_radius(&origin, 32, Some(fifth), &squared_euclidean_f64)
+            .unwrap()
+            .len(),
+        5
+    );
+}
+
+#[test]
+fn view_of_f32_tree_returns_f64_distances() {
+    let tree = dense_tree();
+    let mut bytes = Vec::new();
+    tree.write_to(&mut bytes).unwrap();
+    let mut buffer = vec![0u8; bytes.len() + 8];
+    let start = buffer.as_ptr().align_offset(8);
+    buffer[start..start + bytes.len()].copy_from_slice(&bytes);
+    let view: KdTreeView<f32, u32> = KdTreeView::open(&buffer[start..start + bytes.len()]).unwrap();
+
+    let origin = [0.0f32; 3];
+    let found = view.nearest(&origin, 32, &squared_euclidean_f64).unwrap();
+    assert_eq!(
+        found.iter().map(|&(_, i)| i).collect::<Vec<_>>(),
+        (0..32).collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,119 @@
+use kdtree::KdTree;
+use kdtree::distance::{manhattan, squared_euclidean};
+use kdtree::view::KdTreeView;
+use rand::Rng;
+
+fn brute_force<A: kdtree::Coordinate>(points: &[[A; 2]], query: &[A; 2], k: usize) -> Vec<(A::Distance, usize)> {
+    let mut distances = points
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (squared_euclidean(p, query), i))
+        .collect::<Vec<_>>();
+    distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    distances.truncate(k);
+    distances
+}
+
+#[test]
+fn i32_tree_matches_brute_force() {
+    let mut rng = rand::rng();
+    let points = (0..1000)
+        .map(|_| [rng.random_range(-50..50), rng.random_range(-50..50)])
+        .collect::<Vec<[i32; 2]>>();
+    let mut tree: KdTree<i32, usize, [i32; 2]> = KdTree::with_capacity(2, 4);
+    for (i, &p) in points.iter().enumerate() {
+        tree.add(p, i).unwrap();
+    }
+    for _ in 0..50 {
+        let query = [rng.random_range(-60..60), rng.random_range(-60..60)];
+        let found = tree.nearest(&query, 10, &squared_euclidean).unwrap();
+        let expected = brute_force(&points, &query, 10);
+        // ties may come back in any order, so only compare distances
+        assert_eq!(
+            found.iter().map(|&(d, _)| d).collect::<Vec<u64>>(),
+            expected.iter().map(|&(d, _)| d).collect::<Vec<_>>()
+        );
+        let within = tree.within(&query, 100, &squared_euclidean).unwrap();
+        assert_eq!(
+            within.len(),
+            points.iter().filter(|p| squared_euclidean(*p, &query) <= 100).count()
+        );
+    }
+}
+
+#[test]
+fn extreme_i64_coordinates_do_not_overflow() {
+    let mut tree: KdTree<i64, &str, [i64; 2]> = KdTree::with_capacity(2, 1);
+    tree.add([i64::MIN, i64::MIN], "min").unwrap();
+    tree.add([i64::MAX, i64::MAX], "max").unwrap();
+    tree.add([0, 0], "origin").unwrap();
+    tree.add([i64::MAX - 1, i64::MAX], "next to max").unwrap();
+
+    let nearest = tree.nearest(&[i64::MAX, i64::MAX], 2, &squared_euclidean).unwrap();
+    assert_eq!(nearest, vec![(0u128, &"max"), (1, &"next to max")]);
+    let nearest = tree.nearest(&[i64::MIN, i64::MIN], 4, &squared_euclidean).unwrap();
+    assert_eq!(nearest[0], (0, &"min"));
+    assert_eq!(nearest[1], (2 * (1u128 << 126), &"origin"));
+    // the opposite corner saturates instead of wrapping around
+    assert_eq!(nearest[3].0, u128::MAX);
+    assert_eq!(
+        tree.nearest(&[i64::MIN, i64::MIN], 1, &manhattan).unwrap(),
+        vec![(0, &"min")]
+    );
+}
+
+#[test]
+fn adjacent_integers_are_split() {
+    let mut tree: KdTree<u8, u8, [u8; 1]> = KdTree::with_capacity(1, 1);
+    for v in [u8::MAX, 0, u8::MAX - 1, 1, 2] {
+        tree.add([v], v).unwrap();
+    }
+    for v in [0, 1, 2, u8::MAX - 1, u8::MAX] {
+        assert_eq!(tree.nearest(&[v], 1, &squared_euclidean).unwrap(), vec![(0u32, &v)]);
+        assert_eq!(tree.get(&[v]).unwrap(), Some(&v));
+    }
+    assert_eq!(tree.bounding_box(&[1], &[u8::MAX - 1]).unwrap().len(), 3);
+}
+
+#[test]
+fn morton_codes_keep_full_precision() {
+    // neighbouring codes above 2^53 collapse onto the same f64
+    let base = (1u64 << 60) + 1;
+    let mut tree: KdTree<u64, u64, [u64; 1]> = KdTree::with_capacity(1, 2);
+    for i in 0..16 {
+        tree.add([base + i], i).unwrap();
+    }
+    let nearest = tree.nearest(&[base + 7], 3, &squared_euclidean).unwrap();
+    assert_eq!(nearest[0], (0u128, &7));
+    assert_eq!(nearest[1].0, 1);
+    assert_eq!(nearest[2].0, 1);
+}
+
+#[test]
+fn integer_trees_round_trip_through_the_binary_format() {
+    let mut tree: KdTree<i64, u32, [i64; 2]> = KdTree::with_capacity(2, 2);
+    for i in 0..20 {
+        tree.add([i64::from(i) << 40, -i64::from(i)], i as u32).unwrap();
+    }
+    let mut bytes = Vec::new();
+    tree.write_to(&mut bytes).unwrap();
+    let restored: KdTree<i64, u32, Vec<i64>> = KdTree::read_from(&bytes[..]).unwrap();
+    let query = [3 << 40, 0];
+    assert_eq!(
+        restored.nearest(&query, 5, &squared_euclidean).unwrap(),
+        tree.nearest(&query, 5, &squared_euclidean).unwrap()
+    );
+
+    let mut buffer = vec![0u8; bytes.len() + 8];
+    let start = buffer.as_ptr().align_offset(8);
+    buffer[start..start + bytes.len()].copy_from_slice(&bytes);
+    let view: KdTreeView<i64, u32> = KdTreeView::open(&buffer[start..start + bytes.len()]).unwrap();
+    assert_eq!(
+        view.nearest(&query, 5, &squared_euclidean).unwrap(),
+        tree.nearest(&query, 5, &squared_euclidean)
+            .unwrap()
+            .into_iter()
+            .map(|(d, &i)| (d, i))
+            .collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,86 @@
+use kdtree::ErrorKind;
+use kdtree::kernels::{Columns, Kernel};
+use rand::Rng;
+
+const KERNELS: [Kernel; 3] = [Kernel::SquaredEuclidean, Kernel::Manhattan, Kernel::Dot];
+
+#[test]
+fn kernels_match_the_scalar_metric() {
+    let mut rng = rand::rng();
+    let mut out = Vec::new();
+    // lengths around the vector width exercise both the full chunks and the remainder
+    for len in [0, 1, 7, 8, 9, 16, 37] {
+        let points = (0..len)
+            .map(|_| [0; 3].map(|_| rng.random_range(-10.0..10.0)))
+            .collect::<Vec<[f32; 3]>>();
+        let mut block: Columns = Columns::with_capacity(3, len);
+        for p in points.iter() {
+            block.push(p).unwrap();
+        }
+        assert_eq!(block.len(), len);
+        let query = [0; 3].map(|_| rng.random_range(-10.0..10.0));
+        for kernel in KERNELS {
+            kernel.evaluate(&block, &query, &mut out).unwrap();
+            let expected = points.iter().map(|p| kernel.scalar(p, &query)).collect::<Vec<_>>();
+            assert_eq!(out, expected, "{:?}", kernel);
+        }
+    }
+}
+
+#[test]
+fn columns_store_each_dimension_contiguously() {
+    let mut block: Columns = Columns::new(2);
+    block.push(&[1.0, 2.0]).unwrap();
+    block.push(&[3.0, 4.0]).unwrap();
+    assert_eq!(block.column(0), &[1.0, 3.0]);
+    assert_eq!(block.column(1), &[2.0, 4.0]);
+    assert_eq!(block.point(1), vec![3.0, 4.0]);
+    block.clear();
+    assert!(block.is_empty());
+    assert_eq!(block.column(0), &[] as &[f32]);
+}
+
+#[test]
+fn rejects_invalid_points() {
+    let mut block: Columns = Columns::new(2);
+    assert_eq!(block.push(&[1.0]), Err(ErrorKind::WrongDimension));
+    assert!(matches!(
+        block.push(&[1.0, f32::NAN]),
+        Err(ErrorKind::NonFiniteCoordinate)
+    ));
+    assert!(block.is_empty());
+    assert_eq!(
+        Kernel::Dot.evaluate(&block, &[1.0, 2.0, 3.0], &mut Vec::new()),
+        Err(ErrorKind::WrongDimension)
+    );
+}
+
+#[test]
+fn half_precision_columns() {
+    use half::f16;
+
+    let mut rng = rand::rng();
+    let mut block: Columns<f16> = Columns::new(4);
+    let mut stored = Vec::new();
+    for _ in 0..21 {
+        let p = [0; 4].map(|_| rng.random_range(-100.0..100.0f32));
+        block.push(&p).unwrap();
+        stored.push(p.map(|v| f16::from_f32(v).to_f32()));
+    }
+    assert_eq!(block.point(3), stored[3].to_vec());
+
+    let query = [1.5, -2.0, 0.25, 8.0];
+    let mut out = Vec::new();
+    for kernel in KERNELS {
+        kernel.evaluate(&block, &query, &mut out).unwrap();
+        let expected = stored.iter().map(|p| kernel.scalar(p, &query)).collect::<Vec<_>>();
+        assert_eq!(out, expected, "{:?}", kernel);
+    }
+
+    // values beyond the f16 range would be stored as infinity
+    assert!(matches!(
+        block.push(&[0.0, 1e6, 0.0, 0.0]),
+        Err(ErrorKind::NonFiniteCoordinate)
+    ));
+    assert_eq!(block.len(), 21);
+}
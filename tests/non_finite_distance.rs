@@ -0,0 +1,88 @@
+mod __util__;
+
+use __util__::{POINT_A, basic_tree};
+use kdtree::distance::squared_euclidean;
+use kdtree::{ErrorKind, KdTree};
+use proptest::prelude::*;
+
+fn nan_metric(_: &[f64], _: &[f64]) -> f64 {
+    f64::NAN
+}
+
+fn poisoned(target: [f64; 2]) -> impl Fn(&[f64], &[f64]) -> f64 {
+    move |a, b| {
+        if b == target { f64::NAN } else { squared_euclidean(a, b) }
+    }
+}
+
+#[test]
+fn nan_metric_is_reported() {
+    let tree = basic_tree();
+    let error = ErrorKind::NonFiniteDistance;
+    assert_eq!(tree.nearest(&POINT_A.0, 1, &nan_metric), Err(error));
+    assert_eq!(
+        tree.nearest_within_radius(&POINT_A.0, 2, Some(1.0), &nan_metric),
+        Err(error)
+    );
+    assert_eq!(tree.within(&POINT_A.0, 1.0, &nan_metric), Err(error));
+    assert_eq!(tree.within_count(&POINT_A.0, 1.0, &nan_metric), Err(error));
+    assert_eq!(
+        tree.nearest(&POINT_A.0, 1, &|_: &[f64], _: &[f64]| f64::INFINITY),
+        Err(error)
+    );
+}
+
+#[test]
+fn iterators_stop_on_nan_metric() {
+    let mut tree = basic_tree();
+    let mut iter = tree.iter_nearest(&POINT_A.0, &nan_metric).unwrap();
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.error(), Some(ErrorKind::NonFiniteDistance));
+    assert_eq!(iter.next(), None);
+
+    let mut iter = tree.iter_nearest_mut(&POINT_A.0, &nan_metric).unwrap();
+    assert!(iter.next().is_none());
+    assert_eq!(iter.error(), Some(ErrorKind::NonFiniteDistance));
+
+    let mut iter = tree.iter_nearest(&POINT_A.0, &squared_euclidean).unwrap();
+    assert!(iter.next().is_some());
+    assert_eq!(iter.error(), None);
+}
+
+proptest! {
+    #[test]
+    fn nan_metric_errors_instead_of_wrong_answers(
+        coords in prop::collection::vec((-100.0..100.0f64, -100.0..100.0f64), 1..200),
+        poison in any::<prop::sample::Index>(),
+        query in (-100.0..100.0f64, -100.0..100.0f64),
+        k in 1usize..20,
+        radius in 0.0..2000.0f64,
+    ) {
+        let points: Vec<[f64; 2]> = coords.into_iter().map(|(x, y)| [x, y]).collect();
+        let target = points[poison.index(points.len())];
+        let query = [query.0, query.1];
+        let metric = poisoned(target);
+        let mut tree = KdTree::with_capacity(2, 4);
+        for (i, p) in points.iter().enumerate() {
+            tree.add(*p, i).unwrap();
+        }
+        let mut expected: Vec<f64> = points
+            .iter()
+            .filter(|p| **p != target)
+            .map(|p| squared_euclidean(&query, p))
+            .collect();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        match tree.nearest(&query, k, &metric) {
+            Err(error) => prop_assert_eq!(error, ErrorKind::NonFiniteDistance),
+            Ok(found) => {
+                let found: Vec<f64> = found.into_iter().map(|(d, _)| d).collect();
+                prop_assert_eq!(&found[..], &expected[..k.min(expected.len())]);
+            }
+        }
+        match tree.within_count(&query, radius, &metric) {
+            Err(error) => prop_assert_eq!(error, ErrorKind::NonFiniteDistance),
+            Ok(count) => prop_assert_eq!(count, expected.iter().filter(|&&d| d <= radius).count()),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use kdtree::distance::{manhattan, squared_euclidean};
+use kdtree::kernels::Kernel;
+use kdtree::{ErrorKind, KdTree};
+use rand::Rng;
+
+type Tree = KdTree<f64, usize, Vec<f64>>;
+
+fn random_point(rng: &mut impl Rng) -> Vec<f64> {
+    // a coarse grid produces duplicate points and coincident leaves
+    (0..3).map(|_| f64::from(rng.random_range(0..8u8))).collect()
+}
+
+fn filled(mut tree: Tree, points: &[Vec<f64>]) -> Tree {
+    for (i, p) in points.iter().enumerate() {
+        tree.add(p.clone(), i).unwrap();
+    }
+    tree
+}
+
+fn assert_same_answers(packed: &Tree, unpacked: &Tree, rng: &mut impl Rng) {
+    for _ in 0..20 {
+        let query = random_point(rng);
+        let distances = |found: Vec<(f64, &usize)>| found.into_iter().map(|(d, _)| d).collect::<Vec<_>>();
+        assert_eq!(
+            distances(packed.nearest(&query, 7, &squared_euclidean).unwrap()),
+            distances(unpacked.nearest(&query, 7, &squared_euclidean).unwrap())
+        );
+        let mut within = packed.within(&query, 4.0, &squared_euclidean).unwrap();
+        let mut expected = unpacked.within(&query, 4.0, &squared_euclidean).unwrap();
+        within.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(within, expected);
+
+        let max = query.iter().map(|v| v + 2.0).collect::<Vec<_>>();
+        let mut boxed = packed.bounding_box(&query, &max).unwrap();
+        let mut expected = unpacked.bounding_box(&query, &max).unwrap();
+        boxed.sort();
+        expected.sort();
+        assert_eq!(boxed, expected);
+
+        let iterated = packed.iter_nearest(&query, &squared_euclidean).unwrap().count();
+        assert_eq!(iterated, packed.size());
+        assert_eq!(packed.contains_point(&query), unpacked.contains_point(&query));
+    }
+}
+
+#[test]
+fn packed_leaves_answer_like_unpacked_ones() {
+    let mut rng = rand::rng();
+    let points = (0..300).map(|_| random_point(&mut rng)).collect::<Vec<_>>();
+    let mut packed = filled(Tree::with_packed_leaves(3, 4), &points);
+    let mut unpacked = filled(Tree::with_capacity(3, 4), &points);
+    assert_same_answers(&packed, &unpacked, &mut rng);
+
+    for (i, p) in points.iter().enumerate().step_by(3) {
+        let moved = random_point(&mut rng);
+        assert!(packed.update_position(p, moved.clone(), |&d| d == i).unwrap());
+        assert!(unpacked.update_position(p, moved, |&d| d == i).unwrap());
+    }
+    for (i, p) in points.iter().enumerate().skip(1).step_by(5) {
+        assert_eq!(packed.remove(p, &i).unwrap(), unpacked.remove(p, &i).unwrap());
+    }
+    assert_eq!(packed.size(), unpacked.size());
+    assert_same_answers(&packed, &unpacked, &mut rng);
+}
+
+#[test]
+fn existing_trees_can_be_packed() {
+    let mut rng = rand::rng();
+    let points = (0..100).map(|_| random_point(&mut rng)).collect::<Vec<_>>();
+    let unpacked = filled(Tree::with_capacity(3, 4), &points);
+    let mut packed = unpacked.clone();
+    packed.pack_leaves();
+    assert_same_answers(&packed, &unpacked, &mut rng);
+
+    packed.add(vec![0.5, 0.5, 0.5], 100).unwrap();
+    assert_eq!(
+        packed.nearest(&[0.5, 0.5, 0.5], 1, &squared_euclidean).unwrap(),
+        vec![(0.0, &100)]
+    );
+}
+
+type Metric = fn(&[f32], &[f32]) -> f32;
+
+#[test]
+fn kernels_measure_leaves_like_the_metrics() {
+    let mut rng = rand::rng();
+    let mut packed: KdTree<f32, usize, [f32; 3]> = KdTree::with_packed_leaves(3, 8);
+    let mut unpacked: KdTree<f32, usize, [f32; 3]> = KdTree::with_capacity(3, 8);
+    for i in 0..400 {
+        // every fourth point lands on a coarse grid to produce coincident leaves
+        let point = if i % 4 == 0 {
+            [0; 3].map(|_| f32::from(rng.random_range(0..4u8)))
+        } else {
+            [0; 3].map(|_| rng.random_range(0.0..4.0f32))
+        };
+        packed.add(point, i).unwrap();
+        unpacked.add(point, i).unwrap();
+    }
+    let metrics: [(Kernel, Metric); 2] = [
+        (Kernel::SquaredEuclidean, squared_euclidean),
+        (Kernel::Manhattan, manhattan),
+    ];
+    for _ in 0..20 {
+        let query = [0; 3].map(|_| rng.random_range(-1.0..5.0f32));
+        for (kernel, metric) in metrics {
+            for tree in [&packed, &unpacked] {
+                assert_eq!(
+                    tree.nearest_kernel(&query, 9, kernel).unwrap(),
+                    tree.nearest(&query, 9, &metric).unwrap()
+                );
+                let mut within = tree.within_kernel(&query, 1.5, kernel).unwrap();
+                let mut expected = tree.within(&query, 1.5, &metric).unwrap();
+                within.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                assert_eq!(within, expected);
+                assert_eq!(tree.within_count_kernel(&query, 1.5, kernel).unwrap(), expected.len());
+            }
+        }
+    }
+    assert_eq!(
+        packed.nearest_kernel(&[0.0; 3], 1, Kernel::Dot),
+        Err(ErrorKind::NotADistance)
+    );
+    assert_eq!(
+        packed.within_kernel(&[0.0; 2], 1.0, Kernel::Manhattan),
+        Err(ErrorKind::WrongDimension)
+    );
+}
+
+#[cfg(feature = "serialize")]
+#[test]
+fn serde_keeps_leaves_packed() {
+    let mut rng = rand::rng();
+    let points = (0..50).map(|_| random_point(&mut rng)).collect::<Vec<_>>();
+    let packed = filled(Tree::with_packed_leaves(3, 4), &points);
+    let json = serde_json::to_string(&packed).unwrap();
+    let restored: Tree = serde_json::from_str(&json).unwrap();
+    assert_same_answers(&restored, &packed, &mut rng);
+
+    let mut restored = restored;
+    restored.add(vec![0.5, 0.5, 0.5], 50).unwrap();
+    assert_eq!(
+        restored.nearest(&[0.5, 0.5, 0.5], 1, &squared_euclidean).unwrap(),
+        vec![(0.0, &50)]
+    );
+}
@@ -0,0 +1,212 @@
+#[cfg(all(test, feature = "serialize"))]
+mod portable_tests {
+    use kdtree::KdTree;
+    use kdtree::distance::squared_euclidean;
+    use kdtree::portable;
+    use serde_json::{Value, json};
+
+    type Tree = KdTree<f64, u32, [f64; 2]>;
+
+    fn sample_tree() -> Tree {
+        let mut kdtree = Tree::with_capacity(2, 3);
+        for i in 0..100u32 {
+            kdtree.add([(i % 13) as f64, (i % 7) as f64 * 0.5], i).unwrap();
+        }
+        for i in 0..10 {
+            kdtree.add([4.0, 1.0], 1000 + i).unwrap();
+        }
+        kdtree
+    }
+
+    fn flat(tree: &Tree) -> Value {
+        portable::serialize(tree, serde_json::value::Serializer).unwrap()
+    }
+
+    fn with_structure(tree: &Tree) -> Value {
+        portable::with_structure::serialize(tree, serde_json::value::Serializer).unwrap()
+    }
+
+    fn load(value: Value) -> Result<Tree, serde_json::Error> {
+        portable::deserialize(value)
+    }
+
+    fn load_with_structure(value: Value) -> Result<Tree, serde_json::Error> {
+        portable::with_structure::deserialize(value)
+    }
+
+    fn sorted_entries(tree: &Tree) -> Vec<u32> {
+        let mut entries = tree
+            .bounding_box(&[-100.0, -100.0], &[100.0, 100.0])
+            .unwrap()
+            .into_iter()
+            .copied()
+            .collect::<Vec<_>>();
+        entries.sort_unstable();
+        entries
+    }
+
+    #[test]
+    fn flat_form_lists_entries_only() {
+        let mut kdtree = Tree::with_capacity(2, 4);
+        kdtree.add([1.0, 2.0], 7).unwrap();
+        kdtree.add([3.0, 4.0], 8).unwrap();
+        assert_eq!(
+            flat(&kdtree),
+            json!({"dimensions": 2, "capacity": 4, "entries": [[[1.0, 2.0], 7], [[3.0, 4.0], 8]]})
+        );
+    }
+
+    #[test]
+    fn flat_form_rebuilds_the_tree() {
+        let kdtree = sample_tree();
+        let value = flat(&kdtree);
+        assert_eq!(value["entries"].as_array().unwrap().len(), kdtree.size());
+        assert!(value.get("structure").is_none());
+
+        let restored = load(value).unwrap();
+        assert_eq!(restored.size(), kdtree.size());
+        assert_eq!(sorted_entries(&restored), sorted_entries(&kdtree));
+        let query = [4.2, 1.1];
+        let distances = |tree: &Tree| {
+            tree.nearest(&query, 20, &squared_euclidean)
+                .unwrap()
+                .into_iter()
+                .map(|(d, _)| d)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(distances(&restored), distances(&kdtree));
+    }
+
+    #[test]
+    fn structure_is_restored_without_rebuilding() {
+        let kdtree = sample_tree();
+        let value = with_structure(&kdtree);
+        assert!(value["structure"].as_array().unwrap().len() > 1);
+
+        let restored = load_with_structure(value.clone()).unwrap();
+        assert_eq!(
+            serde_json::to_value(&restored).unwrap(),
+            serde_json::to_value(&kdtree).unwrap()
+        );
+        // the plain form ignores the structure and rebuilds
+        let restored = load(value).unwrap();
+        assert_eq!(sorted_entries(&restored), sorted_entries(&kdtree));
+        assert_eq!(
+            sorted_entries(&load_with_structure(flat(&kdtree)).unwrap()),
+            sorted_entries(&kdtree)
+        );
+    }
+
+    #[test]
+    fn mismatched_structure_falls_back_to_rebuilding() {
+        let kdtree = sample_tree();
+        let mut value = with_structure(&kdtree);
+        value["structure"][0]["stem"]["split_value"] = json!(100.0);
+        let restored = load_with_structure(value).unwrap();
+        assert_eq!(restored.size(), kdtree.size());
+        assert_eq!(sorted_entries(&restored), sorted_entries(&kdtree));
+
+        let mut value = with_structure(&kdtree);
+        value["structure"].as_array_mut().unwrap().pop();
+        assert_eq!(
+            sorted_entries(&load_with_structure(value).unwrap()),
+            sorted_entries(&kdtree)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_entries() {
+        let value = json!({"dimensions": 2, "capacity": 4, "entries": [[[1.0, 2.0, 3.0], 7]]});
+        assert!(load(value).unwrap_err().to_string().contains("wrong dimension"));
+
+        let value = json!({"dimensions": 2, "capacity": 0, "entries": []});
+        assert!(load(value).unwrap_err().to_string().contains("capacity"));
+    }
+
+    /// Deepest nesting of stems in a stored structure
+    fn stem_depth(value: &Value) -> usize {
+        // number of subtrees still to be read below each open stem
+        let mut open = Vec::new();
+        let mut deepest = 0;
+        for node in value["structure"].as_array().unwrap() {
+            if node.get("stem").is_some() {
+                open.push(2);
+                deepest = deepest.max(open.len());
+                continue;
+            }
+            while let Some(pending) = open.last_mut() {
+                *pending -= 1;
+                if *pending > 0 {
+                    break;
+                }
+                open.pop();
+            }
+        }
+        deepest
+    }
+
+    #[test]
+    fn deep_structures_are_rebuilt_balanced() {
+        // a chain of stems, each splitting off a single point on the left
+        let depth = 100_000;
+        let mut structure = Vec::new();
+        for i in 0..depth - 1 {
+            structure.push(json!({"stem": {"split_dimension": 0, "split_value": (i + 1) as f64}}));
+            structure.push(json!({"leaf": {"len": 1}}));
+        }
+        structure.push(json!({"leaf": {"len": 1}}));
+        let entries = (0..depth).map(|i| json!([[i as f64, 0.0], i])).collect::<Vec<_>>();
+        let value = json!({"dimensions": 2, "capacity": 1, "entries": entries, "structure": structure});
+        assert_eq!(stem_depth(&value), 99_999);
+        let mut restored = load_with_structure(value).unwrap();
+        assert_eq!(restored.size(), depth);
+        // 100 000 single entry leaves fit under 17 levels of median splits
+        assert_eq!(stem_depth(&with_structure(&restored)), 17);
+        assert_eq!(restored.get(&[(depth - 1) as f64, 0.0]), Ok(Some(&(depth as u32 - 1))));
+        restored.add([0.5, 0.0], 7).unwrap();
+        assert_eq!(restored.remove(&[0.5, 0.0], &7), Ok(1));
+
+        // a chain that never ends is rejected and the entries are rebuilt instead
+        let kdtree = sample_tree();
+        let mut value = with_structure(&kdtree);
+        value["structure"] = json!(vec![json!({"stem": {"split_dimension": 0, "split_value": 1.0}}); depth]);
+        assert_eq!(
+            sorted_entries(&load_with_structure(value).unwrap()),
+            sorted_entries(&kdtree)
+        );
+    }
+
+    #[test]
+    fn bincode_round_trip() {
+        let kdtree = sample_tree();
+
+        let mut bytes = Vec::new();
+        portable::serialize(
+            &kdtree,
+            &mut bincode::Serializer::new(&mut bytes, bincode::DefaultOptions::new()),
+        )
+        .unwrap();
+        let restored: Tree = portable::deserialize(&mut bincode::Deserializer::from_slice(
+            &bytes,
+            bincode::DefaultOptions::new(),
+        ))
+        .unwrap();
+        assert_eq!(sorted_entries(&restored), sorted_entries(&kdtree));
+
+        let mut bytes = Vec::new();
+        portable::with_structure::serialize(
+            &kdtree,
+            &mut bincode::Serializer::new(&mut bytes, bincode::DefaultOptions::new()),
+        )
+        .unwrap();
+        let restored: Tree = portable::with_structure::deserialize(&mut bincode::Deserializer::from_slice(
+            &bytes,
+            bincode::DefaultOptions::new(),
+        ))
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&restored).unwrap(),
+            serde_json::to_value(&kdtree).unwrap()
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use kdtree::KdTree;
+use kdtree::{ErrorKind, KdTree};
 
 #[test]
 fn bounding_box_returns_all_points_in_range() {
@@ -28,3 +28,15 @@ fn bounding_box_returns_all_points_in_range() {
     assert!(within.contains(&String::from("65")));
     assert!(within.contains(&String::from("66")));
 }
+
+#[test]
+fn bounding_box_rejects_inverted_bounds() {
+    let mut tree = KdTree::with_capacity(2, 2);
+    tree.add([1.0, 1.0], 0).unwrap();
+
+    assert_eq!(
+        tree.bounding_box(&[0.0, 2.0], &[2.0, 0.0]),
+        Err(ErrorKind::InvalidBoundingBox { axis: 1 })
+    );
+    assert_eq!(tree.bounding_box(&[1.0, 1.0], &[1.0, 1.0]), Ok(vec![&0]));
+}
@@ -0,0 +1,85 @@
+mod __util__;
+
+use __util__::{POINT_A, POINT_B, basic_tree};
+use kdtree::distance::{manhattan, squared_euclidean};
+use kdtree::{ErrorKind, KdTree};
+use rand::Rng;
+
+fn distances<T>(found: &[(f64, T)]) -> Vec<f64> {
+    found.iter().map(|(d, _)| *d).collect()
+}
+
+#[test]
+fn farthest_queries_match_expected() {
+    let tree = basic_tree();
+    assert_eq!(tree.farthest(&POINT_A.0, 0, &squared_euclidean).unwrap(), vec![]);
+    assert_eq!(
+        tree.farthest(&POINT_A.0, 2, &squared_euclidean).unwrap(),
+        vec![(18.0, &3), (8.0, &2)]
+    );
+    let found = tree.farthest(&POINT_B.0, 10, &manhattan).unwrap();
+    assert_eq!(distances(&found), vec![4.0, 2.0, 2.0, 0.0]);
+    assert_eq!((found[0].1, found[3].1), (&3, &1));
+}
+
+#[test]
+fn farthest_matches_brute_force() {
+    let mut rng = rand::rng();
+    let points = (0..1000)
+        .map(|_| [rng.random_range(-10.0..10.0), rng.random_range(-10.0..10.0)])
+        .collect::<Vec<[f64; 2]>>();
+    let mut tree = KdTree::with_capacity(2, 4);
+    for (i, &p) in points.iter().enumerate() {
+        tree.add(p, i).unwrap();
+    }
+    for _ in 0..50 {
+        let query = [rng.random_range(-15.0..15.0), rng.random_range(-15.0..15.0)];
+        let mut expected = points.iter().map(|p| squared_euclidean(p, &query)).collect::<Vec<_>>();
+        expected.sort_by(|a, b| b.partial_cmp(a).unwrap());
+
+        let found = tree.farthest(&query, 10, &squared_euclidean).unwrap();
+        assert_eq!(distances(&found), expected[..10]);
+        let iterated = tree
+            .iter_farthest(&query, &squared_euclidean)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(distances(&iterated), expected);
+    }
+}
+
+#[test]
+fn farthest_on_integer_trees() {
+    let mut tree: KdTree<i64, &str, [i64; 1]> = KdTree::with_capacity(1, 1);
+    for (p, name) in [(i64::MIN, "min"), (0, "zero"), (i64::MAX, "max"), (5, "five")] {
+        tree.add([p], name).unwrap();
+    }
+    let found = tree.farthest(&[1], 2, &squared_euclidean).unwrap();
+    assert_eq!(found.iter().map(|&(_, &n)| n).collect::<Vec<_>>(), vec!["min", "max"]);
+    assert_eq!(
+        tree.farthest(&[5], 4, &manhattan).unwrap(),
+        vec![
+            ((1u128 << 63) + 5, &"min"),
+            ((1 << 63) - 6, &"max"),
+            (5, &"zero"),
+            (0, &"five")
+        ]
+    );
+}
+
+#[test]
+fn farthest_reports_errors() {
+    let tree = basic_tree();
+    let empty: KdTree<f64, usize, [f64; 2]> = KdTree::new(2);
+    assert_eq!(empty.farthest(&POINT_A.0, 3, &squared_euclidean).unwrap(), vec![]);
+    assert_eq!(empty.iter_farthest(&POINT_A.0, &squared_euclidean).unwrap().count(), 0);
+    assert!(matches!(
+        tree.farthest(&[0.0], 1, &squared_euclidean),
+        Err(ErrorKind::WrongDimension)
+    ));
+
+    let nan = |_: &[f64], _: &[f64]| f64::NAN;
+    assert_eq!(tree.farthest(&POINT_A.0, 1, &nan), Err(ErrorKind::NonFiniteDistance));
+    let mut iter = tree.iter_farthest(&POINT_A.0, &nan).unwrap();
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.error(), Some(ErrorKind::NonFiniteDistance));
+}
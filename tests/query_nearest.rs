@@ -2,7 +2,8 @@ mod __util__;
 
 use __util__::{POINT_A, POINT_B, POINT_C, basic_tree};
 use kdtree::distance::squared_euclidean;
-use kdtree::{ErrorKind, KdTree};
+use kdtree::{ErrorKind, KdTree, NonFinite, PointError};
+use rand::Rng;
 
 fn assert_ordered_usize(results: Vec<(f64, &usize)>, expected: &[(f64, usize)]) {
     assert_eq!(results.into_iter().map(|(d, v)| (d, *v)).collect::<Vec<_>>(), expected);
@@ -186,6 +187,34 @@ fn handles_non_finite_coordinate() {
     );
 }
 
+#[test]
+fn check_point_describes_rejected_points() {
+    let tree: KdTree<f64, usize, [f64; 2]> = KdTree::with_capacity(2, 1);
+    let wrong_dimension = tree.check_point(&[0.0]).unwrap_err();
+    assert_eq!(wrong_dimension, PointError::WrongDimension { expected: 2, actual: 1 });
+    assert_eq!(ErrorKind::from(wrong_dimension), ErrorKind::WrongDimension);
+    assert_eq!(wrong_dimension.to_string(), "wrong dimension: expected 2, got 1");
+
+    assert_eq!(
+        tree.check_point(&[f64::NAN, 0.0]),
+        Err(PointError::NonFiniteCoordinate {
+            axis: 0,
+            value: NonFinite::NaN
+        })
+    );
+    assert_eq!(
+        tree.check_point(&[f64::INFINITY, 0.0]),
+        Err(PointError::NonFiniteCoordinate {
+            axis: 0,
+            value: NonFinite::Infinity
+        })
+    );
+    let neg_infinity = tree.check_point(&[0.0, f64::NEG_INFINITY]).unwrap_err();
+    assert_eq!(ErrorKind::from(neg_infinity), ErrorKind::NonFiniteCoordinate);
+    assert_eq!(neg_infinity.to_string(), "non-finite coordinate -inf on axis 1");
+    assert_eq!(tree.check_point(&[0.0, 0.0]), Ok(()));
+}
+
 #[test]
 fn handles_singularity() {
     let mut tree = KdTree::with_capacity(2, 1);
@@ -219,3 +248,130 @@ fn handles_pending_order() {
         &[(25f64, 3), (25f64, 4)],
     );
 }
+
+#[test]
+fn nearest_filtered_only_returns_matching_items() {
+    let tree = basic_tree();
+    assert_ordered_usize(
+        tree.nearest_filtered(&POINT_A.0, 2, &squared_euclidean, |_, &i| i % 2 == 1)
+            .unwrap(),
+        &[(2f64, 1), (18f64, 3)],
+    );
+    assert_ordered_usize(
+        tree.nearest_filtered(&POINT_A.0, 4, &squared_euclidean, |p, _| p[0] >= 2.0)
+            .unwrap(),
+        &[(8f64, 2), (18f64, 3)],
+    );
+    assert_ordered_usize(
+        tree.nearest_filtered_within_radius(&POINT_A.0, 4, Some(10.0), &squared_euclidean, |_, &i| i != 0)
+            .unwrap(),
+        &[(2f64, 1), (8f64, 2)],
+    );
+    assert!(
+        tree.nearest_filtered(&POINT_A.0, 4, &squared_euclidean, |_, _| false)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn nearest_filtered_fills_k_with_selective_filters() {
+    let mut rng = rand::rng();
+    let points = (0..2000)
+        .map(|_| [rng.random_range(-10.0..10.0), rng.random_range(-10.0..10.0)])
+        .collect::<Vec<[f64; 2]>>();
+    let mut tree = KdTree::with_capacity(2, 4);
+    for (i, &p) in points.iter().enumerate() {
+        tree.add(p, i).unwrap();
+    }
+    // only one item in a hundred matches
+    let open = |i: usize| i % 100 == 7;
+    for _ in 0..20 {
+        let query = [rng.random_range(-10.0..10.0), rng.random_range(-10.0..10.0)];
+        let mut expected = (0..points.len())
+            .filter(|&i| open(i))
+            .map(|i| (squared_euclidean(&points[i], &query), i))
+            .collect::<Vec<_>>();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        expected.truncate(5);
+
+        let found = tree
+            .nearest_filtered(&query, 5, &squared_euclidean, |_, &i| open(i))
+            .unwrap();
+        assert_eq!(found.iter().map(|&(d, &i)| (d, i)).collect::<Vec<_>>(), expected);
+    }
+}
+
+#[test]
+fn nearest_to_set_matches_brute_force() {
+    let mut rng = rand::rng();
+    let points = (0..1000)
+        .map(|_| [rng.random_range(-10.0..10.0), rng.random_range(-10.0..10.0)])
+        .collect::<Vec<[f64; 2]>>();
+    let mut tree = KdTree::with_capacity(2, 4);
+    for (i, &p) in points.iter().enumerate() {
+        tree.add(p, i).unwrap();
+    }
+    for _ in 0..20 {
+        // samples along a polyline
+        let mut queries = vec![[rng.random_range(-10.0..10.0), rng.random_range(-10.0..10.0)]];
+        for _ in 0..rng.random_range(0..8) {
+            let [x, y] = *queries.last().unwrap();
+            queries.push([x + rng.random_range(-2.0..2.0), y + rng.random_range(-2.0..2.0)]);
+        }
+        let closest = |p: &[f64; 2]| {
+            let distances = queries.iter().map(|q| squared_euclidean(p, q));
+            distances
+                .enumerate()
+                .fold((f64::MAX, 0), |best, (i, d)| if d < best.0 { (d, i) } else { best })
+        };
+        let mut expected = points.iter().map(|p| closest(p).0).collect::<Vec<_>>();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        expected.truncate(10);
+
+        let found = tree.nearest_to_set(&queries, 10, &squared_euclidean).unwrap();
+        assert_eq!(found.iter().map(|&(d, _, _)| d).collect::<Vec<_>>(), expected);
+        for &(d, query, &i) in &found {
+            assert_eq!(closest(&points[i]), (d, query));
+        }
+    }
+}
+
+#[test]
+fn nearest_to_set_returns_every_entry_of_a_coincident_leaf() {
+    // five copies of one point overflow a leaf of two, which then keeps a single copy
+    let mut tree = KdTree::with_capacity(2, 2);
+    for i in 0..5 {
+        tree.add([1.0, 1.0], i).unwrap();
+    }
+    tree.add([4.0, 0.0], 5).unwrap();
+    tree.add([0.0, 4.0], 6).unwrap();
+    let found = tree
+        .nearest_to_set(&[[-1.0, -1.0], [1.0, 2.0]], 6, &squared_euclidean)
+        .unwrap();
+    let mut shared = found[..5].iter().map(|&(d, q, &i)| (d, q, i)).collect::<Vec<_>>();
+    shared.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(shared, (0..5).map(|i| (1.0, 1, i)).collect::<Vec<_>>());
+    assert_eq!(found[5], (5.0, 1, &6));
+}
+
+#[test]
+fn nearest_to_set_edge_cases() {
+    let tree = basic_tree();
+    let no_queries: [[f64; 2]; 0] = [];
+    assert_eq!(tree.nearest_to_set(&no_queries, 3, &squared_euclidean).unwrap(), vec![]);
+    assert_eq!(
+        tree.nearest_to_set(&[POINT_A.0], 2, &squared_euclidean).unwrap(),
+        vec![(0.0, 0, &0), (2.0, 0, &1)]
+    );
+    // B is as close to A as to C, so it is reported against the first of them
+    let mut found = tree
+        .nearest_to_set(&[POINT_A.0, POINT_C.0], 4, &squared_euclidean)
+        .unwrap();
+    found.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(found, vec![(0.0, 0, &0), (0.0, 1, &2), (2.0, 0, &1), (2.0, 1, &3)]);
+    assert_eq!(
+        tree.nearest_to_set(&[vec![0.0, 0.0], vec![0.0]], 1, &squared_euclidean),
+        Err(ErrorKind::WrongDimension)
+    );
+}
@@ -0,0 +1,142 @@
+use kdtree::region::{AxisBox, Ball, HalfSpace, Polygon, Region};
+use kdtree::{ErrorKind, KdTree};
+use rand::Rng;
+
+fn assert_matches_brute_force<R: Region<f64>>(tree: &KdTree<f64, usize, [f64; 2]>, points: &[[f64; 2]], region: &R) {
+    let expected = (0..points.len())
+        .filter(|&i| region.contains(&points[i]))
+        .collect::<Vec<_>>();
+    let mut found = tree
+        .query_region(region)
+        .unwrap()
+        .into_iter()
+        .copied()
+        .collect::<Vec<_>>();
+    found.sort();
+    assert_eq!(found, expected);
+    let mut iterated = tree.iter_region(region).unwrap().copied().collect::<Vec<_>>();
+    iterated.sort();
+    assert_eq!(iterated, expected);
+    assert_eq!(tree.count_region(region).unwrap(), expected.len());
+}
+
+#[test]
+fn built_in_regions_match_brute_force() {
+    let mut rng = rand::rng();
+    let points = (0..2000)
+        .map(|_| [rng.random_range(-10.0..10.0), rng.random_range(-10.0..10.0)])
+        .collect::<Vec<[f64; 2]>>();
+    let mut tree = KdTree::with_capacity(2, 4);
+    for (i, &p) in points.iter().enumerate() {
+        tree.add(p, i).unwrap();
+    }
+    for _ in 0..20 {
+        let center = [rng.random_range(-10.0..10.0), rng.random_range(-10.0..10.0)];
+        let size = rng.random_range(0.0..8.0);
+        let min = vec![center[0] - size, center[1] - size / 2.0];
+        let max = vec![center[0] + size, center[1] + size];
+        assert_matches_brute_force(&tree, &points, &AxisBox::new(min, max).unwrap());
+        assert_matches_brute_force(&tree, &points, &Ball::new(center.to_vec(), size));
+        let normal = vec![rng.random_range(-1.0..1.0), rng.random_range(-1.0..1.0)];
+        assert_matches_brute_force(&tree, &points, &HalfSpace::new(normal, size - 4.0));
+        // a concave arrow shape
+        let [x, y] = center;
+        let polygon = Polygon::new([
+            [x - size, y - size],
+            [x, y - size / 4.0],
+            [x + size, y - size],
+            [x, y + size],
+        ]);
+        assert_matches_brute_force(&tree, &points, &polygon);
+    }
+}
+
+/// Points within `radius` of the segment between `a` and `b`
+struct Capsule {
+    a: [f64; 2],
+    b: [f64; 2],
+    radius: f64,
+}
+
+impl Region<f64> for Capsule {
+    fn contains(&self, p: &[f64]) -> bool {
+        let ab = [self.b[0] - self.a[0], self.b[1] - self.a[1]];
+        let ap = [p[0] - self.a[0], p[1] - self.a[1]];
+        let t = ((ap[0] * ab[0] + ap[1] * ab[1]) / (ab[0] * ab[0] + ab[1] * ab[1])).clamp(0.0, 1.0);
+        let d = [ap[0] - t * ab[0], ap[1] - t * ab[1]];
+        d[0] * d[0] + d[1] * d[1] <= self.radius * self.radius
+    }
+
+    fn intersects_box(&self, min_bounds: &[f64], max_bounds: &[f64]) -> bool {
+        (0..2).all(|axis| {
+            let low = self.a[axis].min(self.b[axis]) - self.radius;
+            let high = self.a[axis].max(self.b[axis]) + self.radius;
+            min_bounds[axis] <= high && max_bounds[axis] >= low
+        })
+    }
+}
+
+#[test]
+fn custom_regions_can_be_queried() {
+    let mut rng = rand::rng();
+    let points = (0..500)
+        .map(|_| [rng.random_range(-10.0..10.0), rng.random_range(-10.0..10.0)])
+        .collect::<Vec<[f64; 2]>>();
+    let mut tree = KdTree::with_capacity(2, 4);
+    for (i, &p) in points.iter().enumerate() {
+        tree.add(p, i).unwrap();
+    }
+    let capsule = Capsule {
+        a: [-5.0, -5.0],
+        b: [6.0, 2.0],
+        radius: 1.5,
+    };
+    assert_matches_brute_force(&tree, &points, &capsule);
+}
+
+#[test]
+fn regions_test_the_shared_point_of_coincident_leaves() {
+    // five copies of one point overflow a leaf of two, which then keeps a single copy
+    let mut points = vec![[1.0, 1.0]; 5];
+    points.extend([[4.0, 0.0], [0.0, 4.0], [3.0, 3.0]]);
+    let mut tree = KdTree::with_capacity(2, 2);
+    for (i, &p) in points.iter().enumerate() {
+        tree.add(p, i).unwrap();
+    }
+    assert_matches_brute_force(&tree, &points, &Ball::new(vec![1.0, 1.0], 0.5));
+    assert_matches_brute_force(&tree, &points, &Ball::new(vec![0.0, 0.0], 1.0));
+    assert_matches_brute_force(&tree, &points, &AxisBox::new(vec![1.0, 0.0], vec![4.0, 1.0]).unwrap());
+    assert_matches_brute_force(&tree, &points, &HalfSpace::new(vec![1.0, 1.0], 2.0));
+    assert_matches_brute_force(&tree, &points, &HalfSpace::new(vec![1.0, 1.0], 1.9));
+    assert_matches_brute_force(&tree, &points, &Polygon::new([[0.0, 0.0], [2.0, 0.5], [0.5, 2.0]]));
+}
+
+#[test]
+fn region_edge_cases() {
+    let empty: KdTree<f64, usize, [f64; 2]> = KdTree::new(2);
+    assert_eq!(
+        empty.query_region(&Ball::new(vec![0.0, 0.0], 1.0)).unwrap(),
+        Vec::<&usize>::new()
+    );
+    assert_eq!(empty.count_region(&HalfSpace::new(vec![1.0, 0.0], 0.0)).unwrap(), 0);
+
+    let mut tree: KdTree<f64, usize, [f64; 3]> = KdTree::new(3);
+    tree.add([0.0, 0.0, 0.0], 0).unwrap();
+    assert_eq!(
+        tree.query_region(&Polygon::new([[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]])),
+        Err(ErrorKind::WrongDimension)
+    );
+    assert_eq!(
+        AxisBox::new(vec![1.0, 0.0], vec![0.0, 1.0]),
+        Err(ErrorKind::InvalidBoundingBox { axis: 0 })
+    );
+    assert_eq!(AxisBox::new(vec![0.0, 0.0], vec![1.0]), Err(ErrorKind::WrongDimension));
+
+    let mut tree: KdTree<i32, usize, [i32; 2]> = KdTree::with_capacity(2, 1);
+    for (i, p) in [[0, 0], [3, 4], [5, 5], [-3, -4]].into_iter().enumerate() {
+        tree.add(p, i).unwrap();
+    }
+    let mut found = tree.query_region(&Ball::new(vec![0, 0], 5)).unwrap();
+    found.sort();
+    assert_eq!(found, vec![&0, &1, &3]);
+}
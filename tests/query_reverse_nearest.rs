@@ -0,0 +1,128 @@
+use kdtree::distance::{manhattan, squared_euclidean};
+use kdtree::{ErrorKind, KdTree};
+use rand::Rng;
+
+type Tree = KdTree<f64, usize, [f64; 2]>;
+
+/// Distance from every entry to its `k`-th nearest other entry
+fn brute_force_radii(points: &[[f64; 2]], k: usize, distance: fn(&[f64], &[f64]) -> f64) -> Vec<f64> {
+    (0..points.len())
+        .map(|i| {
+            let mut others = (0..points.len())
+                .filter(|&j| j != i)
+                .map(|j| distance(&points[i], &points[j]))
+                .collect::<Vec<_>>();
+            others.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            others.get(k - 1).copied().unwrap_or(f64::MAX)
+        })
+        .collect()
+}
+
+/// Entries that have `query` among their `k` nearest
+fn brute_force(
+    points: &[[f64; 2]],
+    radii: &[f64],
+    query: &[f64; 2],
+    distance: fn(&[f64], &[f64]) -> f64,
+) -> Vec<usize> {
+    (0..points.len())
+        .filter(|&i| distance(query, &points[i]) <= radii[i])
+        .collect()
+}
+
+fn sorted_indices(points: &[[f64; 2]], found: Vec<(&[f64; 2], &usize)>) -> Vec<usize> {
+    let mut indices = found
+        .into_iter()
+        .map(|(p, &i)| {
+            assert_eq!(p, &points[i]);
+            i
+        })
+        .collect::<Vec<_>>();
+    indices.sort();
+    indices
+}
+
+#[test]
+fn reverse_nearest_matches_brute_force() {
+    let mut rng = rand::rng();
+    // a coarse grid produces ties between neighbours
+    let points = (0..600)
+        .map(|_| [0; 2].map(|_| f64::from(rng.random_range(0..30u8))))
+        .collect::<Vec<_>>();
+    let mut tree = Tree::with_capacity(2, 4);
+    for (i, &p) in points.iter().enumerate() {
+        tree.add(p, i).unwrap();
+    }
+    for k in [1, 3, 8] {
+        let expected_radii = brute_force_radii(&points, k, squared_euclidean);
+        let radii = tree.knn_radii(k, &squared_euclidean).unwrap();
+        assert_eq!(radii.k(), k);
+        for _ in 0..10 {
+            let query = [rng.random_range(-5.0..35.0), rng.random_range(-5.0..35.0)];
+            let expected = brute_force(&points, &expected_radii, &query, squared_euclidean);
+            let found = tree.reverse_nearest(&query, k, &squared_euclidean).unwrap();
+            assert_eq!(sorted_indices(&points, found), expected);
+            let cached = tree.reverse_nearest_cached(&query, &radii, &squared_euclidean).unwrap();
+            assert_eq!(sorted_indices(&points, cached), expected);
+        }
+        // stored points tie with their neighbours on the grid
+        let query = points[0];
+        let expected = brute_force(&points, &brute_force_radii(&points, k, manhattan), &query, manhattan);
+        let found = tree.reverse_nearest(&query, k, &manhattan).unwrap();
+        assert_eq!(sorted_indices(&points, found), expected);
+    }
+}
+
+#[test]
+fn reverse_nearest_sees_every_entry_of_a_coincident_leaf() {
+    // four copies of one point overflow a leaf of two, which then keeps a single copy
+    let mut points = vec![[1.0, 1.0]; 4];
+    points.extend([[4.0, 0.0], [0.0, 4.0], [3.0, 3.0]]);
+    let mut tree = Tree::with_capacity(2, 2);
+    for (i, &p) in points.iter().enumerate() {
+        tree.add(p, i).unwrap();
+    }
+    for k in [1, 3, 4, 6] {
+        let radii = brute_force_radii(&points, k, squared_euclidean);
+        for query in [[1.0, 1.0], [1.5, 1.0], [2.0, 2.0], [3.5, 0.5]] {
+            let expected = brute_force(&points, &radii, &query, squared_euclidean);
+            let found = tree.reverse_nearest(&query, k, &squared_euclidean).unwrap();
+            assert_eq!(sorted_indices(&points, found), expected);
+        }
+    }
+}
+
+#[test]
+fn reverse_nearest_edge_cases() {
+    let mut tree = Tree::with_capacity(2, 2);
+    assert_eq!(
+        tree.reverse_nearest(&[0.0, 0.0], 1, &squared_euclidean).unwrap(),
+        vec![]
+    );
+    tree.add([0.0, 0.0], 0).unwrap();
+    // an entry without k others has every query among its neighbours
+    assert_eq!(
+        tree.reverse_nearest(&[9.0, 9.0], 1, &squared_euclidean).unwrap(),
+        vec![(&[0.0, 0.0], &0)]
+    );
+    tree.add([1.0, 0.0], 1).unwrap();
+    tree.add([5.0, 0.0], 2).unwrap();
+    let mut found = tree.reverse_nearest(&[4.0, 0.0], 1, &squared_euclidean).unwrap();
+    found.sort_by_key(|&(_, &i)| i);
+    assert_eq!(found, vec![(&[5.0, 0.0], &2)]);
+    assert_eq!(
+        tree.reverse_nearest(&[4.0, 0.0], 0, &squared_euclidean).unwrap(),
+        vec![]
+    );
+
+    let radii = tree.knn_radii(1, &squared_euclidean).unwrap();
+    tree.add([6.0, 0.0], 3).unwrap();
+    assert_eq!(
+        tree.reverse_nearest_cached(&[4.0, 0.0], &radii, &squared_euclidean),
+        Err(ErrorKind::StaleKnnRadii)
+    );
+    assert_eq!(
+        tree.reverse_nearest(&[4.0], 1, &squared_euclidean),
+        Err(ErrorKind::WrongDimension)
+    );
+}
@@ -0,0 +1,145 @@
+use kdtree::distance::squared_euclidean;
+use kdtree::shape::{AxisBox, Point, Ray, Segment, Shape};
+use kdtree::{ErrorKind, KdTree};
+use rand::Rng;
+
+fn assert_matches_brute_force<Q: Shape<f64, Distance = f64>>(
+    tree: &KdTree<f64, usize, [f64; 3]>,
+    points: &[[f64; 3]],
+    shape: &Q,
+    radius: f64,
+) {
+    let mut expected = points
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (shape.distance_to_point(p), i))
+        .collect::<Vec<_>>();
+    expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let distances = |found: Vec<(f64, &usize)>| found.into_iter().map(|(d, _)| d).collect::<Vec<_>>();
+
+    let nearest = tree.nearest_to_shape(shape, 10).unwrap();
+    assert_eq!(
+        distances(nearest),
+        expected.iter().take(10).map(|&(d, _)| d).collect::<Vec<_>>()
+    );
+
+    let mut within = tree
+        .within_shape(shape, radius)
+        .unwrap()
+        .into_iter()
+        .map(|(d, &i)| (d, i))
+        .collect::<Vec<_>>();
+    within.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    expected.retain(|&(d, _)| d <= radius);
+    assert_eq!(within, expected);
+}
+
+#[test]
+fn built_in_shapes_match_brute_force() {
+    let mut rng = rand::rng();
+    let points = (0..1000)
+        .map(|_| [(); 3].map(|_| rng.random_range(-10.0..10.0)))
+        .collect::<Vec<[f64; 3]>>();
+    let mut tree = KdTree::with_capacity(3, 4);
+    for (i, &p) in points.iter().enumerate() {
+        tree.add(p, i).unwrap();
+    }
+    for _ in 0..20 {
+        let mut random_point = || [(); 3].map(|_| rng.random_range(-12.0..12.0)).to_vec();
+        let (a, b) = (random_point(), random_point());
+        let radius = 4.0;
+        assert_matches_brute_force(&tree, &points, &Point::new(a.clone()), radius);
+        assert_matches_brute_force(&tree, &points, &Segment::new(a.clone(), b.clone()).unwrap(), radius);
+        let direction = b.iter().zip(&a).map(|(b, a)| b - a).collect();
+        assert_matches_brute_force(&tree, &points, &Ray::new(a.clone(), direction).unwrap(), radius);
+        let (min, max) = a
+            .iter()
+            .zip(&b)
+            .map(|(&a, &b)| (a.min(b) / 2.0, a.max(b) / 2.0))
+            .unzip();
+        assert_matches_brute_force(&tree, &points, &AxisBox::new(min, max).unwrap(), radius);
+    }
+}
+
+#[test]
+fn point_shape_matches_nearest() {
+    let mut rng = rand::rng();
+    let points = (0..500)
+        .map(|_| [(); 3].map(|_| rng.random_range(-10.0..10.0)))
+        .collect::<Vec<[f64; 3]>>();
+    let mut tree = KdTree::with_capacity(3, 4);
+    for (i, &p) in points.iter().enumerate() {
+        tree.add(p, i).unwrap();
+    }
+    // entries at the same distance may come back in either order
+    let distances = |found: Vec<(f64, &usize)>| found.into_iter().map(|(d, _)| d).collect::<Vec<_>>();
+    for _ in 0..20 {
+        let query = [(); 3].map(|_| rng.random_range(-12.0..12.0));
+        assert_eq!(
+            distances(tree.nearest_to_shape(&Point::new(query.to_vec()), 7).unwrap()),
+            distances(tree.nearest(&query, 7, &squared_euclidean).unwrap())
+        );
+    }
+}
+
+#[test]
+fn shapes_reach_every_entry_of_a_coincident_leaf() {
+    // five copies of one point overflow a leaf of two, which then keeps a single copy
+    let mut tree = KdTree::with_capacity(3, 2);
+    for i in 0..5 {
+        tree.add([1.0, 1.0, 1.0], i).unwrap();
+    }
+    tree.add([4.0, 0.0, 0.0], 5).unwrap();
+    tree.add([0.0, 4.0, 0.0], 6).unwrap();
+    let sorted = |found: Vec<(f64, &usize)>| {
+        let mut found = found.into_iter().map(|(d, &i)| (d, i)).collect::<Vec<_>>();
+        found.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        found
+    };
+    let shared = (0..5).map(|i| (2.0, i)).collect::<Vec<_>>();
+
+    let segment = Segment::new(vec![0.0, 0.0, 0.0], vec![2.0, 0.0, 0.0]).unwrap();
+    assert_eq!(sorted(tree.nearest_to_shape(&segment, 5).unwrap()), shared);
+    let ray = Ray::new(vec![1.0, 0.0, 0.0], vec![0.0, 0.0, 1.0]).unwrap();
+    let touching = (0..5).map(|i| (1.0, i)).collect::<Vec<_>>();
+    assert_eq!(sorted(tree.within_shape(&ray, 2.0).unwrap()), touching);
+    let area = AxisBox::new(vec![1.0, 2.0, 1.0], vec![2.0, 3.0, 1.0]).unwrap();
+    assert_eq!(sorted(tree.within_shape(&area, 2.0).unwrap()), touching);
+}
+
+#[test]
+fn shape_edge_cases() {
+    let mut tree: KdTree<i32, &str, [i32; 2]> = KdTree::with_capacity(2, 1);
+    tree.add([0, 0], "origin").unwrap();
+    tree.add([10, 10], "corner").unwrap();
+    tree.add([3, 20], "above").unwrap();
+    let area = AxisBox::new(vec![2, 2], vec![5, 5]).unwrap();
+    assert_eq!(
+        tree.nearest_to_shape(&area, 3).unwrap(),
+        vec![(8, &"origin"), (50, &"corner"), (225, &"above")]
+    );
+    assert_eq!(tree.within_shape(&area, 8).unwrap(), vec![(8, &"origin")]);
+
+    // a degenerate segment is a point, a ray only extends forwards
+    let dot = Segment::new(vec![8.0, 12.0], vec![8.0, 12.0]).unwrap();
+    assert_eq!(tree.nearest_to_shape(&dot, 1).unwrap(), vec![(8.0, &"corner")]);
+    let ray = Ray::new(vec![0.0, 5.0], vec![0.0, -1.0]).unwrap();
+    assert_eq!(tree.nearest_to_shape(&ray, 1).unwrap(), vec![(0.0, &"origin")]);
+    assert_eq!(tree.within_shape(&ray, 99.0).unwrap(), vec![(0.0, &"origin")]);
+
+    assert_eq!(Segment::new(vec![0.0, 0.0], vec![1.0]), Err(ErrorKind::WrongDimension));
+    assert_eq!(
+        Segment::new(vec![f64::NAN, 0.0], vec![1.0, 1.0]),
+        Err(ErrorKind::NonFiniteCoordinate)
+    );
+    assert_eq!(
+        Ray::new(vec![0.0, f64::INFINITY], vec![1.0, 0.0]),
+        Err(ErrorKind::NonFiniteCoordinate)
+    );
+    assert_eq!(
+        tree.nearest_to_shape(&Point::new(vec![0, 0, 0]), 1),
+        Err(ErrorKind::WrongDimension)
+    );
+    let empty: KdTree<f64, usize, [f64; 2]> = KdTree::new(2);
+    assert_eq!(empty.nearest_to_shape(&Point::new(vec![0.0, 0.0]), 3).unwrap(), vec![]);
+}
@@ -0,0 +1,108 @@
+use kdtree::{ErrorKind, KdTree};
+use rand::Rng;
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn cosine(a: &[f64], b: &[f64]) -> f64 {
+    dot(a, b) / (dot(a, a).sqrt() * dot(b, b).sqrt())
+}
+
+fn brute_force(points: &[[f64; 4]], score: impl Fn(&[f64]) -> f64, k: usize) -> Vec<f64> {
+    let mut scores = points.iter().map(|p| score(p)).collect::<Vec<_>>();
+    scores.sort_by(|a, b| b.partial_cmp(a).unwrap());
+    scores.truncate(k);
+    scores
+}
+
+#[test]
+fn max_inner_product_matches_brute_force() {
+    let mut rng = rand::rng();
+    let points = (0..1000)
+        .map(|_| [0; 4].map(|_| rng.random_range(-1.0..1.0)))
+        .collect::<Vec<[f64; 4]>>();
+    let mut tree = KdTree::with_capacity(4, 8);
+    for (i, &p) in points.iter().enumerate() {
+        tree.add(p, i).unwrap();
+    }
+    for _ in 0..50 {
+        let query = [0; 4].map(|_| rng.random_range(-3.0..3.0));
+        let found = tree.max_inner_product(&query, 10).unwrap();
+        assert_eq!(
+            found.iter().map(|&(s, _)| s).collect::<Vec<_>>(),
+            brute_force(&points, |p| dot(&query, p), 10)
+        );
+        for (s, &i) in found {
+            assert_eq!(s, dot(&query, &points[i]));
+        }
+    }
+}
+
+#[test]
+fn nearest_cosine_matches_brute_force() {
+    let mut rng = rand::rng();
+    // directions matter rather than lengths, so keep the points away from the origin
+    let points = (0..1000)
+        .map(|_| [0; 4].map(|_| rng.random_range(1.0..2.0) * if rng.random() { 1.0 } else { -1.0 }))
+        .collect::<Vec<[f64; 4]>>();
+    let mut tree = KdTree::with_capacity(4, 8);
+    for (i, &p) in points.iter().enumerate() {
+        tree.add(p, i).unwrap();
+    }
+    for _ in 0..50 {
+        let query = [0; 4].map(|_| rng.random_range(-3.0..3.0));
+        let found = tree.nearest_cosine(&query, 10).unwrap();
+        let expected = brute_force(&points, |p| cosine(&query, p), 10);
+        for ((s, _), e) in found.iter().zip(&expected) {
+            assert!((s - e).abs() < 1e-12, "{} != {}", s, e);
+        }
+        assert_eq!(found.len(), 10);
+    }
+}
+
+#[test]
+fn similarity_edge_cases() {
+    let mut tree: KdTree<f64, &str, [f64; 2]> = KdTree::with_capacity(2, 2);
+    assert_eq!(tree.max_inner_product(&[1.0, 0.0], 3).unwrap(), vec![]);
+    tree.add([0.0, 0.0], "origin").unwrap();
+    tree.add([2.0, 0.0], "east").unwrap();
+    tree.add([0.0, -1.0], "south").unwrap();
+    tree.add([-3.0, 0.0], "west").unwrap();
+
+    assert_eq!(tree.max_inner_product(&[1.0, 0.0], 0).unwrap(), vec![]);
+    let found = tree.max_inner_product(&[1.0, 0.0], 10).unwrap();
+    // origin and south tie, so only their scores are fixed
+    assert_eq!(
+        found.iter().map(|&(s, _)| s).collect::<Vec<_>>(),
+        vec![2.0, 0.0, 0.0, -3.0]
+    );
+    assert_eq!((found[0].1, found[3].1), (&"east", &"west"));
+    // the origin has no direction and is left out
+    assert_eq!(
+        tree.nearest_cosine(&[1.0, 0.0], 10).unwrap(),
+        vec![(1.0, &"east"), (0.0, &"south"), (-1.0, &"west")]
+    );
+    assert_eq!(tree.nearest_cosine(&[0.0, 0.0], 1), Err(ErrorKind::ZeroVector));
+    assert_eq!(tree.nearest_cosine(&[1.0], 1), Err(ErrorKind::WrongDimension));
+}
+
+#[test]
+fn similarity_on_integer_and_f32_trees() {
+    let mut tree: KdTree<i32, u8, [i32; 2]> = KdTree::with_capacity(2, 1);
+    for (i, p) in [[1, 1], [5, -5], [-2, 4], [3, 3]].into_iter().enumerate() {
+        tree.add(p, i as u8).unwrap();
+    }
+    assert_eq!(tree.max_inner_product(&[0, 1], 2).unwrap(), vec![(4.0, &2), (3.0, &3)]);
+    let cosine = tree.nearest_cosine(&[1, 1], 2).unwrap();
+    assert_eq!(cosine.iter().map(|&(_, &i)| i).collect::<Vec<_>>().len(), 2);
+    assert!(
+        cosine
+            .iter()
+            .all(|&(s, &i)| (i == 0 || i == 3) && (s - 1.0).abs() < 1e-12)
+    );
+
+    let mut tree: KdTree<f32, u8, [f32; 2]> = KdTree::new(2);
+    tree.add([0.5, 0.25], 0).unwrap();
+    assert_eq!(tree.max_inner_product(&[2.0, 4.0], 1).unwrap(), vec![(2.0, &0)]);
+}
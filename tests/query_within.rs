@@ -1,8 +1,9 @@
 mod __util__;
 
 use __util__::{POINT_A, POINT_B, basic_tree};
-use kdtree::KdTree;
 use kdtree::distance::squared_euclidean;
+use kdtree::{ErrorKind, KdTree};
+use rand::Rng;
 
 fn assert_unordered_usize(results: Vec<(f64, &usize)>, expected: &[(f64, usize)]) {
     let mut actual = results.into_iter().map(|(d, v)| (d, *v)).collect::<Vec<_>>();
@@ -52,3 +53,68 @@ fn within_handles_pending_order_tree() {
     assert_unordered_usize(tree.within(&[55f64], 5.0, &squared_euclidean).unwrap(), &[(0.0, 4)]);
     assert_unordered_usize(tree.within(&[56f64], 5.0, &squared_euclidean).unwrap(), &[(1.0, 4)]);
 }
+
+#[test]
+fn within_range_queries_match_expected() {
+    let tree = basic_tree();
+    assert_unordered_usize(
+        tree.within_range(&POINT_A.0, 1.0, 8.0, &squared_euclidean).unwrap(),
+        &[(2.0, 1), (8.0, 2)],
+    );
+    assert_unordered_usize(
+        tree.within_range(&POINT_B.0, 0.0, 0.0, &squared_euclidean).unwrap(),
+        &[(0.0, 1)],
+    );
+    assert_eq!(
+        tree.within_range_count(&POINT_B.0, 1.0, 8.0, &squared_euclidean)
+            .unwrap(),
+        3
+    );
+    assert_eq!(
+        tree.within_range_count(&POINT_A.0, 19.0, 100.0, &squared_euclidean)
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        tree.within_range(&POINT_A.0, 2.0, 1.0, &squared_euclidean),
+        Err(ErrorKind::InvalidDistanceRange)
+    );
+}
+
+#[test]
+fn within_range_matches_brute_force() {
+    let mut rng = rand::rng();
+    let points = (0..2000)
+        .map(|_| [rng.random_range(-10.0..10.0), rng.random_range(-10.0..10.0)])
+        .collect::<Vec<[f64; 2]>>();
+    let mut tree = KdTree::with_capacity(2, 4);
+    for (i, &p) in points.iter().enumerate() {
+        tree.add(p, i).unwrap();
+    }
+    for _ in 0..50 {
+        let query = [rng.random_range(-12.0..12.0), rng.random_range(-12.0..12.0)];
+        let inner: f64 = rng.random_range(0.0..50.0);
+        let outer = inner + rng.random_range(0.0..50.0);
+        let mut expected = points
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (squared_euclidean(p, &query), i))
+            .filter(|&(d, _)| inner <= d && d <= outer)
+            .collect::<Vec<_>>();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let mut found = tree
+            .within_range(&query, inner, outer, &squared_euclidean)
+            .unwrap()
+            .into_iter()
+            .map(|(d, &i)| (d, i))
+            .collect::<Vec<_>>();
+        found.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(found, expected);
+        assert_eq!(
+            tree.within_range_count(&query, inner, outer, &squared_euclidean)
+                .unwrap(),
+            expected.len()
+        );
+    }
+}
@@ -0,0 +1,101 @@
+#[cfg(feature = "rkyv")]
+mod rkyv_tests {
+    use kdtree::KdTree;
+    use kdtree::distance::squared_euclidean;
+    use kdtree::kdtree::ArchivedKdTree;
+    use rand::Rng;
+    use rkyv::rancor::Error;
+
+    type Tree = KdTree<f64, u32, [f64; 3]>;
+    type Archived = ArchivedKdTree<f64, u32, [f64; 3]>;
+
+    fn sample_tree() -> Tree {
+        let mut rng = rand::rng();
+        let mut kdtree = Tree::with_capacity(3, 8);
+        for i in 0..2000 {
+            // a coarse grid produces plenty of ties and coincident leaves
+            let point = [0; 3].map(|_| (rng.random_range(0..15) as f64) / 15.0);
+            kdtree.add(point, i).unwrap();
+        }
+        kdtree
+    }
+
+    #[test]
+    fn archived_queries_match_owned_tree() {
+        let mut rng = rand::rng();
+        let kdtree = sample_tree();
+        let bytes = rkyv::to_bytes::<Error>(&kdtree).unwrap();
+        let archived = rkyv::access::<Archived, Error>(&bytes).unwrap();
+        assert_eq!(archived.size(), kdtree.size());
+        assert_eq!(archived.dimensions(), 3);
+
+        let native = |results: Vec<(f64, &rkyv::Archived<u32>)>| {
+            results.into_iter().map(|(d, v)| (d, v.to_native())).collect::<Vec<_>>()
+        };
+        let owned = |results: Vec<(f64, &u32)>| results.into_iter().map(|(d, &v)| (d, v)).collect::<Vec<_>>();
+        for _ in 0..50 {
+            let query = [0; 3].map(|_| rng.random::<f64>());
+            for k in [1, 9, 50] {
+                assert_eq!(
+                    native(archived.nearest(&query, k, &squared_euclidean).unwrap()),
+                    owned(kdtree.nearest(&query, k, &squared_euclidean).unwrap())
+                );
+            }
+            assert_eq!(
+                native(archived.within(&query, 0.02, &squared_euclidean).unwrap()),
+                owned(kdtree.within(&query, 0.02, &squared_euclidean).unwrap())
+            );
+            assert_eq!(
+                archived.within_count(&query, 0.05, &squared_euclidean).unwrap(),
+                kdtree.within_count(&query, 0.05, &squared_euclidean).unwrap()
+            );
+            let upper = query.map(|v| v + 0.25);
+            assert_eq!(
+                archived
+                    .bounding_box(&query, &upper)
+                    .unwrap()
+                    .into_iter()
+                    .map(|v| v.to_native())
+                    .collect::<Vec<_>>(),
+                kdtree
+                    .bounding_box(&query, &upper)
+                    .unwrap()
+                    .into_iter()
+                    .copied()
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn archived_tree_deserializes_back() {
+        let kdtree = sample_tree();
+        let bytes = rkyv::to_bytes::<Error>(&kdtree).unwrap();
+        let restored: Tree = rkyv::from_bytes::<Tree, Error>(&bytes).unwrap();
+        let query = [0.5, 0.5, 0.5];
+        assert_eq!(
+            restored.nearest(&query, 20, &squared_euclidean).unwrap(),
+            kdtree.nearest(&query, 20, &squared_euclidean).unwrap()
+        );
+    }
+
+    #[test]
+    fn archived_empty_tree_and_invalid_queries() {
+        let kdtree = Tree::new(3);
+        let bytes = rkyv::to_bytes::<Error>(&kdtree).unwrap();
+        let archived = rkyv::access::<Archived, Error>(&bytes).unwrap();
+        assert_eq!(archived.nearest(&[0.0; 3], 4, &squared_euclidean), Ok(vec![]));
+        assert_eq!(archived.bounding_box(&[0.0; 3], &[1.0; 3]), Ok(vec![]));
+        assert_eq!(
+            archived.within_count(&[0.0; 2], 1.0, &squared_euclidean),
+            Err(kdtree::ErrorKind::WrongDimension)
+        );
+    }
+
+    #[test]
+    fn access_rejects_garbage() {
+        let mut bytes = rkyv::util::AlignedVec::<16>::new();
+        bytes.extend_from_slice(&[0xff; 64]);
+        assert!(rkyv::access::<Archived, Error>(&bytes).is_err());
+    }
+}
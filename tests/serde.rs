@@ -1,6 +1,24 @@
 #[cfg(all(test, feature = "serialize"))]
 mod serialization_tests {
     use kdtree::KdTree;
+    use kdtree::distance::squared_euclidean;
+    use serde_json::{Value, json};
+
+    type Tree = KdTree<f64, usize, [f64; 2]>;
+
+    /// Four points in a capacity 3 tree: a stem splitting on dimension 0 with two leaves
+    fn stem_json() -> Value {
+        let mut kdtree = Tree::with_capacity(2, 3);
+        for (i, p) in [[0.0, 0.0], [1.0, 1.0], [2.0, 2.0], [3.0, 3.0]].into_iter().enumerate() {
+            kdtree.add(p, i).unwrap();
+        }
+        serde_json::to_value(&kdtree).unwrap()
+    }
+
+    fn rejected(value: Value, message: &str) {
+        let error = serde_json::from_value::<Tree>(value).unwrap_err().to_string();
+        assert!(error.contains(message), "unexpected error: {}", error);
+    }
 
     #[test]
     fn empty_tree() {
@@ -11,6 +29,18 @@ mod serialization_tests {
         assert_eq!(deserialized_tree.size(), 0);
     }
 
+    #[test]
+    fn empty_tree_with_zero_capacity() {
+        let kdtree = Tree::with_capacity(2, 0);
+        let serialized = serde_json::to_string(&kdtree).unwrap();
+        let mut deserialized_tree: Tree = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized_tree.size(), 0);
+        assert_eq!(
+            deserialized_tree.add([0.0, 0.0], 0),
+            Err(kdtree::ErrorKind::ZeroCapacity)
+        );
+    }
+
     #[test]
     fn tree_with_one_element() {
         let mut kdtree = KdTree::<f64, String, [f64; 2]>::new(2);
@@ -20,4 +50,168 @@ mod serialization_tests {
         let deserialized_tree: KdTree<f64, String, [f64; 2]> = serde_json::from_str(&serialized).unwrap();
         assert_eq!(deserialized_tree.size(), 1);
     }
+
+    #[test]
+    fn round_trip_keeps_queries_working() {
+        let mut kdtree = Tree::with_capacity(2, 3);
+        for i in 0..200 {
+            kdtree.add([(i % 17) as f64, (i % 5) as f64], i).unwrap();
+        }
+        for i in 0..50 {
+            kdtree.add([1.0, 1.0], 1000 + i).unwrap();
+        }
+        kdtree.remove(&[3.0, 3.0], &3).unwrap();
+
+        let serialized = serde_json::to_string(&kdtree).unwrap();
+        let deserialized_tree: Tree = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized_tree.size(), kdtree.size());
+        assert_eq!(
+            deserialized_tree.nearest(&[1.0, 1.0], 60, &squared_euclidean).unwrap(),
+            kdtree.nearest(&[1.0, 1.0], 60, &squared_euclidean).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_size_not_matching_bucket() {
+        let mut value = stem_json();
+        value["left"]["size"] = json!(5);
+        rejected(value, "size 5 does not match bucket length 2");
+    }
+
+    #[test]
+    fn rejects_size_not_matching_children() {
+        let mut value = stem_json();
+        value["size"] = json!(7);
+        rejected(value, "size 7 does not match children sizes 2 + 2");
+    }
+
+    #[test]
+    fn rejects_points_and_bucket_of_different_lengths() {
+        let mut value = stem_json();
+        value["right"]["bucket"].as_array_mut().unwrap().pop();
+        value["right"]["size"] = json!(1);
+        value["size"] = json!(3);
+        rejected(value, "leaf has 2 points for 1 entries");
+    }
+
+    #[test]
+    fn rejects_leaves_over_capacity() {
+        let mut value = stem_json();
+        value["capacity"] = json!(1);
+        value["left"]["capacity"] = json!(1);
+        value["right"]["capacity"] = json!(1);
+        rejected(value, "leaf holds 2 entries, more than its capacity 1");
+    }
+
+    #[test]
+    fn rejects_split_dimension_out_of_range() {
+        let mut value = stem_json();
+        value["split_dimension"] = json!(2);
+        rejected(value, "split dimension 2 exceeds 2 dimensions");
+    }
+
+    #[test]
+    fn rejects_points_with_wrong_dimensions() {
+        let mut value = json!({
+            "left": null, "right": null, "dimensions": 3, "capacity": 2, "size": 1,
+            "min_bounds": [0.0, 0.0, 0.0], "max_bounds": [0.0, 0.0, 0.0],
+            "split_value": null, "split_dimension": null,
+            "points": [[0.0, 0.0]], "bucket": [0]
+        });
+        rejected(value.clone(), "point has 2 dimensions, expected 3");
+        value["min_bounds"] = json!([0.0, 0.0]);
+        rejected(value, "bounds have 2 and 3 dimensions, expected 3");
+    }
+
+    #[test]
+    fn rejects_incomplete_nodes() {
+        let mut value = stem_json();
+        value["right"] = Value::Null;
+        rejected(value, "neither a complete leaf nor a complete stem");
+
+        let mut value = stem_json();
+        value["split_value"] = Value::Null;
+        rejected(value, "stem is missing its split");
+    }
+
+    #[test]
+    fn rejects_unpartitioned_children() {
+        let mut value = stem_json();
+        let left = value["left"].clone();
+        value["left"] = value["right"].clone();
+        value["right"] = left;
+        rejected(value, "children are not partitioned by the split value");
+    }
+
+    #[test]
+    fn rejects_points_outside_of_bounds() {
+        let mut value = stem_json();
+        value["left"]["points"][0] = json!([-1.0, 0.0]);
+        rejected(value, "point lies outside of its leaf bounds");
+    }
+
+    #[test]
+    fn accepts_inclusive_splits_written_by_0_8() {
+        // 0.8 split adjacent values on the lower one and sent values equal to it left
+        let low = 1.0f64;
+        let high = f64::from_bits(low.to_bits() + 1);
+        let mut kdtree = KdTree::<f64, usize, [f64; 1]>::with_capacity(1, 1);
+        kdtree.add([low], 0).unwrap();
+        kdtree.add([high], 1).unwrap();
+        let mut value = serde_json::to_value(&kdtree).unwrap();
+        value["split_value"] = json!(low);
+
+        let migrated: KdTree<f64, usize, [f64; 1]> = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(migrated.get(&[low]), Ok(Some(&0)));
+        assert_eq!(migrated.get(&[high]), Ok(Some(&1)));
+
+        value["right"]["points"] = json!([]);
+        value["right"]["bucket"] = json!([]);
+        value["right"]["size"] = json!(0);
+        value["size"] = json!(1);
+        let migrated: KdTree<f64, usize, [f64; 1]> = serde_json::from_value(value).unwrap();
+        assert_eq!(migrated.size(), 1);
+        assert_eq!(migrated.get(&[low]), Ok(Some(&0)));
+    }
+
+    #[test]
+    fn bincode_round_trip() {
+        let empty = Tree::new(2);
+        let bytes = bincode::serialize(&empty).unwrap();
+        assert_eq!(bincode::deserialize::<Tree>(&bytes).unwrap().size(), 0);
+
+        let mut kdtree = Tree::with_capacity(2, 3);
+        for i in 0..100 {
+            kdtree.add([(i % 11) as f64, (i % 3) as f64], i).unwrap();
+        }
+        for i in 0..20 {
+            kdtree.add([2.0, 2.0], 1000 + i).unwrap();
+        }
+        let bytes = bincode::serialize(&kdtree).unwrap();
+        let deserialized_tree: Tree = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(deserialized_tree.size(), kdtree.size());
+        assert_eq!(
+            deserialized_tree.nearest(&[2.0, 2.0], 30, &squared_euclidean).unwrap(),
+            kdtree.nearest(&[2.0, 2.0], 30, &squared_euclidean).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_value(&deserialized_tree).unwrap(),
+            serde_json::to_value(&kdtree).unwrap()
+        );
+    }
+
+    #[test]
+    fn bincode_rejects_inconsistent_trees() {
+        let mut kdtree = Tree::new(2);
+        kdtree.add([1.0, 2.0], 7).unwrap();
+        let mut bytes = bincode::serialize(&kdtree).unwrap();
+        // a leaf starts with two `None` children, then dimensions, capacity and size as u64
+        bytes[18..26].copy_from_slice(&5u64.to_le_bytes());
+        let error = bincode::deserialize::<Tree>(&bytes).unwrap_err().to_string();
+        assert!(
+            error.contains("size 5 does not match bucket length 1"),
+            "unexpected error: {}",
+            error
+        );
+    }
 }
@@ -1,4 +1,5 @@
 use kdtree::KdTree;
+use kdtree::distance::squared_euclidean;
 
 #[test]
 fn zero_capacity_tree_rejects_insertions() {
@@ -36,3 +37,61 @@ fn splitting_handles_extreme_bounds() {
         tree.add([high], ()).unwrap();
     }
 }
+
+#[test]
+fn duplicates_share_a_single_leaf_point() {
+    let mut tree = KdTree::with_capacity(2, 4);
+    for i in 0..1_000_000 {
+        tree.add([1.0, 1.0], i).unwrap();
+    }
+    assert_eq!(tree.size(), 1_000_000);
+
+    let nearest = tree.nearest(&[0.0, 0.0], 3, &squared_euclidean).unwrap();
+    assert_eq!(nearest.len(), 3);
+    assert!(nearest.iter().all(|&(d, _)| d == 2.0));
+    assert_eq!(
+        tree.within_count(&[1.0, 1.0], 0.0, &squared_euclidean).unwrap(),
+        1_000_000
+    );
+    assert_eq!(tree.bounding_box(&[1.0, 1.0], &[1.0, 1.0]).unwrap().len(), 1_000_000);
+}
+
+#[test]
+fn distinct_point_splits_coincident_leaf() {
+    let mut tree = KdTree::with_capacity(2, 2);
+    for i in 0..10 {
+        tree.add([1.0, 1.0], i).unwrap();
+    }
+    tree.add([3.0, 1.0], 10).unwrap();
+    tree.add([0.0, 1.0], 11).unwrap();
+    assert_eq!(tree.size(), 12);
+
+    let nearest = tree.nearest(&[3.0, 1.0], 2, &squared_euclidean).unwrap();
+    assert_eq!(nearest[0], (0.0, &10));
+    assert_eq!(nearest[1].0, 4.0);
+    assert_eq!(
+        tree.nearest(&[0.0, 1.0], 1, &squared_euclidean).unwrap(),
+        vec![(0.0, &11)]
+    );
+    assert_eq!(tree.within_count(&[1.0, 1.0], 0.0, &squared_euclidean).unwrap(), 10);
+}
+
+#[test]
+fn removing_from_coincident_leaf() {
+    let mut tree = KdTree::with_capacity(1, 2);
+    for i in 0..100 {
+        tree.add([5.0], i % 4).unwrap();
+    }
+    assert_eq!(tree.remove(&[5.0], &1), Ok(25));
+    assert_eq!(tree.remove(&[4.0], &2), Ok(0));
+    assert_eq!(tree.size(), 75);
+    for i in [0, 2, 3] {
+        assert_eq!(tree.remove(&[5.0], &i), Ok(25));
+    }
+    assert_eq!(tree.size(), 0);
+    assert!(tree.nearest(&[5.0], 1, &squared_euclidean).unwrap().is_empty());
+
+    tree.add([5.0], 7).unwrap();
+    tree.add([6.0], 8).unwrap();
+    assert_eq!(tree.nearest(&[6.0], 1, &squared_euclidean).unwrap(), vec![(0.0, &8)]);
+}
@@ -0,0 +1,211 @@
+use kdtree::KdTree;
+use kdtree::distance::squared_euclidean;
+use kdtree::summary::Summary;
+use rand::Rng;
+
+/// Payload: an id and one of 8 categories
+type Item = (usize, u8);
+
+/// Categories present in a subtree, with the number and sum of ids
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Stats {
+    categories: u8,
+    count: usize,
+    ids: usize,
+}
+
+impl Summary<Item> for Stats {
+    fn empty() -> Self {
+        Stats::default()
+    }
+
+    fn add(&mut self, &(id, category): &Item) {
+        self.categories |= 1 << category;
+        self.count += 1;
+        self.ids += id;
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.categories |= other.categories;
+        self.count += other.count;
+        self.ids += other.ids;
+    }
+}
+
+type Tree = KdTree<f64, Item, [f64; 2], Stats>;
+
+/// Entry `id` at a random point, with categories clustered along the first axis so that
+/// whole subtrees lack most of them
+fn random_entry(rng: &mut impl Rng, id: usize) -> ([f64; 2], Item) {
+    let p = [rng.random_range(-10.0..10.0), rng.random_range(-10.0..10.0)];
+    (p, (id, ((p[0] + 10.0) / 2.5) as u8))
+}
+
+fn stats<'a>(items: impl IntoIterator<Item = &'a Item>) -> Stats {
+    let mut stats = Stats::empty();
+    items.into_iter().for_each(|item| stats.add(item));
+    stats
+}
+
+#[test]
+fn summaries_follow_updates() {
+    let mut rng = rand::rng();
+    let mut entries = (0..300).map(|i| random_entry(&mut rng, i)).collect::<Vec<_>>();
+    let mut tree = Tree::with_summary(2, 4);
+    for &(p, item) in &entries {
+        tree.add(p, item).unwrap();
+    }
+    assert_eq!(tree.summary(), &stats(entries.iter().map(|(_, item)| item)));
+
+    for _ in 0..200 {
+        let i = rng.random_range(0..entries.len());
+        let (p, item) = entries[i];
+        if rng.random_bool(0.5) {
+            assert_eq!(tree.remove(&p, &item).unwrap(), 1);
+            entries.swap_remove(i);
+        } else {
+            let moved = [rng.random_range(-10.0..10.0), rng.random_range(-10.0..10.0)];
+            assert!(tree.update_position(&p, moved, |&x| x == item).unwrap());
+            entries[i].0 = moved;
+        }
+    }
+    assert_eq!(tree.size(), entries.len());
+    assert_eq!(tree.summary(), &stats(entries.iter().map(|(_, item)| item)));
+}
+
+#[test]
+fn pruned_queries_match_brute_force() {
+    let mut rng = rand::rng();
+    let entries = (0..1000).map(|i| random_entry(&mut rng, i)).collect::<Vec<_>>();
+    let mut tree = Tree::with_summary(2, 4);
+    for &(p, item) in &entries {
+        tree.add(p, item).unwrap();
+    }
+    for _ in 0..30 {
+        let query = [rng.random_range(-12.0..12.0), rng.random_range(-12.0..12.0)];
+        let category = rng.random_range(0..8u8);
+        let subtree = |s: &Stats| s.categories & (1 << category) != 0;
+        let entry = |_: &[f64; 2], &(_, c): &Item| c == category;
+
+        let mut expected = entries
+            .iter()
+            .filter(|(_, (_, c))| *c == category)
+            .map(|(p, (id, _))| (squared_euclidean(p, &query), *id))
+            .collect::<Vec<_>>();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let nearest = tree
+            .nearest_pruned(&query, 5, &squared_euclidean, subtree, entry)
+            .unwrap();
+        let distances = nearest.iter().map(|(d, _)| *d).collect::<Vec<_>>();
+        let expected_distances = expected.iter().take(5).map(|(d, _)| *d).collect::<Vec<_>>();
+        assert_eq!(distances, expected_distances);
+        assert!(nearest.iter().all(|(_, (_, c))| *c == category));
+
+        let radius = rng.random_range(0.0..40.0);
+        let mut within = tree
+            .within_pruned(&query, radius, &squared_euclidean, subtree, entry)
+            .unwrap()
+            .into_iter()
+            .map(|(d, (id, _))| (d, *id))
+            .collect::<Vec<_>>();
+        within.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let expected_within = expected
+            .iter()
+            .filter(|(d, _)| *d <= radius)
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(within, expected_within);
+
+        let max = [query[0] + 4.0, query[1] + 4.0];
+        let mut boxed = tree
+            .bounding_box_pruned(&query, &max, subtree, entry)
+            .unwrap()
+            .into_iter()
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        boxed.sort();
+        let mut expected_boxed = entries
+            .iter()
+            .filter(|(p, (_, c))| *c == category && (0..2).all(|i| query[i] <= p[i] && p[i] <= max[i]))
+            .map(|(_, (id, _))| *id)
+            .collect::<Vec<_>>();
+        expected_boxed.sort();
+        assert_eq!(boxed, expected_boxed);
+    }
+}
+
+#[test]
+fn summarize_box_matches_brute_force() {
+    let mut rng = rand::rng();
+    let entries = (0..1000).map(|i| random_entry(&mut rng, i)).collect::<Vec<_>>();
+    let mut tree = Tree::with_summary(2, 4);
+    for &(p, item) in &entries {
+        tree.add(p, item).unwrap();
+    }
+    for _ in 0..30 {
+        let min = [rng.random_range(-12.0..12.0), rng.random_range(-12.0..12.0)];
+        let max = [
+            min[0] + rng.random_range(0.0..15.0),
+            min[1] + rng.random_range(0.0..15.0),
+        ];
+        let inside = entries
+            .iter()
+            .filter(|(p, _)| (0..2).all(|i| min[i] <= p[i] && p[i] <= max[i]))
+            .map(|(_, item)| item);
+        assert_eq!(tree.summarize_box(&min, &max).unwrap(), stats(inside));
+    }
+    assert_eq!(
+        tree.summarize_box(&[-100.0, -100.0], &[100.0, 100.0]).unwrap(),
+        tree.summary().clone()
+    );
+    assert!(tree.summarize_box(&[1.0, 0.0], &[0.0, 1.0]).is_err());
+}
+
+#[test]
+fn summaries_count_every_entry_of_a_coincident_leaf() {
+    // six copies of one point overflow a leaf of two, which then keeps a single copy
+    let mut entries = (0..6).map(|i| ([1.0, 1.0], (i, i as u8))).collect::<Vec<_>>();
+    entries.extend([([4.0, 0.0], (6, 7)), ([0.0, 4.0], (7, 7))]);
+    let mut tree = Tree::with_summary(2, 2);
+    for &(p, item) in &entries {
+        tree.add(p, item).unwrap();
+    }
+    assert_eq!(tree.summary(), &stats(entries.iter().map(|(_, item)| item)));
+    assert_eq!(
+        tree.summarize_box(&[0.5, 0.5], &[1.5, 1.5]).unwrap(),
+        stats(entries[..6].iter().map(|(_, item)| item))
+    );
+    let found = tree
+        .nearest_pruned(
+            &[0.0, 0.0],
+            1,
+            &squared_euclidean,
+            |s| s.categories & (1 << 3) != 0,
+            |_, &(_, c)| c == 3,
+        )
+        .unwrap();
+    assert_eq!(found, vec![(2.0, &(3, 3))]);
+
+    assert_eq!(tree.remove(&[1.0, 1.0], &(3, 3)).unwrap(), 1);
+    entries.remove(3);
+    assert_eq!(tree.summary(), &stats(entries.iter().map(|(_, item)| item)));
+}
+
+#[cfg(feature = "serialize")]
+#[test]
+fn deserialized_trees_rebuild_summaries() {
+    let mut rng = rand::rng();
+    let mut tree = Tree::with_summary(2, 4);
+    for i in 0..200 {
+        let (p, item) = random_entry(&mut rng, i);
+        tree.add(p, item).unwrap();
+    }
+    let serialized = serde_json::to_string(&tree).unwrap();
+    let restored: Tree = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(restored.summary(), tree.summary());
+    assert_eq!(
+        restored.summarize_box(&[-5.0, -5.0], &[5.0, 5.0]).unwrap(),
+        tree.summarize_box(&[-5.0, -5.0], &[5.0, 5.0]).unwrap()
+    );
+}
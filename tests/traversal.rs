@@ -1,6 +1,7 @@
 mod __util__;
 
 use __util__::{POINT_A, POINT_B, basic_tree};
+use kdtree::KdTree;
 use kdtree::distance::squared_euclidean;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
@@ -92,3 +93,18 @@ fn iter_nearest_limits_distance_evaluations() {
     iter.next().unwrap();
     assert_eq!(reset(&counter), 0);
 }
+
+#[test]
+fn coincident_leaf_evaluates_distance_once() {
+    let counter = AtomicUsize::new(0);
+    let distance = countered_distance(&counter);
+    let mut tree = KdTree::with_capacity(2, 2);
+    for i in 0..64 {
+        tree.add(POINT_B.0, i).unwrap();
+    }
+
+    assert_eq!(tree.nearest(&POINT_A.0, 5, &distance).unwrap().len(), 5);
+    assert_eq!(reset(&counter), 1);
+    assert_eq!(tree.within(&POINT_A.0, 2.0, &distance).unwrap().len(), 64);
+    assert_eq!(reset(&counter), 1);
+}
@@ -0,0 +1,69 @@
+mod __util__;
+
+use __util__::{POINT_A, POINT_C, basic_tree};
+use kdtree::distance::squared_euclidean;
+use kdtree::{ErrorKind, KdTree};
+
+#[test]
+fn update_position_moves_matching_entry() {
+    let mut tree = basic_tree();
+    assert_eq!(tree.update_position(&POINT_A.0, [2.5, 2.5], |&d| d == 0), Ok(true));
+    assert_eq!(tree.size(), 4);
+    assert_eq!(tree.get(&POINT_A.0), Ok(None));
+    assert_eq!(tree.get(&[2.5, 2.5]), Ok(Some(&0)));
+    assert_eq!(
+        tree.nearest(&[0.0, 0.0], 1, &squared_euclidean).unwrap(),
+        vec![(2.0, &1)]
+    );
+}
+
+#[test]
+fn update_position_ignores_missing_entries() {
+    let mut tree = basic_tree();
+    assert_eq!(tree.update_position(&POINT_C.0, [9.0, 9.0], |&d| d == 0), Ok(false));
+    assert_eq!(tree.update_position(&[0.5, 0.5], [9.0, 9.0], |_| true), Ok(false));
+    assert_eq!(tree.get(&POINT_C.0), Ok(Some(&2)));
+    assert_eq!(tree.size(), 4);
+    assert_eq!(
+        tree.update_position(&POINT_C.0, [f64::NAN, 0.0], |_| true),
+        Err(ErrorKind::NonFiniteCoordinate)
+    );
+}
+
+#[test]
+fn update_position_splits_coincident_entries() {
+    let mut tree = KdTree::with_capacity(2, 2);
+    for i in 0..6 {
+        tree.add([1.0, 1.0], i).unwrap();
+    }
+    assert_eq!(tree.update_position(&[1.0, 1.0], [1.0, 1.0], |&d| d == 2), Ok(true));
+    assert_eq!(tree.update_position(&[1.0, 1.0], [4.0, 1.0], |&d| d == 3), Ok(true));
+    assert_eq!(tree.size(), 6);
+    assert_eq!(tree.get(&[4.0, 1.0]), Ok(Some(&3)));
+    assert_eq!(tree.within_count(&[1.0, 1.0], 0.0, &squared_euclidean).unwrap(), 5);
+}
+
+#[test]
+fn update_position_matches_brute_force() {
+    let mut tree = KdTree::with_capacity(2, 4);
+    let mut positions: Vec<[f64; 2]> = (0..200).map(|_| rand::random()).collect();
+    for (i, p) in positions.iter().enumerate() {
+        tree.add(*p, i).unwrap();
+    }
+    for step in 0..1000 {
+        let i = step % positions.len();
+        let [x, y] = positions[i];
+        let next = if step % 3 == 0 {
+            rand::random()
+        } else {
+            [x + (rand::random::<f64>() - 0.5) * 0.01, y]
+        };
+        assert_eq!(tree.update_position(&positions[i], next, |&d| d == i), Ok(true));
+        positions[i] = next;
+    }
+    assert_eq!(tree.size(), positions.len());
+    for (i, p) in positions.iter().enumerate() {
+        assert_eq!(tree.nearest(p, 1, &squared_euclidean).unwrap()[0].0, 0.0);
+        assert!(tree.within(p, 0.0, &squared_euclidean).unwrap().contains(&(0.0, &i)));
+    }
+}
@@ -0,0 +1,169 @@
+mod __util__;
+
+use __util__::basic_tree;
+use kdtree::binary::FormatError;
+use kdtree::distance::squared_euclidean;
+use kdtree::view::KdTreeView;
+use kdtree::{ErrorKind, KdTree};
+use rand::Rng;
+
+/// Copies `bytes` to an 8 byte aligned offset of a fresh buffer, optionally shifted by `shift`
+fn aligned(bytes: &[u8], shift: usize) -> (Vec<u8>, std::ops::Range<usize>) {
+    let mut buffer = vec![0u8; bytes.len() + 16];
+    let start = buffer.as_ptr().align_offset(8) + shift;
+    buffer[start..start + bytes.len()].copy_from_slice(bytes);
+    (buffer, start..start + bytes.len())
+}
+
+fn encode<T: kdtree::binary::Payload, U: AsRef<[f64]>>(tree: &KdTree<f64, T, U>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    tree.write_to(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn view_matches_owned_tree() {
+    let mut rng = rand::rng();
+    let mut tree: KdTree<f64, u64, [f64; 3]> = KdTree::with_capacity(3, 8);
+    for i in 0..2000 {
+        // a coarse grid produces plenty of ties and coincident leaves
+        let point = [0; 3].map(|_| (rng.random_range(0..20) as f64) / 20.0);
+        tree.add(point, i).unwrap();
+    }
+    let (buffer, range) = aligned(&encode(&tree), 0);
+    let view: KdTreeView<f64, u64> = KdTreeView::open(&buffer[range]).unwrap();
+    assert_eq!(view.size(), tree.size());
+    assert_eq!(view.dimensions(), 3);
+    assert_eq!(view.capacity(), 8);
+
+    let owned = |results: Vec<(f64, &u64)>| results.into_iter().map(|(d, &i)| (d, i)).collect::<Vec<_>>();
+    for _ in 0..50 {
+        let query = [0; 3].map(|_| rng.random::<f64>());
+        for k in [1, 7, 40] {
+            assert_eq!(
+                view.nearest(&query, k, &squared_euclidean).unwrap(),
+                owned(tree.nearest(&query, k, &squared_euclidean).unwrap())
+            );
+        }
+        assert_eq!(
+            view.within(&query, 0.02, &squared_euclidean).unwrap(),
+            owned(tree.within(&query, 0.02, &squared_euclidean).unwrap())
+        );
+        assert_eq!(
+            view.within_count(&query, 0.05, &squared_euclidean).unwrap(),
+            tree.within_count(&query, 0.05, &squared_euclidean).unwrap()
+        );
+        let upper = query.map(|v| v + 0.2);
+        assert_eq!(
+            view.bounding_box(&query, &upper).unwrap(),
+            tree.bounding_box(&query, &upper)
+                .unwrap()
+                .into_iter()
+                .copied()
+                .collect::<Vec<_>>()
+        );
+    }
+}
+
+#[test]
+fn view_decodes_variable_length_payloads() {
+    let mut tree: KdTree<f64, String, [f64; 2]> = KdTree::with_capacity(2, 2);
+    for i in 0..6 {
+        tree.add([i as f64, 0.0], format!("point {}", i)).unwrap();
+    }
+    let (buffer, range) = aligned(&encode(&tree), 0);
+    let view: KdTreeView<f64, String> = KdTreeView::open(&buffer[range]).unwrap();
+    let expected = tree.nearest(&[4.5, 0.0], 3, &squared_euclidean).unwrap();
+    let found = view.nearest(&[4.5, 0.0], 3, &squared_euclidean).unwrap();
+    assert_eq!(found.len(), 3);
+    for ((d1, s1), (d2, s2)) in found.iter().zip(expected) {
+        assert_eq!((d1, s1), (&d2, s2));
+    }
+}
+
+#[test]
+fn view_of_empty_tree() {
+    let tree: KdTree<f64, u64, [f64; 2]> = KdTree::new(2);
+    let (buffer, range) = aligned(&encode(&tree), 0);
+    let view: KdTreeView<f64, u64> = KdTreeView::open(&buffer[range]).unwrap();
+    assert_eq!(view.nearest(&[0.0, 0.0], 3, &squared_euclidean), Ok(vec![]));
+    assert_eq!(view.within_count(&[0.0, 0.0], 1.0, &squared_euclidean), Ok(0));
+    assert_eq!(view.bounding_box(&[0.0, 0.0], &[1.0, 1.0]), Ok(vec![]));
+}
+
+#[test]
+fn view_rejects_invalid_queries() {
+    let (buffer, range) = aligned(&encode(&basic_tree()), 0);
+    let view: KdTreeView<f64, usize> = KdTreeView::open(&buffer[range]).unwrap();
+    assert_eq!(
+        view.nearest(&[0.0], 1, &squared_euclidean),
+        Err(ErrorKind::WrongDimension)
+    );
+    assert_eq!(
+        view.bounding_box(&[1.0, 0.0], &[0.0, 1.0]),
+        Err(ErrorKind::InvalidBoundingBox { axis: 0 })
+    );
+    assert_eq!(
+        view.nearest(&[0.0, 0.0], 1, &|_: &[f64], _: &[f64]| f64::NAN),
+        Err(ErrorKind::NonFiniteDistance)
+    );
+}
+
+#[test]
+fn view_rejects_misaligned_buffers() {
+    let (buffer, range) = aligned(&encode(&basic_tree()), 1);
+    let result: Result<KdTreeView<f64, usize>, _> = KdTreeView::open(&buffer[range]);
+    assert!(matches!(result, Err(FormatError::Misaligned { required: 8 })));
+}
+
+#[test]
+fn view_validates_structure_and_payloads() {
+    let bytes = encode(&basic_tree());
+    let mut corrupted = bytes.clone();
+    corrupted[68..72].copy_from_slice(&7u32.to_le_bytes());
+    let (buffer, range) = aligned(&corrupted, 0);
+    let result: Result<KdTreeView<f64, usize>, _> = KdTreeView::open_validated(&buffer[range]);
+    assert!(matches!(result, Err(FormatError::Corrupted(_))));
+
+    let (buffer, range) = aligned(&bytes, 0);
+    let result: Result<KdTreeView<f64, bool>, _> = KdTreeView::open_validated(&buffer[range]);
+    assert!(matches!(result, Err(FormatError::Corrupted("malformed payload"))));
+}
+
+#[test]
+fn view_reports_corruption_from_queries() {
+    let bytes = encode(&basic_tree());
+    let mut corrupted = bytes.clone();
+    corrupted[68..72].copy_from_slice(&7u32.to_le_bytes());
+    let (buffer, range) = aligned(&corrupted, 0);
+    let view: KdTreeView<f64, usize> = KdTreeView::open(&buffer[range]).unwrap();
+    assert!(matches!(
+        view.nearest(&[0.0, 0.0], 1, &squared_euclidean),
+        Err(ErrorKind::Corrupted(_))
+    ));
+    assert!(matches!(
+        view.within_count(&[0.0, 0.0], 100.0, &squared_euclidean),
+        Err(ErrorKind::Corrupted(_))
+    ));
+    assert!(matches!(
+        view.bounding_box(&[0.0, 0.0], &[9.0, 9.0]),
+        Err(ErrorKind::Corrupted(_))
+    ));
+
+    // a stem pointing back at the root would loop forever if it was followed
+    let mut cyclic = bytes.clone();
+    cyclic[72..80].copy_from_slice(&0u64.to_le_bytes());
+    let (buffer, range) = aligned(&cyclic, 0);
+    let view: KdTreeView<f64, usize> = KdTreeView::open(&buffer[range]).unwrap();
+    assert!(matches!(
+        view.within(&[0.0, 0.0], 100.0, &squared_euclidean),
+        Err(ErrorKind::Corrupted(_))
+    ));
+
+    let (buffer, range) = aligned(&bytes, 0);
+    let view: KdTreeView<f64, bool> = KdTreeView::open(&buffer[range]).unwrap();
+    assert_eq!(
+        view.nearest(&[0.0, 0.0], 1, &squared_euclidean),
+        Err(ErrorKind::Corrupted("malformed payload"))
+    );
+}